@@ -0,0 +1,289 @@
+use crate::signal::{Sf64, Sfreq, Signal, Trigger};
+use std::{cell::RefCell, f64::consts::PI};
+
+/// The frequency of a partial relative to the fundamental frequency
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartialRatio {
+    /// A whole multiple of the fundamental frequency. The 1st harmonic is the fundamental.
+    Harmonic(usize),
+    /// An arbitrary multiple of the fundamental frequency
+    Ratio(f64),
+}
+
+impl PartialRatio {
+    /// Partials at whole number ratios become harmonics, which are cheaper to compute
+    pub fn from_ratio(ratio: f64) -> Self {
+        if ratio >= 1.0 && ratio.fract() == 0.0 {
+            Self::Harmonic(ratio as usize)
+        } else {
+            Self::Ratio(ratio)
+        }
+    }
+}
+
+/// A fixed set of partials described by their frequency ratios and amplitudes. Use it to build
+/// spectra from formulas and then convert it to `Partial`s for an `AdditiveOscillator`.
+#[derive(Clone, Debug, Default)]
+pub struct Spectrum {
+    /// Pairs of (ratio, amplitude)
+    pub partials: Vec<(f64, f64)>,
+}
+
+impl Spectrum {
+    /// The first `num_harmonics` harmonics, with amplitudes given by a function of the harmonic
+    /// number (starting from 1)
+    pub fn harmonic<F: Fn(usize) -> f64>(num_harmonics: usize, amplitude: F) -> Self {
+        Self {
+            partials: (1..=num_harmonics)
+                .map(|number| (number as f64, amplitude(number)))
+                .collect(),
+        }
+    }
+
+    /// Partials at the given ratios, with amplitudes given by a function of the index of the
+    /// partial (starting from 0) and its ratio
+    pub fn inharmonic<I: IntoIterator<Item = f64>, F: Fn(usize, f64) -> f64>(
+        ratios: I,
+        amplitude: F,
+    ) -> Self {
+        Self {
+            partials: ratios
+                .into_iter()
+                .enumerate()
+                .map(|(i, ratio)| (ratio, amplitude(i, ratio)))
+                .collect(),
+        }
+    }
+
+    /// Fourier series of a saw wave rising from -1 to 1, in phase with `Waveform::Saw`
+    pub fn saw(num_harmonics: usize) -> Self {
+        Self::harmonic(num_harmonics, |n| -2.0 / (PI * n as f64))
+    }
+
+    /// Fourier series of a square wave between -1 and 1, in phase with `Waveform::Pulse` with
+    /// a pulse width of 0.5. Only odd harmonics are present.
+    pub fn square(num_harmonics: usize) -> Self {
+        Self::harmonic(num_harmonics, |n| {
+            if n % 2 == 1 {
+                -4.0 / (PI * n as f64)
+            } else {
+                0.0
+            }
+        })
+        .without_silent_partials()
+    }
+
+    /// Fourier series of a triangle wave between -1 and 1, starting from 0 and rising. Only odd
+    /// harmonics are present.
+    pub fn triangle(num_harmonics: usize) -> Self {
+        Self::harmonic(num_harmonics, |n| {
+            if n % 2 == 1 {
+                let sign = if (n / 2) % 2 == 0 { 1.0 } else { -1.0 };
+                sign * 8.0 / (PI * PI * (n * n) as f64)
+            } else {
+                0.0
+            }
+        })
+        .without_silent_partials()
+    }
+
+    /// Harmonics whose frequencies are stretched by the stiffness of a string, as in a
+    /// piano. The nth partial is at n * sqrt(1 + inharmonicity * n^2) times the fundamental.
+    pub fn stiff_string(num_partials: usize, inharmonicity: f64) -> Self {
+        Self::inharmonic(
+            (1..=num_partials).map(|n| {
+                let n = n as f64;
+                n * (1.0 + (inharmonicity * n * n)).sqrt()
+            }),
+            |i, _| 1.0 / (i + 1) as f64,
+        )
+    }
+
+    /// The partials of a church bell, named relative to the strike note: hum, prime, tierce,
+    /// quint, nominal and the partials above it
+    pub fn bell() -> Self {
+        const RATIOS_AND_AMPLITUDES: &[(f64, f64)] = &[
+            (0.5, 0.6),
+            (1.0, 0.8),
+            (1.183, 0.6),
+            (1.506, 0.3),
+            (2.0, 1.0),
+            (2.514, 0.3),
+            (2.662, 0.25),
+            (3.011, 0.35),
+            (4.166, 0.2),
+            (5.433, 0.12),
+            (6.796, 0.08),
+        ];
+        Self {
+            partials: RATIOS_AND_AMPLITUDES.to_vec(),
+        }
+    }
+
+    /// Remove partials with an amplitude of 0
+    pub fn without_silent_partials(mut self) -> Self {
+        self.partials.retain(|&(_, amplitude)| amplitude != 0.0);
+        self
+    }
+
+    /// Multiply all amplitudes so that the loudest partial has the given amplitude
+    pub fn normalize(mut self, max_amplitude: f64) -> Self {
+        let loudest = self
+            .partials
+            .iter()
+            .fold(0.0f64, |acc, &(_, amplitude)| acc.max(amplitude.abs()));
+        if loudest > 0.0 {
+            for (_, amplitude) in self.partials.iter_mut() {
+                *amplitude *= max_amplitude / loudest;
+            }
+        }
+        self
+    }
+
+    /// The frequency ratio of each partial
+    pub fn ratios(&self) -> Vec<PartialRatio> {
+        self.partials
+            .iter()
+            .map(|&(ratio, _)| PartialRatio::from_ratio(ratio))
+            .collect()
+    }
+
+    /// The amplitude of each partial, in the same order as `ratios`
+    pub fn amplitudes(&self) -> Vec<f64> {
+        self.partials
+            .iter()
+            .map(|&(_, amplitude)| amplitude)
+            .collect()
+    }
+}
+
+pub struct AdditiveOscillator {
+    pub freq: Sfreq,
+    /// The frequency ratio of each partial
+    pub ratios: Vec<PartialRatio>,
+    /// The amplitude of each partial, in the same order as `ratios`. Partials beyond the end of
+    /// the amplitudes are silent.
+    pub amplitudes: Signal<Vec<f64>>,
+    /// Resets the phase of every partial to 0
+    pub reset_trigger: Trigger,
+}
+
+/// Partials are faded out as they approach the nyquist frequency rather than cut off abruptly,
+/// so that sweeping the frequency doesn't click. This is the width of the fade as a fraction of
+/// the nyquist frequency.
+const NYQUIST_FADE_WIDTH_01: f64 = 0.1;
+
+fn nyquist_gain(partial_freq_hz: f64, sample_rate_hz: f64) -> f64 {
+    let nyquist_hz = sample_rate_hz / 2.0;
+    ((nyquist_hz - partial_freq_hz.abs()) / (nyquist_hz * NYQUIST_FADE_WIDTH_01)).clamp(0.0, 1.0)
+}
+
+struct AdditiveState {
+    /// Phase of the fundamental, shared by all harmonics
+    phase_01: f64,
+    /// Phase of each inharmonic partial
+    inharmonic_phases_01: Vec<f64>,
+    /// (harmonic number, index of amplitude) of each harmonic partial, sorted by harmonic number
+    harmonics: Vec<(usize, usize)>,
+    /// (ratio, index of amplitude) of each inharmonic partial
+    inharmonics: Vec<(f64, usize)>,
+}
+
+impl AdditiveOscillator {
+    /// All partials are computed by a single signal. Harmonics share one phase accumulator
+    /// and are computed from it with the recurrence sin((n+1)x) = 2cos(x)sin(nx) - sin((n-1)x),
+    /// so a sine only needs to be evaluated once per sample for the whole harmonic series.
+    pub fn signal(self) -> Sf64 {
+        let mut harmonics = Vec::new();
+        let mut inharmonics = Vec::new();
+        for (i, ratio) in self.ratios.into_iter().enumerate() {
+            match ratio {
+                PartialRatio::Harmonic(0) => (),
+                PartialRatio::Harmonic(number) => harmonics.push((number, i)),
+                PartialRatio::Ratio(ratio) => inharmonics.push((ratio, i)),
+            }
+        }
+        harmonics.sort_by_key(|&(number, _)| number);
+        let state = RefCell::new(AdditiveState {
+            phase_01: 0.0,
+            inharmonic_phases_01: vec![0.0; inharmonics.len()],
+            harmonics,
+            inharmonics,
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let freq_hz = self.freq.sample(ctx).hz();
+            let amplitudes = self.amplitudes.sample(ctx);
+            let amplitude = |i: usize| amplitudes.get(i).copied().unwrap_or(0.0);
+            if self.reset_trigger.sample(ctx) {
+                state.phase_01 = 0.0;
+                state.inharmonic_phases_01.fill(0.0);
+            }
+            let mut output = 0.0;
+            let theta = state.phase_01 * 2.0 * PI;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            // sin((n-1) * theta) and sin(n * theta)
+            let mut sin_prev = 0.0;
+            let mut sin_current = sin_theta;
+            let mut n = 1;
+            for &(number, i) in state.harmonics.iter() {
+                let gain = nyquist_gain(freq_hz * number as f64, ctx.sample_rate_hz);
+                if gain == 0.0 {
+                    // Harmonics are sorted so all remaining harmonics are also too high
+                    break;
+                }
+                while n < number {
+                    let sin_next = (2.0 * cos_theta * sin_current) - sin_prev;
+                    sin_prev = sin_current;
+                    sin_current = sin_next;
+                    n += 1;
+                }
+                output += sin_current * amplitude(i) * gain;
+            }
+            let AdditiveState {
+                inharmonic_phases_01,
+                inharmonics,
+                ..
+            } = &mut *state;
+            for (&(ratio, i), phase_01) in inharmonics.iter().zip(inharmonic_phases_01.iter_mut()) {
+                let partial_freq_hz = freq_hz * ratio;
+                let gain = nyquist_gain(partial_freq_hz, ctx.sample_rate_hz);
+                if gain > 0.0 {
+                    output += (*phase_01 * 2.0 * PI).sin() * amplitude(i) * gain;
+                }
+                *phase_01 = (*phase_01 + (partial_freq_hz / ctx.sample_rate_hz)).rem_euclid(1.0);
+            }
+            state.phase_01 = (state.phase_01 + (freq_hz / ctx.sample_rate_hz)).rem_euclid(1.0);
+            output
+        })
+    }
+}
+
+#[test]
+fn test_additive_oscillator_amplitudes() {
+    use crate::{builder::additive::additive_hz, signal::SignalCtx};
+    let sample_rate_hz = 1000.0;
+    let amplitudes = Signal::from_fn(|ctx| vec![1.0, ctx.sample_index as f64 / 1000.0, 0.5]);
+    let signal = additive_hz(100.0)
+        .harmonics(2)
+        .inharmonics([1.5])
+        .amplitudes(amplitudes)
+        .build();
+    for sample_index in 0..1000 {
+        let t = sample_index as f64 / sample_rate_hz;
+        let expected = (2.0 * PI * 100.0 * t).sin()
+            + ((sample_index as f64 / 1000.0) * (2.0 * PI * 200.0 * t).sin())
+            + (0.5 * (2.0 * PI * 150.0 * t).sin());
+        let actual = signal.sample(&SignalCtx {
+            sample_index,
+            sample_rate_hz,
+        });
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "sample {}: {} != {}",
+            sample_index,
+            actual,
+            expected
+        );
+    }
+}
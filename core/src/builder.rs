@@ -72,7 +72,9 @@ pub mod env {
 
 pub mod oscillator {
     use crate::{
-        oscillator::{Oscillator, Waveform},
+        oscillator::{
+            Oscillator, OscillatorOutputs, SubOscillator, SubOscillatorWaveform, Waveform,
+        },
         signal::{const_, sfreq_hz, sfreq_s, Sf64, Sfreq, Signal, Trigger},
     };
 
@@ -82,6 +84,11 @@ pub mod oscillator {
         pulse_width_01: Option<Sf64>,
         reset_trigger: Option<Trigger>,
         reset_offset_01: Option<Sf64>,
+        hard_sync_phase_01: Option<Sf64>,
+        sub_oscillator: Option<(SubOscillatorWaveform, u8)>,
+        sub_level_01: Option<Sf64>,
+        linear_fm_hz: Option<Sf64>,
+        exponential_fm_octaves: Option<Sf64>,
     }
 
     impl OscillatorBuilder {
@@ -92,6 +99,11 @@ pub mod oscillator {
                 pulse_width_01: None,
                 reset_trigger: None,
                 reset_offset_01: None,
+                hard_sync_phase_01: None,
+                sub_oscillator: None,
+                sub_level_01: None,
+                linear_fm_hz: None,
+                exponential_fm_octaves: None,
             }
         }
 
@@ -110,15 +122,67 @@ pub mod oscillator {
             self
         }
 
-        pub fn build(self) -> Sf64 {
+        /// Reset the phase of this oscillator each time the phase of a master oscillator wraps
+        /// around. Get the phase of the master oscillator with `build_outputs`.
+        pub fn hard_sync(mut self, master_phase_01: impl Into<Sf64>) -> Self {
+            self.hard_sync_phase_01 = Some(master_phase_01.into());
+            self
+        }
+
+        /// Add a sub oscillator `octaves_below` octaves below this oscillator
+        pub fn sub_oscillator(
+            mut self,
+            waveform: SubOscillatorWaveform,
+            octaves_below: u8,
+        ) -> Self {
+            self.sub_oscillator = Some((waveform, octaves_below));
+            self
+        }
+
+        pub fn sub_level_01(mut self, sub_level_01: impl Into<Sf64>) -> Self {
+            self.sub_level_01 = Some(sub_level_01.into());
+            self
+        }
+
+        /// Add a signal in Hz to the oscillator's frequency. The frequency may go below 0 in
+        /// which case the oscillator runs backwards (through-zero FM). To modulate by another
+        /// oscillator, scale its output by the modulation depth in Hz.
+        pub fn linear_fm_hz(mut self, linear_fm_hz: impl Into<Sf64>) -> Self {
+            self.linear_fm_hz = Some(linear_fm_hz.into());
+            self
+        }
+
+        /// Raise the oscillator's frequency by this many octaves
+        pub fn exponential_fm_octaves(mut self, exponential_fm_octaves: impl Into<Sf64>) -> Self {
+            self.exponential_fm_octaves = Some(exponential_fm_octaves.into());
+            self
+        }
+
+        /// Build the oscillator's signal along with its phase and sub oscillator
+        pub fn build_outputs(self) -> OscillatorOutputs {
+            let sub_level_01 = self.sub_level_01.unwrap_or_else(|| const_(0.5));
             Oscillator {
                 waveform: self.waveform,
                 freq: self.freq,
                 pulse_width_01: self.pulse_width_01.unwrap_or_else(|| const_(0.5)),
                 reset_trigger: self.reset_trigger.unwrap_or_else(|| Trigger::never()),
                 reset_offset_01: self.reset_offset_01.unwrap_or_else(|| const_(0.0)),
+                hard_sync_phase_01: self.hard_sync_phase_01,
+                sub_oscillator: self.sub_oscillator.map(|(waveform, octaves_below)| {
+                    SubOscillator {
+                        waveform,
+                        octaves_below,
+                        level_01: sub_level_01,
+                    }
+                }),
+                linear_fm_hz: self.linear_fm_hz.unwrap_or_else(|| const_(0.0)),
+                exponential_fm_octaves: self.exponential_fm_octaves.unwrap_or_else(|| const_(0.0)),
             }
-            .signal()
+            .outputs()
+        }
+
+        pub fn build(self) -> Sf64 {
+            self.build_outputs().signal
         }
     }
 
@@ -144,10 +208,198 @@ pub mod oscillator {
     }
 }
 
+pub mod additive {
+    use crate::{
+        additive::{AdditiveOscillator, PartialRatio, Spectrum},
+        signal::{const_, sfreq_hz, Sf64, Sfreq, Signal, Trigger},
+    };
+
+    pub struct AdditiveOscillatorBuilder {
+        freq: Sfreq,
+        ratios: Vec<PartialRatio>,
+        amplitudes: Option<Signal<Vec<f64>>>,
+        reset_trigger: Option<Trigger>,
+    }
+
+    impl AdditiveOscillatorBuilder {
+        pub fn new(freq: impl Into<Sfreq>) -> Self {
+            Self {
+                freq: freq.into(),
+                ratios: Vec::new(),
+                amplitudes: None,
+                reset_trigger: None,
+            }
+        }
+
+        pub fn ratio(mut self, ratio: PartialRatio) -> Self {
+            self.ratios.push(ratio);
+            self
+        }
+
+        pub fn ratios(mut self, ratios: impl IntoIterator<Item = PartialRatio>) -> Self {
+            self.ratios.extend(ratios);
+            self
+        }
+
+        /// Add the first `num_harmonics` harmonics, starting from the fundamental
+        pub fn harmonics(self, num_harmonics: usize) -> Self {
+            self.ratios((1..=num_harmonics).map(PartialRatio::Harmonic))
+        }
+
+        /// Add partials at the given ratios of the fundamental frequency
+        pub fn inharmonics(self, ratios: impl IntoIterator<Item = f64>) -> Self {
+            self.ratios(ratios.into_iter().map(PartialRatio::Ratio))
+        }
+
+        /// The amplitude of each partial, in the order the partials were added. Defaults to 1
+        /// for every partial.
+        pub fn amplitudes(mut self, amplitudes: impl Into<Signal<Vec<f64>>>) -> Self {
+            self.amplitudes = Some(amplitudes.into());
+            self
+        }
+
+        /// Use the partials of the spectrum, with constant amplitudes
+        pub fn spectrum(self, spectrum: &Spectrum) -> Self {
+            self.ratios(spectrum.ratios())
+                .amplitudes(const_(spectrum.amplitudes()))
+        }
+
+        pub fn reset_trigger(mut self, reset_trigger: impl Into<Trigger>) -> Self {
+            self.reset_trigger = Some(reset_trigger.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            let num_partials = self.ratios.len();
+            AdditiveOscillator {
+                freq: self.freq,
+                ratios: self.ratios,
+                amplitudes: self
+                    .amplitudes
+                    .unwrap_or_else(|| const_(vec![1.0; num_partials])),
+                reset_trigger: self.reset_trigger.unwrap_or_else(Trigger::never),
+            }
+            .signal()
+        }
+    }
+
+    pub fn additive(freq: impl Into<Sfreq>) -> AdditiveOscillatorBuilder {
+        AdditiveOscillatorBuilder::new(freq)
+    }
+
+    pub fn additive_hz(freq_hz: impl Into<Sf64>) -> AdditiveOscillatorBuilder {
+        additive(sfreq_hz(freq_hz))
+    }
+}
+
+pub mod lfo {
+    use crate::{
+        lfo::{Lfo, LfoPolarity, LfoShape},
+        signal::{const_, sfreq_hz, Sf64, Sfreq, Signal, Trigger},
+    };
+
+    pub struct LfoBuilder {
+        shape: Signal<LfoShape>,
+        freq: Option<Sfreq>,
+        polarity: Option<LfoPolarity>,
+        phase_offset_01: Option<Sf64>,
+        fade_in_s: Option<Sf64>,
+        retrigger: Option<Trigger>,
+        one_shot: Option<bool>,
+    }
+
+    impl LfoBuilder {
+        pub fn new(shape: impl Into<Signal<LfoShape>>) -> Self {
+            Self {
+                shape: shape.into(),
+                freq: None,
+                polarity: None,
+                phase_offset_01: None,
+                fade_in_s: None,
+                retrigger: None,
+                one_shot: None,
+            }
+        }
+
+        pub fn freq(mut self, freq: impl Into<Sfreq>) -> Self {
+            self.freq = Some(freq.into());
+            self
+        }
+
+        pub fn freq_hz(mut self, freq_hz: impl Into<Sf64>) -> Self {
+            self.freq = Some(sfreq_hz(freq_hz));
+            self
+        }
+
+        /// Set the rate so that each cycle lasts `beats_per_cycle` beats at the given tempo.
+        /// E.g. 0.25 for a cycle every 16th note or 4.0 for a cycle every bar of 4/4.
+        pub fn tempo_sync(
+            mut self,
+            bpm: impl Into<Sf64>,
+            beats_per_cycle: impl Into<Sf64>,
+        ) -> Self {
+            let bpm = bpm.into();
+            let beats_per_cycle = beats_per_cycle.into();
+            self.freq = Some(sfreq_hz(
+                bpm.zip(&beats_per_cycle)
+                    .map(|(bpm, beats_per_cycle)| bpm / (60.0 * beats_per_cycle)),
+            ));
+            self
+        }
+
+        pub fn polarity(mut self, polarity: LfoPolarity) -> Self {
+            self.polarity = Some(polarity);
+            self
+        }
+
+        pub fn unipolar(self) -> Self {
+            self.polarity(LfoPolarity::Unipolar)
+        }
+
+        pub fn phase_offset_01(mut self, phase_offset_01: impl Into<Sf64>) -> Self {
+            self.phase_offset_01 = Some(phase_offset_01.into());
+            self
+        }
+
+        pub fn fade_in_s(mut self, fade_in_s: impl Into<Sf64>) -> Self {
+            self.fade_in_s = Some(fade_in_s.into());
+            self
+        }
+
+        pub fn retrigger(mut self, retrigger: impl Into<Trigger>) -> Self {
+            self.retrigger = Some(retrigger.into());
+            self
+        }
+
+        pub fn one_shot(mut self, one_shot: bool) -> Self {
+            self.one_shot = Some(one_shot);
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            Lfo {
+                shape: self.shape,
+                freq: self.freq.unwrap_or_else(|| sfreq_hz(1.0)),
+                polarity: self.polarity.unwrap_or_default(),
+                phase_offset_01: self.phase_offset_01.unwrap_or_else(|| const_(0.0)),
+                fade_in_s: self.fade_in_s.unwrap_or_else(|| const_(0.0)),
+                retrigger: self.retrigger.unwrap_or_else(Trigger::never),
+                one_shot: self.one_shot.unwrap_or(false),
+            }
+            .signal()
+        }
+    }
+
+    pub fn lfo(shape: impl Into<Signal<LfoShape>>) -> LfoBuilder {
+        LfoBuilder::new(shape)
+    }
+}
+
 pub mod gate {
     use crate::{
         clock::{PeriodicGate, PeriodicTrigger},
         signal::{const_, sfreq_hz, sfreq_s, Gate, Sf64, Sfreq, Trigger},
+        tap_tempo::{TapTempo, TapTempoOutput},
     };
 
     pub struct PeriodicGateBuilder {
@@ -220,6 +472,66 @@ pub mod gate {
     pub fn periodic_trigger_s(freq_s: impl Into<Sf64>) -> PeriodicTriggerBuilder {
         PeriodicTriggerBuilder::new(sfreq_s(freq_s))
     }
+
+    pub struct TapTempoBuilder {
+        tap: Trigger,
+        initial_bpm: Option<f64>,
+        timeout_s: Option<Sf64>,
+        outlier_tolerance_01: Option<Sf64>,
+        history_length: Option<usize>,
+    }
+
+    impl TapTempoBuilder {
+        pub fn new(tap: impl Into<Trigger>) -> Self {
+            Self {
+                tap: tap.into(),
+                initial_bpm: None,
+                timeout_s: None,
+                outlier_tolerance_01: None,
+                history_length: None,
+            }
+        }
+
+        pub fn initial_bpm(mut self, initial_bpm: f64) -> Self {
+            self.initial_bpm = Some(initial_bpm);
+            self
+        }
+
+        pub fn timeout_s(mut self, timeout_s: impl Into<Sf64>) -> Self {
+            self.timeout_s = Some(timeout_s.into());
+            self
+        }
+
+        pub fn outlier_tolerance_01(mut self, outlier_tolerance_01: impl Into<Sf64>) -> Self {
+            self.outlier_tolerance_01 = Some(outlier_tolerance_01.into());
+            self
+        }
+
+        pub fn history_length(mut self, history_length: usize) -> Self {
+            self.history_length = Some(history_length);
+            self
+        }
+
+        pub fn build(self) -> TapTempoOutput {
+            TapTempo {
+                tap: self.tap,
+                initial_bpm: self.initial_bpm.unwrap_or(120.0),
+                timeout_s: self.timeout_s.unwrap_or_else(|| const_(2.0)),
+                outlier_tolerance_01: self.outlier_tolerance_01.unwrap_or_else(|| const_(0.3)),
+                history_length: self.history_length.unwrap_or(4),
+            }
+            .output()
+        }
+    }
+
+    pub fn tap_tempo(tap: impl Into<Trigger>) -> TapTempoBuilder {
+        TapTempoBuilder::new(tap)
+    }
+
+    /// Tap the tempo by pressing a key (or any other gate)
+    pub fn tap_tempo_gate(tap: impl Into<Gate>) -> TapTempoBuilder {
+        TapTempoBuilder::new(tap.into().to_trigger_rising_edge())
+    }
 }
 
 pub mod filter {
@@ -589,8 +901,9 @@ pub mod filter {
 
 pub mod loopers {
     use crate::{
+        keyboard::KeyEvent,
         loopers::*,
-        signal::{const_, Gate, Su8, Trigger},
+        signal::{const_, Gate, Sf64, Signal, Su8, Trigger},
     };
 
     pub struct ClockedTriggerLooperBuilder {
@@ -598,6 +911,7 @@ pub mod loopers {
         add: Option<Gate>,
         remove: Option<Gate>,
         length: Option<usize>,
+        sequence: Option<LooperSequence<bool>>,
     }
 
     impl ClockedTriggerLooperBuilder {
@@ -607,6 +921,7 @@ pub mod loopers {
                 add: None,
                 remove: None,
                 length: None,
+                sequence: None,
             }
         }
 
@@ -630,12 +945,21 @@ pub mod loopers {
             self
         }
 
+        /// Use an existing sequence, such as one loaded from a file. The length of the looper is
+        /// determined by the sequence, and any length set with `length` is ignored.
+        pub fn sequence(mut self, sequence: &LooperSequence<bool>) -> Self {
+            self.sequence = Some(sequence.clone());
+            self
+        }
+
         pub fn build(self) -> Trigger {
             ClockedTriggerLooper {
                 clock: self.clock.unwrap_or_else(|| Trigger::never()),
                 add: self.add.unwrap_or_else(|| Gate::never()),
                 remove: self.remove.unwrap_or_else(|| Gate::never()),
-                length: self.length.unwrap_or(8),
+                sequence: self
+                    .sequence
+                    .unwrap_or_else(|| LooperSequence::new_empty(self.length.unwrap_or(8))),
             }
             .trigger()
         }
@@ -647,6 +971,7 @@ pub mod loopers {
         input_midi_index: Option<Su8>,
         clear: Option<Gate>,
         length: Option<usize>,
+        sequence: Option<LooperSequence<MidiNoteLooperEntry>>,
     }
 
     impl ClockedMidiNoteMonophonicLooperBuilder {
@@ -657,6 +982,7 @@ pub mod loopers {
                 input_midi_index: None,
                 clear: None,
                 length: None,
+                sequence: None,
             }
         }
 
@@ -685,156 +1011,690 @@ pub mod loopers {
             self
         }
 
+        /// Use an existing sequence, such as one loaded from a file. The length of the looper is
+        /// determined by the sequence, and any length set with `length` is ignored.
+        pub fn sequence(mut self, sequence: &LooperSequence<MidiNoteLooperEntry>) -> Self {
+            self.sequence = Some(sequence.clone());
+            self
+        }
+
         pub fn build(self) -> (Gate, Su8) {
             ClockedMidiNoteMonophonicLooper {
                 clock: self.clock.unwrap_or_else(|| Trigger::never()),
                 input_gate: self.input_gate.unwrap_or_else(|| Gate::never()),
                 input_midi_index: self.input_midi_index.unwrap_or_else(|| const_(0)),
                 clear: self.clear.unwrap_or_else(|| Gate::never()),
-                length: self.length.unwrap_or(8),
+                sequence: self
+                    .sequence
+                    .unwrap_or_else(|| LooperSequence::new_empty(self.length.unwrap_or(8))),
             }
             .signal()
         }
     }
 
-    pub fn clocked_trigger_looper() -> ClockedTriggerLooperBuilder {
-        ClockedTriggerLooperBuilder::new()
-    }
-
-    pub fn clocked_midi_note_monophonic_looper() -> ClockedMidiNoteMonophonicLooperBuilder {
-        ClockedMidiNoteMonophonicLooperBuilder::new()
+    pub struct ClockedKeyEventLooperBuilder {
+        clock: Option<Trigger>,
+        input: Option<Signal<Vec<KeyEvent>>>,
+        record: Option<Gate>,
+        erase: Option<Gate>,
+        undo: Option<Trigger>,
+        length: Option<usize>,
+        quantize_01: Option<Sf64>,
     }
-}
-
-pub mod sampler {
-    pub use crate::sampler::{Sample, Sampler};
-    use crate::signal::{Sf64, Trigger};
 
-    pub struct SamplerBuilder<'a> {
-        sample: &'a Sample,
-        trigger: Option<Trigger>,
+    impl Default for ClockedKeyEventLooperBuilder {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
-    impl<'a> SamplerBuilder<'a> {
-        pub fn new(sample: &'a Sample) -> Self {
+    impl ClockedKeyEventLooperBuilder {
+        pub fn new() -> Self {
             Self {
-                sample,
-                trigger: None,
+                clock: None,
+                input: None,
+                record: None,
+                erase: None,
+                undo: None,
+                length: None,
+                quantize_01: None,
             }
         }
 
-        pub fn trigger(mut self, trigger: impl Into<Trigger>) -> Self {
-            self.trigger = Some(trigger.into());
+        pub fn clock(mut self, clock: impl Into<Trigger>) -> Self {
+            self.clock = Some(clock.into());
             self
         }
 
-        pub fn build(self) -> Sf64 {
-            Sampler::new(self.sample, self.trigger.unwrap_or_else(Trigger::never)).signal()
+        pub fn input(mut self, input: impl Into<Signal<Vec<KeyEvent>>>) -> Self {
+            self.input = Some(input.into());
+            self
         }
-    }
-
-    pub fn sampler(sample: &Sample) -> SamplerBuilder {
-        SamplerBuilder::new(sample)
-    }
-}
-
-pub mod patches {
-    use crate::{
-        patches,
-        signal::{const_, sfreq_hz, Sf64, Sfreq, Trigger},
-    };
-
-    pub struct SupersawBuilder {
-        resolution: Option<usize>,
-        freq: Sfreq,
-        ratio: Option<Sf64>,
-        reset_trigger: Option<Trigger>,
-        reset_offset_01: Option<Sf64>,
-    }
 
-    impl SupersawBuilder {
-        pub fn new(freq: impl Into<Sfreq>) -> Self {
-            Self {
-                resolution: None,
-                freq: freq.into(),
-                ratio: None,
-                reset_trigger: None,
-                reset_offset_01: None,
-            }
+        pub fn record(mut self, record: impl Into<Gate>) -> Self {
+            self.record = Some(record.into());
+            self
         }
 
-        pub fn resolution(mut self, resolution: impl Into<usize>) -> Self {
-            self.resolution = Some(resolution.into());
+        pub fn erase(mut self, erase: impl Into<Gate>) -> Self {
+            self.erase = Some(erase.into());
             self
         }
 
-        pub fn ratio(mut self, ratio: impl Into<Sf64>) -> Self {
-            self.ratio = Some(ratio.into());
+        pub fn undo(mut self, undo: impl Into<Trigger>) -> Self {
+            self.undo = Some(undo.into());
             self
         }
 
-        pub fn reset_trigger(mut self, reset_trigger: impl Into<Trigger>) -> Self {
-            self.reset_trigger = Some(reset_trigger.into());
+        pub fn length(mut self, length: usize) -> Self {
+            self.length = Some(length);
             self
         }
 
-        pub fn reset_offset_01(mut self, reset_offset_01: impl Into<Sf64>) -> Self {
-            self.reset_offset_01 = Some(reset_offset_01.into());
+        pub fn quantize_01(mut self, quantize_01: impl Into<Sf64>) -> Self {
+            self.quantize_01 = Some(quantize_01.into());
             self
         }
 
-        pub fn build(self) -> Sf64 {
-            patches::supersaw(
-                self.resolution.unwrap_or(1),
-                self.freq,
-                self.ratio.unwrap_or_else(|| const_(0.01)),
-                self.reset_trigger.unwrap_or_else(|| Trigger::never()),
-                self.reset_offset_01.unwrap_or_else(|| const_(0.0)),
-            )
+        pub fn build(self) -> Signal<Vec<KeyEvent>> {
+            ClockedKeyEventLooper {
+                clock: self.clock.unwrap_or_else(Trigger::never),
+                input: self.input.unwrap_or_else(|| const_(Vec::new())),
+                record: self.record.unwrap_or_else(Gate::never),
+                erase: self.erase.unwrap_or_else(Gate::never),
+                undo: self.undo.unwrap_or_else(Trigger::never),
+                length: self.length.unwrap_or(8),
+                quantize_01: self.quantize_01.unwrap_or_else(|| const_(0.0)),
+            }
+            .signal()
         }
     }
 
-    pub fn supersaw(freq: impl Into<Sfreq>) -> SupersawBuilder {
-        SupersawBuilder::new(freq)
-    }
-
-    pub fn supersaw_hz(freq_hz: impl Into<Sf64>) -> SupersawBuilder {
-        supersaw(sfreq_hz(freq_hz))
-    }
-
-    pub struct PulsePwmBuilder {
-        osc_freq: Sfreq,
-        pwm_freq: Option<Sfreq>,
-        offset_01: Option<Sf64>,
-        scale_01: Option<Sf64>,
-        reset_trigger: Option<Trigger>,
-        reset_offset_01: Option<Sf64>,
+    pub struct ClockedAudioLooperBuilder {
+        clock: Option<Trigger>,
+        input: Sf64,
+        record: Option<Gate>,
+        play: Option<Gate>,
+        overdub: Option<Gate>,
+        clear: Option<Gate>,
+        multiply: Option<Trigger>,
+        feedback_01: Option<Sf64>,
+        half_speed: Option<Gate>,
+        reverse: Option<Gate>,
+        crossfade_s: Option<f64>,
     }
 
-    impl PulsePwmBuilder {
-        pub fn new(osc_freq: impl Into<Sfreq>) -> Self {
+    impl ClockedAudioLooperBuilder {
+        pub fn new(input: impl Into<Sf64>) -> Self {
             Self {
-                osc_freq: osc_freq.into(),
-                pwm_freq: None,
-                offset_01: None,
-                scale_01: None,
-                reset_trigger: None,
-                reset_offset_01: None,
+                clock: None,
+                input: input.into(),
+                record: None,
+                play: None,
+                overdub: None,
+                clear: None,
+                multiply: None,
+                feedback_01: None,
+                half_speed: None,
+                reverse: None,
+                crossfade_s: None,
             }
         }
 
-        pub fn pwm_freq(mut self, pwm_freq: impl Into<Sfreq>) -> Self {
-            self.pwm_freq = Some(pwm_freq.into());
+        pub fn clock(mut self, clock: impl Into<Trigger>) -> Self {
+            self.clock = Some(clock.into());
             self
         }
 
-        pub fn offset_01(mut self, offset_01: impl Into<Sf64>) -> Self {
-            self.offset_01 = Some(offset_01.into());
+        pub fn record(mut self, record: impl Into<Gate>) -> Self {
+            self.record = Some(record.into());
             self
         }
 
-        pub fn scale_01(mut self, scale_01: impl Into<Sf64>) -> Self {
-            self.scale_01 = Some(scale_01.into());
+        pub fn play(mut self, play: impl Into<Gate>) -> Self {
+            self.play = Some(play.into());
+            self
+        }
+
+        pub fn overdub(mut self, overdub: impl Into<Gate>) -> Self {
+            self.overdub = Some(overdub.into());
+            self
+        }
+
+        pub fn clear(mut self, clear: impl Into<Gate>) -> Self {
+            self.clear = Some(clear.into());
+            self
+        }
+
+        pub fn multiply(mut self, multiply: impl Into<Trigger>) -> Self {
+            self.multiply = Some(multiply.into());
+            self
+        }
+
+        pub fn feedback_01(mut self, feedback_01: impl Into<Sf64>) -> Self {
+            self.feedback_01 = Some(feedback_01.into());
+            self
+        }
+
+        pub fn half_speed(mut self, half_speed: impl Into<Gate>) -> Self {
+            self.half_speed = Some(half_speed.into());
+            self
+        }
+
+        pub fn reverse(mut self, reverse: impl Into<Gate>) -> Self {
+            self.reverse = Some(reverse.into());
+            self
+        }
+
+        pub fn crossfade_s(mut self, crossfade_s: f64) -> Self {
+            self.crossfade_s = Some(crossfade_s);
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            ClockedAudioLooper {
+                clock: self.clock.unwrap_or_else(Trigger::never),
+                input: self.input,
+                record: self.record.unwrap_or_else(Gate::never),
+                play: self.play.unwrap_or_else(|| const_(true).to_gate()),
+                overdub: self.overdub.unwrap_or_else(Gate::never),
+                clear: self.clear.unwrap_or_else(Gate::never),
+                multiply: self.multiply.unwrap_or_else(Trigger::never),
+                feedback_01: self.feedback_01.unwrap_or_else(|| const_(1.0)),
+                half_speed: self.half_speed.unwrap_or_else(Gate::never),
+                reverse: self.reverse.unwrap_or_else(Gate::never),
+                crossfade_s: self.crossfade_s.unwrap_or(0.005),
+            }
+            .signal()
+        }
+    }
+
+    pub fn clocked_trigger_looper() -> ClockedTriggerLooperBuilder {
+        ClockedTriggerLooperBuilder::new()
+    }
+
+    pub fn clocked_midi_note_monophonic_looper() -> ClockedMidiNoteMonophonicLooperBuilder {
+        ClockedMidiNoteMonophonicLooperBuilder::new()
+    }
+
+    pub fn clocked_key_event_looper() -> ClockedKeyEventLooperBuilder {
+        ClockedKeyEventLooperBuilder::new()
+    }
+
+    pub fn clocked_audio_looper(input: impl Into<Sf64>) -> ClockedAudioLooperBuilder {
+        ClockedAudioLooperBuilder::new(input)
+    }
+}
+
+pub mod sequencers {
+    use crate::{
+        sequencers::*,
+        signal::{Gate, Trigger},
+    };
+
+    pub struct ArrangementBuilder {
+        step: Trigger,
+        steps_per_bar: Option<usize>,
+        patterns: Vec<ArrangementPattern>,
+        song: Vec<SongEntry>,
+        scenes: Vec<Scene>,
+    }
+
+    impl ArrangementBuilder {
+        pub fn new(step: impl Into<Trigger>) -> Self {
+            Self {
+                step: step.into(),
+                steps_per_bar: None,
+                patterns: Vec::new(),
+                song: Vec::new(),
+                scenes: Vec::new(),
+            }
+        }
+
+        pub fn steps_per_bar(mut self, steps_per_bar: usize) -> Self {
+            self.steps_per_bar = Some(steps_per_bar);
+            self
+        }
+
+        pub fn pattern(mut self, name: impl Into<String>, steps: Vec<u8>) -> Self {
+            self.patterns.push(ArrangementPattern {
+                name: name.into(),
+                steps,
+            });
+            self
+        }
+
+        pub fn entry(mut self, pattern: impl Into<String>, repeats: usize) -> Self {
+            self.song.push(SongEntry {
+                pattern: pattern.into(),
+                repeats,
+            });
+            self
+        }
+
+        pub fn scene(mut self, trigger: impl Into<Trigger>, song_index: usize) -> Self {
+            self.scenes.push(Scene {
+                trigger: trigger.into(),
+                song_index,
+            });
+            self
+        }
+
+        /// Convenience for switching scenes with a key (or other gate). The scene is queued when
+        /// the gate is first held down.
+        pub fn scene_gate(self, gate: impl Into<Gate>, song_index: usize) -> Self {
+            self.scene(gate.into().to_trigger_rising_edge(), song_index)
+        }
+
+        pub fn build(self) -> SequencedTriggers {
+            Arrangement {
+                step: self.step,
+                steps_per_bar: self.steps_per_bar.unwrap_or(8),
+                patterns: self.patterns,
+                song: self.song,
+                scenes: self.scenes,
+            }
+            .sequenced_triggers()
+        }
+    }
+
+    pub fn arrangement(step: impl Into<Trigger>) -> ArrangementBuilder {
+        ArrangementBuilder::new(step)
+    }
+}
+
+pub mod granular {
+    use crate::{
+        granular::{GrainWindow, Granular},
+        sampler::Sample,
+        signal::{const_, Sf64, Signal},
+    };
+
+    pub struct GranularBuilder {
+        sample: Sample,
+        grain_size_s: Option<Sf64>,
+        density_hz: Option<Sf64>,
+        position_01: Option<Sf64>,
+        position_jitter_01: Option<Sf64>,
+        pitch_semitones: Option<Sf64>,
+        pitch_jitter_semitones: Option<Sf64>,
+        window: Option<Signal<GrainWindow>>,
+        max_grains: Option<Sf64>,
+    }
+
+    impl GranularBuilder {
+        pub fn new(sample: &Sample) -> Self {
+            Self {
+                sample: sample.clone(),
+                grain_size_s: None,
+                density_hz: None,
+                position_01: None,
+                position_jitter_01: None,
+                pitch_semitones: None,
+                pitch_jitter_semitones: None,
+                window: None,
+                max_grains: None,
+            }
+        }
+
+        pub fn grain_size_s(mut self, grain_size_s: impl Into<Sf64>) -> Self {
+            self.grain_size_s = Some(grain_size_s.into());
+            self
+        }
+
+        pub fn density_hz(mut self, density_hz: impl Into<Sf64>) -> Self {
+            self.density_hz = Some(density_hz.into());
+            self
+        }
+
+        pub fn position_01(mut self, position_01: impl Into<Sf64>) -> Self {
+            self.position_01 = Some(position_01.into());
+            self
+        }
+
+        pub fn position_jitter_01(mut self, position_jitter_01: impl Into<Sf64>) -> Self {
+            self.position_jitter_01 = Some(position_jitter_01.into());
+            self
+        }
+
+        pub fn pitch_semitones(mut self, pitch_semitones: impl Into<Sf64>) -> Self {
+            self.pitch_semitones = Some(pitch_semitones.into());
+            self
+        }
+
+        pub fn pitch_jitter_semitones(mut self, pitch_jitter_semitones: impl Into<Sf64>) -> Self {
+            self.pitch_jitter_semitones = Some(pitch_jitter_semitones.into());
+            self
+        }
+
+        pub fn window(mut self, window: impl Into<Signal<GrainWindow>>) -> Self {
+            self.window = Some(window.into());
+            self
+        }
+
+        pub fn max_grains(mut self, max_grains: impl Into<Sf64>) -> Self {
+            self.max_grains = Some(max_grains.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            Granular {
+                sample: self.sample,
+                grain_size_s: self.grain_size_s.unwrap_or_else(|| const_(0.1)),
+                density_hz: self.density_hz.unwrap_or_else(|| const_(20.0)),
+                position_01: self.position_01.unwrap_or_else(|| const_(0.0)),
+                position_jitter_01: self.position_jitter_01.unwrap_or_else(|| const_(0.0)),
+                pitch_semitones: self.pitch_semitones.unwrap_or_else(|| const_(0.0)),
+                pitch_jitter_semitones: self.pitch_jitter_semitones.unwrap_or_else(|| const_(0.0)),
+                window: self.window.unwrap_or_else(|| const_(GrainWindow::Hann)),
+                max_grains: self.max_grains.unwrap_or_else(|| const_(32.0)),
+            }
+            .signal()
+        }
+    }
+
+    pub fn granular(sample: &Sample) -> GranularBuilder {
+        GranularBuilder::new(sample)
+    }
+}
+
+pub mod sampler {
+    pub use crate::sampler::{Sample, Sampler};
+    use crate::{
+        builder,
+        keyboard::VoiceDesc,
+        music::Note,
+        sampler::{Interpolation, LoopMode, SampleLoop},
+        signal::{Gate, Sf64, Signal, Trigger},
+    };
+
+    pub struct SamplerBuilder<'a> {
+        sample: &'a Sample,
+        trigger: Option<Trigger>,
+        gate: Option<Gate>,
+        rate: Option<Sf64>,
+        interpolation: Option<Signal<Interpolation>>,
+        start: Option<usize>,
+        end: Option<usize>,
+        sample_loop: Option<SampleLoop>,
+        reverse: Option<bool>,
+        attack_s: Option<Sf64>,
+        decay_s: Option<Sf64>,
+        sustain_01: Option<Sf64>,
+        release_s: Option<Sf64>,
+    }
+
+    impl<'a> SamplerBuilder<'a> {
+        pub fn new(sample: &'a Sample) -> Self {
+            Self {
+                sample,
+                trigger: None,
+                gate: None,
+                rate: None,
+                interpolation: None,
+                start: None,
+                end: None,
+                sample_loop: None,
+                reverse: None,
+                attack_s: None,
+                decay_s: None,
+                sustain_01: None,
+                release_s: None,
+            }
+        }
+
+        pub fn trigger(mut self, trigger: impl Into<Trigger>) -> Self {
+            self.trigger = Some(trigger.into());
+            self
+        }
+
+        /// Held while the note is held. Controls sustain loops and the volume envelope.
+        pub fn gate(mut self, gate: impl Into<Gate>) -> Self {
+            self.gate = Some(gate.into());
+            self
+        }
+
+        /// Playback speed relative to the original speed of the sample
+        pub fn rate(mut self, rate: impl Into<Sf64>) -> Self {
+            self.rate = Some(rate.into());
+            self
+        }
+
+        pub fn pitch_semitones(self, pitch_semitones: impl Into<Sf64>) -> Self {
+            self.rate(
+                pitch_semitones
+                    .into()
+                    .map(|semitones| (semitones / 12.0).exp2()),
+            )
+        }
+
+        /// Play the sample at `freq_hz`, given that it was recorded at `root_freq_hz`
+        pub fn freq_hz(self, freq_hz: impl Into<Sf64>, root_freq_hz: f64) -> Self {
+            self.rate(freq_hz.into().map(move |freq_hz| freq_hz / root_freq_hz))
+        }
+
+        /// Play the sample at the pitch of a voice's note, given that it was recorded at
+        /// `root_note`. The sample starts on each key press and the voice's key drives the
+        /// sustain loop and volume envelope.
+        pub fn voice(self, voice_desc: &VoiceDesc, root_note: Note) -> Self {
+            self.trigger(&voice_desc.key_press)
+                .gate(&voice_desc.key_down)
+                .freq_hz(voice_desc.freq_hz(), root_note.freq_hz())
+        }
+
+        pub fn interpolation(mut self, interpolation: impl Into<Signal<Interpolation>>) -> Self {
+            self.interpolation = Some(interpolation.into());
+            self
+        }
+
+        /// Index of the sample where playback starts
+        pub fn start(mut self, start: usize) -> Self {
+            self.start = Some(start);
+            self
+        }
+
+        /// Index of the sample where playback stops
+        pub fn end(mut self, end: usize) -> Self {
+            self.end = Some(end);
+            self
+        }
+
+        pub fn sample_loop(mut self, sample_loop: SampleLoop) -> Self {
+            self.sample_loop = Some(sample_loop);
+            self
+        }
+
+        /// Loop from `start` to `end` for as long as the sample plays
+        pub fn loop_continuous(self, start: usize, end: usize, crossfade: usize) -> Self {
+            self.sample_loop(SampleLoop {
+                mode: LoopMode::Continuous,
+                start,
+                end,
+                crossfade,
+            })
+        }
+
+        /// Loop from `start` to `end` while the gate is held
+        pub fn loop_sustain(self, start: usize, end: usize, crossfade: usize) -> Self {
+            self.sample_loop(SampleLoop {
+                mode: LoopMode::Sustain,
+                start,
+                end,
+                crossfade,
+            })
+        }
+
+        pub fn reverse(mut self, reverse: bool) -> Self {
+            self.reverse = Some(reverse);
+            self
+        }
+
+        pub fn attack_s(mut self, attack_s: impl Into<Sf64>) -> Self {
+            self.attack_s = Some(attack_s.into());
+            self
+        }
+
+        pub fn decay_s(mut self, decay_s: impl Into<Sf64>) -> Self {
+            self.decay_s = Some(decay_s.into());
+            self
+        }
+
+        pub fn sustain_01(mut self, sustain_01: impl Into<Sf64>) -> Self {
+            self.sustain_01 = Some(sustain_01.into());
+            self
+        }
+
+        pub fn release_s(mut self, release_s: impl Into<Sf64>) -> Self {
+            self.release_s = Some(release_s.into());
+            self
+        }
+
+        /// If any of the envelope parameters are set, the sample is shaped by an ADSR envelope
+        /// driven by the gate. Otherwise it plays through at full volume.
+        pub fn build(self) -> Sf64 {
+            let trigger = self.trigger.unwrap_or_else(Trigger::never);
+            let gate = self.gate.unwrap_or_else(Gate::never);
+            let has_envelope = self.attack_s.is_some()
+                || self.decay_s.is_some()
+                || self.sustain_01.is_some()
+                || self.release_s.is_some();
+            let mut sampler = Sampler::new(self.sample, trigger.clone());
+            sampler.gate = gate.clone();
+            if let Some(rate) = self.rate {
+                sampler.rate = rate;
+            }
+            if let Some(interpolation) = self.interpolation {
+                sampler.interpolation = interpolation;
+            }
+            sampler.start = self.start.unwrap_or(sampler.start);
+            sampler.end = self.end.or(sampler.end);
+            sampler.sample_loop = self.sample_loop.unwrap_or(sampler.sample_loop);
+            sampler.reverse = self.reverse.unwrap_or(sampler.reverse);
+            let signal = sampler.signal();
+            if has_envelope {
+                let mut env = builder::env::adsr_linear_01(gate).key_press(trigger);
+                if let Some(attack_s) = self.attack_s {
+                    env = env.attack_s(attack_s);
+                }
+                if let Some(decay_s) = self.decay_s {
+                    env = env.decay_s(decay_s);
+                }
+                if let Some(sustain_01) = self.sustain_01 {
+                    env = env.sustain_01(sustain_01);
+                }
+                if let Some(release_s) = self.release_s {
+                    env = env.release_s(release_s);
+                }
+                signal.mul_lazy(&env.build())
+            } else {
+                signal
+            }
+        }
+    }
+
+    pub fn sampler(sample: &Sample) -> SamplerBuilder {
+        SamplerBuilder::new(sample)
+    }
+}
+
+pub mod patches {
+    use crate::{
+        keyboard::VoiceDesc,
+        patches::{self, physical::ModalMode},
+        signal::{const_, sfreq_hz, Gate, Sf64, Sfreq, Trigger},
+    };
+
+    pub struct SupersawBuilder {
+        resolution: Option<usize>,
+        freq: Sfreq,
+        ratio: Option<Sf64>,
+        reset_trigger: Option<Trigger>,
+        reset_offset_01: Option<Sf64>,
+    }
+
+    impl SupersawBuilder {
+        pub fn new(freq: impl Into<Sfreq>) -> Self {
+            Self {
+                resolution: None,
+                freq: freq.into(),
+                ratio: None,
+                reset_trigger: None,
+                reset_offset_01: None,
+            }
+        }
+
+        pub fn resolution(mut self, resolution: impl Into<usize>) -> Self {
+            self.resolution = Some(resolution.into());
+            self
+        }
+
+        pub fn ratio(mut self, ratio: impl Into<Sf64>) -> Self {
+            self.ratio = Some(ratio.into());
+            self
+        }
+
+        pub fn reset_trigger(mut self, reset_trigger: impl Into<Trigger>) -> Self {
+            self.reset_trigger = Some(reset_trigger.into());
+            self
+        }
+
+        pub fn reset_offset_01(mut self, reset_offset_01: impl Into<Sf64>) -> Self {
+            self.reset_offset_01 = Some(reset_offset_01.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            patches::supersaw(
+                self.resolution.unwrap_or(1),
+                self.freq,
+                self.ratio.unwrap_or_else(|| const_(0.01)),
+                self.reset_trigger.unwrap_or_else(|| Trigger::never()),
+                self.reset_offset_01.unwrap_or_else(|| const_(0.0)),
+            )
+        }
+    }
+
+    pub fn supersaw(freq: impl Into<Sfreq>) -> SupersawBuilder {
+        SupersawBuilder::new(freq)
+    }
+
+    pub fn supersaw_hz(freq_hz: impl Into<Sf64>) -> SupersawBuilder {
+        supersaw(sfreq_hz(freq_hz))
+    }
+
+    pub struct PulsePwmBuilder {
+        osc_freq: Sfreq,
+        pwm_freq: Option<Sfreq>,
+        offset_01: Option<Sf64>,
+        scale_01: Option<Sf64>,
+        reset_trigger: Option<Trigger>,
+        reset_offset_01: Option<Sf64>,
+    }
+
+    impl PulsePwmBuilder {
+        pub fn new(osc_freq: impl Into<Sfreq>) -> Self {
+            Self {
+                osc_freq: osc_freq.into(),
+                pwm_freq: None,
+                offset_01: None,
+                scale_01: None,
+                reset_trigger: None,
+                reset_offset_01: None,
+            }
+        }
+
+        pub fn pwm_freq(mut self, pwm_freq: impl Into<Sfreq>) -> Self {
+            self.pwm_freq = Some(pwm_freq.into());
+            self
+        }
+
+        pub fn offset_01(mut self, offset_01: impl Into<Sf64>) -> Self {
+            self.offset_01 = Some(offset_01.into());
+            self
+        }
+
+        pub fn scale_01(mut self, scale_01: impl Into<Sf64>) -> Self {
+            self.scale_01 = Some(scale_01.into());
             self
         }
 
@@ -928,10 +1788,236 @@ pub mod patches {
         HatClosedBuilder::new(trigger)
     }
 
+    pub struct PluckBuilder {
+        freq: Sfreq,
+        trigger: Trigger,
+        damping_01: Option<Sf64>,
+        stretch_01: Option<Sf64>,
+        brightness_01: Option<Sf64>,
+    }
+
+    impl PluckBuilder {
+        pub fn new(freq: impl Into<Sfreq>, trigger: impl Into<Trigger>) -> Self {
+            Self {
+                freq: freq.into(),
+                trigger: trigger.into(),
+                damping_01: None,
+                stretch_01: None,
+                brightness_01: None,
+            }
+        }
+
+        pub fn damping_01(mut self, damping_01: impl Into<Sf64>) -> Self {
+            self.damping_01 = Some(damping_01.into());
+            self
+        }
+
+        pub fn stretch_01(mut self, stretch_01: impl Into<Sf64>) -> Self {
+            self.stretch_01 = Some(stretch_01.into());
+            self
+        }
+
+        pub fn brightness_01(mut self, brightness_01: impl Into<Sf64>) -> Self {
+            self.brightness_01 = Some(brightness_01.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            patches::physical::pluck(
+                self.freq,
+                self.trigger,
+                self.damping_01.unwrap_or_else(|| const_(0.3)),
+                self.stretch_01.unwrap_or_else(|| const_(0.0)),
+                self.brightness_01.unwrap_or_else(|| const_(1.0)),
+            )
+        }
+    }
+
+    pub fn pluck(freq: impl Into<Sfreq>, trigger: impl Into<Trigger>) -> PluckBuilder {
+        PluckBuilder::new(freq, trigger)
+    }
+
+    pub fn pluck_hz(freq_hz: impl Into<Sf64>, trigger: impl Into<Trigger>) -> PluckBuilder {
+        pluck(sfreq_hz(freq_hz), trigger)
+    }
+
+    /// Pluck the string each time a key is pressed
+    pub fn pluck_voice(voice_desc: &VoiceDesc) -> PluckBuilder {
+        pluck(sfreq_hz(voice_desc.freq_hz()), &voice_desc.key_press)
+    }
+
+    pub struct BowedStringBuilder {
+        freq: Sfreq,
+        gate: Gate,
+        bow_pressure_01: Option<Sf64>,
+        bow_velocity_01: Option<Sf64>,
+    }
+
+    impl BowedStringBuilder {
+        pub fn new(freq: impl Into<Sfreq>, gate: impl Into<Gate>) -> Self {
+            Self {
+                freq: freq.into(),
+                gate: gate.into(),
+                bow_pressure_01: None,
+                bow_velocity_01: None,
+            }
+        }
+
+        pub fn bow_pressure_01(mut self, bow_pressure_01: impl Into<Sf64>) -> Self {
+            self.bow_pressure_01 = Some(bow_pressure_01.into());
+            self
+        }
+
+        pub fn bow_velocity_01(mut self, bow_velocity_01: impl Into<Sf64>) -> Self {
+            self.bow_velocity_01 = Some(bow_velocity_01.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            patches::physical::bowed_string(
+                self.freq,
+                self.gate,
+                self.bow_pressure_01.unwrap_or_else(|| const_(0.5)),
+                self.bow_velocity_01.unwrap_or_else(|| const_(0.7)),
+            )
+        }
+    }
+
+    pub fn bowed_string(freq: impl Into<Sfreq>, gate: impl Into<Gate>) -> BowedStringBuilder {
+        BowedStringBuilder::new(freq, gate)
+    }
+
+    pub fn bowed_string_hz(freq_hz: impl Into<Sf64>, gate: impl Into<Gate>) -> BowedStringBuilder {
+        bowed_string(sfreq_hz(freq_hz), gate)
+    }
+
+    /// Bow the string while a key is held, with the key's velocity as the bow velocity and its
+    /// pressure as the bow pressure
+    pub fn bowed_string_voice(voice_desc: &VoiceDesc) -> BowedStringBuilder {
+        bowed_string(sfreq_hz(voice_desc.freq_hz()), &voice_desc.key_down)
+            .bow_velocity_01(&voice_desc.velocity_01)
+            .bow_pressure_01(&voice_desc.pressure_01)
+    }
+
+    pub struct BlownPipeBuilder {
+        freq: Sfreq,
+        gate: Gate,
+        breath_pressure_01: Option<Sf64>,
+        breath_noise_01: Option<Sf64>,
+    }
+
+    impl BlownPipeBuilder {
+        pub fn new(freq: impl Into<Sfreq>, gate: impl Into<Gate>) -> Self {
+            Self {
+                freq: freq.into(),
+                gate: gate.into(),
+                breath_pressure_01: None,
+                breath_noise_01: None,
+            }
+        }
+
+        pub fn breath_pressure_01(mut self, breath_pressure_01: impl Into<Sf64>) -> Self {
+            self.breath_pressure_01 = Some(breath_pressure_01.into());
+            self
+        }
+
+        pub fn breath_noise_01(mut self, breath_noise_01: impl Into<Sf64>) -> Self {
+            self.breath_noise_01 = Some(breath_noise_01.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            patches::physical::blown_pipe(
+                self.freq,
+                self.gate,
+                self.breath_pressure_01.unwrap_or_else(|| const_(0.5)),
+                self.breath_noise_01.unwrap_or_else(|| const_(0.2)),
+            )
+        }
+    }
+
+    pub fn blown_pipe(freq: impl Into<Sfreq>, gate: impl Into<Gate>) -> BlownPipeBuilder {
+        BlownPipeBuilder::new(freq, gate)
+    }
+
+    pub fn blown_pipe_hz(freq_hz: impl Into<Sf64>, gate: impl Into<Gate>) -> BlownPipeBuilder {
+        blown_pipe(sfreq_hz(freq_hz), gate)
+    }
+
+    /// Blow the pipe while a key is held, with the key's velocity as the breath pressure
+    pub fn blown_pipe_voice(voice_desc: &VoiceDesc) -> BlownPipeBuilder {
+        blown_pipe(sfreq_hz(voice_desc.freq_hz()), &voice_desc.key_down)
+            .breath_pressure_01(&voice_desc.velocity_01)
+    }
+
+    pub struct StruckBarBuilder {
+        freq: Sfreq,
+        trigger: Trigger,
+        modes: Option<Vec<ModalMode>>,
+        decay_s: Option<Sf64>,
+        hardness_01: Option<Sf64>,
+    }
+
+    impl StruckBarBuilder {
+        pub fn new(freq: impl Into<Sfreq>, trigger: impl Into<Trigger>) -> Self {
+            Self {
+                freq: freq.into(),
+                trigger: trigger.into(),
+                modes: None,
+                decay_s: None,
+                hardness_01: None,
+            }
+        }
+
+        /// Replace the modes of a bar with those of some other resonating object
+        pub fn modes(mut self, modes: impl Into<Vec<ModalMode>>) -> Self {
+            self.modes = Some(modes.into());
+            self
+        }
+
+        pub fn decay_s(mut self, decay_s: impl Into<Sf64>) -> Self {
+            self.decay_s = Some(decay_s.into());
+            self
+        }
+
+        pub fn hardness_01(mut self, hardness_01: impl Into<Sf64>) -> Self {
+            self.hardness_01 = Some(hardness_01.into());
+            self
+        }
+
+        pub fn build(self) -> Sf64 {
+            patches::physical::modal_resonator_bank(
+                self.freq,
+                self.trigger,
+                self.modes
+                    .unwrap_or_else(|| patches::physical::STRUCK_BAR_MODES.to_vec()),
+                self.decay_s.unwrap_or_else(|| const_(1.0)),
+                self.hardness_01.unwrap_or_else(|| const_(0.5)),
+            )
+        }
+    }
+
+    pub fn struck_bar(freq: impl Into<Sfreq>, trigger: impl Into<Trigger>) -> StruckBarBuilder {
+        StruckBarBuilder::new(freq, trigger)
+    }
+
+    pub fn struck_bar_hz(
+        freq_hz: impl Into<Sf64>,
+        trigger: impl Into<Trigger>,
+    ) -> StruckBarBuilder {
+        struck_bar(sfreq_hz(freq_hz), trigger)
+    }
+
+    /// Strike the bar each time a key is pressed, with harder strikes for higher velocities
+    pub fn struck_bar_voice(voice_desc: &VoiceDesc) -> StruckBarBuilder {
+        struck_bar(sfreq_hz(voice_desc.freq_hz()), &voice_desc.key_press)
+            .hardness_01(&voice_desc.velocity_01)
+    }
+
     pub mod triggerable {
         use crate::{
             patches,
-            signal::{triggerable, Triggerable},
+            signal::{triggerable, Sfreq, Triggerable},
         };
 
         pub struct KickBuilder;
@@ -966,5 +2052,33 @@ pub mod patches {
         pub fn hat_closed() -> HatClosedBuilder {
             HatClosedBuilder
         }
+
+        pub struct PluckBuilder {
+            freq: Sfreq,
+        }
+
+        impl PluckBuilder {
+            pub fn build(self) -> Triggerable<f64> {
+                triggerable(move |trigger| super::pluck(&self.freq, trigger).build())
+            }
+        }
+
+        pub fn pluck(freq: impl Into<Sfreq>) -> PluckBuilder {
+            PluckBuilder { freq: freq.into() }
+        }
+
+        pub struct StruckBarBuilder {
+            freq: Sfreq,
+        }
+
+        impl StruckBarBuilder {
+            pub fn build(self) -> Triggerable<f64> {
+                triggerable(move |trigger| super::struck_bar(&self.freq, trigger).build())
+            }
+        }
+
+        pub fn struck_bar(freq: impl Into<Sfreq>) -> StruckBarBuilder {
+            StruckBarBuilder { freq: freq.into() }
+        }
     }
 }
@@ -1,7 +1,11 @@
 use crate::{
     builder,
     oscillator::Waveform,
-    signal::{Gate, Sf64, Sfreq, Trigger},
+    signal::{Gate, Sf64, Sfreq, Signal, SignalCtx, Trigger},
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
 };
 
 pub struct PeriodicGate {
@@ -49,3 +53,282 @@ impl PeriodicTrigger {
             .to_trigger_rising_edge()
     }
 }
+
+/// Keeps track of the number of samples between consecutive pulses of a clock. This is the basis
+/// for all the utilities that need to know the period of a clock. The period is unknown until two
+/// pulses have been observed.
+#[derive(Default)]
+pub(crate) struct ClockPeriodTracker {
+    samples_since_last_pulse: u64,
+    period_samples: Option<u64>,
+    seen_pulse: bool,
+}
+
+impl ClockPeriodTracker {
+    /// Call once per sample. Returns true if the clock pulsed this sample.
+    pub(crate) fn tick(&mut self, clock: &Trigger, ctx: &SignalCtx) -> bool {
+        // Counted before checking the clock so that the period includes the pulse sample itself
+        self.samples_since_last_pulse += 1;
+        if clock.sample(ctx) {
+            if self.seen_pulse {
+                self.period_samples = Some(self.samples_since_last_pulse);
+            }
+            self.seen_pulse = true;
+            self.samples_since_last_pulse = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The number of samples since the most recent pulse, which is 0 on the sample of the pulse
+    pub(crate) fn samples_since_last_pulse(&self) -> u64 {
+        self.samples_since_last_pulse
+    }
+
+    /// The number of samples between the two most recent pulses
+    pub(crate) fn period_samples(&self) -> Option<u64> {
+        self.period_samples
+    }
+}
+
+/// Measures the time between consecutive pulses of a clock. Yields 0 until the clock has pulsed
+/// twice.
+pub struct ClockPeriod {
+    pub clock: Trigger,
+}
+
+impl ClockPeriod {
+    pub fn signal_s(self) -> Sf64 {
+        let tracker = RefCell::new(ClockPeriodTracker::default());
+        Signal::from_fn(move |ctx| {
+            let mut tracker = tracker.borrow_mut();
+            tracker.tick(&self.clock, ctx);
+            tracker
+                .period_samples
+                .map(|period_samples| period_samples as f64 / ctx.sample_rate_hz)
+                .unwrap_or(0.0)
+        })
+    }
+}
+
+/// Produces `by` evenly spaced pulses for each pulse of the input clock, based on the measured
+/// period of the input clock. The first pulse coincides with the input pulse, so the output
+/// stays phase-locked to the input even if its tempo drifts. Until the input period is known, the
+/// input clock is passed through unchanged.
+pub struct ClockMultiply {
+    pub clock: Trigger,
+    pub by: Signal<u32>,
+}
+
+impl ClockMultiply {
+    pub fn trigger(self) -> Trigger {
+        let tracker = RefCell::new(ClockPeriodTracker::default());
+        let pulses_since_clock = Cell::new(0);
+        Signal::from_fn(move |ctx| {
+            let mut tracker = tracker.borrow_mut();
+            if tracker.tick(&self.clock, ctx) {
+                pulses_since_clock.set(1);
+                return true;
+            }
+            let (Some(period_samples), by) = (tracker.period_samples, self.by.sample(ctx).max(1))
+            else {
+                return false;
+            };
+            let pulses = pulses_since_clock.get();
+            if pulses >= by {
+                return false;
+            }
+            let next_pulse_sample = (pulses as u64 * period_samples) / by as u64;
+            if tracker.samples_since_last_pulse >= next_pulse_sample {
+                pulses_since_clock.set(pulses + 1);
+                true
+            } else {
+                false
+            }
+        })
+        .to_trigger_raw()
+    }
+}
+
+/// Delays each pulse of the input clock by a fraction of its measured period. Shuffle is applied
+/// by delaying only every second pulse, starting with the second pulse after a reset.
+pub struct ClockOffset {
+    pub clock: Trigger,
+    pub offset_01: Sf64,
+    pub shuffle: bool,
+    pub reset: Trigger,
+}
+
+impl ClockOffset {
+    pub fn trigger(self) -> Trigger {
+        let tracker = RefCell::new(ClockPeriodTracker::default());
+        // Number of samples remaining until each delayed pulse is emitted
+        let pending = RefCell::new(VecDeque::<u64>::new());
+        let count = Cell::new(0u64);
+        Signal::from_fn(move |ctx| {
+            let mut tracker = tracker.borrow_mut();
+            let mut pending = pending.borrow_mut();
+            if self.reset.sample(ctx) {
+                count.set(0);
+                pending.clear();
+            }
+            let mut output = false;
+            for remaining in pending.iter_mut() {
+                *remaining = remaining.saturating_sub(1);
+            }
+            while pending.front() == Some(&0) {
+                pending.pop_front();
+                output = true;
+            }
+            if tracker.tick(&self.clock, ctx) {
+                let count_val = count.get();
+                count.set(count_val + 1);
+                let apply_offset = !self.shuffle || count_val % 2 == 1;
+                let delay_samples = match tracker.period_samples {
+                    Some(period_samples) if apply_offset => {
+                        (self.offset_01.sample(ctx).clamp(0.0, 1.0) * period_samples as f64) as u64
+                    }
+                    _ => 0,
+                };
+                if delay_samples == 0 {
+                    output = true;
+                } else {
+                    pending.push_back(delay_samples);
+                }
+            }
+            output
+        })
+        .to_trigger_raw()
+    }
+}
+
+/// Like `Trigger::divide` but the count can be reset so that the next input pulse always passes
+/// through. This is needed to keep a divided clock in phase with the bars of an external clock.
+pub struct ClockDivide {
+    pub clock: Trigger,
+    pub by: Signal<u32>,
+    pub reset: Trigger,
+}
+
+impl ClockDivide {
+    pub fn trigger(self) -> Trigger {
+        let count = Cell::new(0);
+        let reset = self.reset;
+        let by = self.by;
+        self.clock
+            .to_signal()
+            .map_ctx(move |clock, ctx| {
+                if reset.sample(ctx) {
+                    count.set(0);
+                }
+                if clock {
+                    let count_val = count.get();
+                    count.set((count_val + 1) % by.sample(ctx).max(1));
+                    count_val == 0
+                } else {
+                    false
+                }
+            })
+            .to_trigger_raw()
+    }
+}
+
+impl Trigger {
+    /// The time in seconds between the two most recent pulses.
+    pub fn period_s(&self) -> Sf64 {
+        ClockPeriod {
+            clock: self.clone(),
+        }
+        .signal_s()
+    }
+
+    pub fn multiply(&self, by: impl Into<Signal<u32>>) -> Self {
+        ClockMultiply {
+            clock: self.clone(),
+            by: by.into(),
+        }
+        .trigger()
+    }
+
+    pub fn offset_01(&self, offset_01: impl Into<Sf64>) -> Self {
+        ClockOffset {
+            clock: self.clone(),
+            offset_01: offset_01.into(),
+            shuffle: false,
+            reset: Trigger::never(),
+        }
+        .trigger()
+    }
+
+    /// Delay every second pulse by a fraction of the clock period. The reset trigger determines
+    /// which pulses count as the "first" of each pair.
+    pub fn shuffle_01(&self, shuffle_01: impl Into<Sf64>, reset: impl Into<Trigger>) -> Self {
+        ClockOffset {
+            clock: self.clone(),
+            offset_01: shuffle_01.into(),
+            shuffle: true,
+            reset: reset.into(),
+        }
+        .trigger()
+    }
+
+    pub fn divide_with_reset(&self, by: impl Into<Signal<u32>>, reset: impl Into<Trigger>) -> Self {
+        ClockDivide {
+            clock: self.clone(),
+            by: by.into(),
+            reset: reset.into(),
+        }
+        .trigger()
+    }
+
+    /// Treating `self` as a clock that pulses once per step, fire on the first step of every nth
+    /// bar.
+    pub fn every_nth_bar(
+        &self,
+        steps_per_bar: impl Into<Signal<u32>>,
+        n: impl Into<Signal<u32>>,
+        reset: impl Into<Trigger>,
+    ) -> Self {
+        let steps_per_bar = steps_per_bar.into();
+        let n = n.into();
+        self.divide_with_reset(
+            steps_per_bar
+                .zip(&n)
+                .map(|(steps_per_bar, n)| steps_per_bar * n),
+            reset,
+        )
+    }
+}
+
+#[test]
+fn test_fixed_period_clock() {
+    let period_samples = 100;
+    let clock = Signal::from_fn(move |ctx| {
+        let sample_in_period = ctx.sample_index % period_samples;
+        sample_in_period == 0
+    })
+    .to_trigger_raw();
+    let period_s = clock.period_s();
+    let multiplied = clock.multiply(4);
+    let mut multiplied_pulses = Vec::new();
+    for sample_index in 0..(period_samples * 3) {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        let period_s = period_s.sample(&ctx);
+        if sample_index >= period_samples {
+            assert_eq!(period_s, 0.1);
+        }
+        if multiplied.sample(&ctx) {
+            multiplied_pulses.push(sample_index);
+        }
+    }
+    // The input period is only known after its second pulse so the first period passes
+    // through a single pulse
+    assert_eq!(
+        multiplied_pulses,
+        vec![0, 100, 125, 150, 175, 200, 225, 250, 275]
+    );
+}
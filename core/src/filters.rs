@@ -166,6 +166,79 @@ impl Filter for Delay {
     }
 }
 
+/// A ring buffer of past samples which can be read at a fractional number of samples in the
+/// past. Unlike `Delay` this isn't a `Filter` but a building block for models with feedback
+/// loops, such as physical models of strings, whose tuning depends on the exact loop length.
+#[derive(Default)]
+pub struct FractionalDelayLine {
+    buffer: Vec<f64>,
+    /// Index where the next sample will be written
+    write_index: usize,
+}
+
+impl FractionalDelayLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make room for reading up to `max_delay_samples` samples in the past, keeping the
+    /// samples already in the buffer
+    pub fn reserve(&mut self, max_delay_samples: f64) {
+        // Interpolation reads up to 2 samples beyond the integer part of the delay
+        let required_len = max_delay_samples.max(0.0).ceil() as usize + 3;
+        if required_len <= self.buffer.len() {
+            return;
+        }
+        let mut buffer = vec![0.0; required_len];
+        let offset = required_len - self.buffer.len();
+        for i in 0..self.buffer.len() {
+            buffer[offset + i] = self.buffer[(self.write_index + i) % self.buffer.len()];
+        }
+        self.buffer = buffer;
+        self.write_index = 0;
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        if self.buffer.is_empty() {
+            self.reserve(0.0);
+        }
+        self.buffer[self.write_index] = sample;
+        self.write_index = (self.write_index + 1) % self.buffer.len();
+    }
+
+    /// The sample pushed `i` pushes ago, where 0 is the most recently pushed sample
+    fn tap(&self, i: usize) -> f64 {
+        let len = self.buffer.len();
+        self.buffer[(self.write_index + len - 1 - (i % len)) % len]
+    }
+
+    /// Read the signal `delay_samples` samples before the most recently pushed sample, using
+    /// third-order Lagrange interpolation between samples. The delay is limited to the space
+    /// made available by `reserve`.
+    pub fn read(&self, delay_samples: f64) -> f64 {
+        if self.buffer.len() < 4 {
+            return 0.0;
+        }
+        let delay_samples = delay_samples.clamp(0.0, (self.buffer.len() - 3) as f64);
+        let index = delay_samples.floor() as usize;
+        let frac = delay_samples - index as f64;
+        if index == 0 {
+            // There's no newer sample to interpolate with so fall back to linear interpolation
+            return self.tap(0) + ((self.tap(1) - self.tap(0)) * frac);
+        }
+        // Position of the read point relative to the 4 samples surrounding it
+        let x = frac + 1.0;
+        let h0 = -(x - 1.0) * (x - 2.0) * (x - 3.0) / 6.0;
+        let h1 = x * (x - 2.0) * (x - 3.0) / 2.0;
+        let h2 = -x * (x - 1.0) * (x - 3.0) / 2.0;
+        let h3 = x * (x - 1.0) * (x - 2.0) / 6.0;
+        (h0 * self.tap(index - 1))
+            + (h1 * self.tap(index))
+            + (h2 * self.tap(index + 1))
+            + (h3 * self.tap(index + 2))
+    }
+}
+
 pub struct Echo {
     delay: Delay,
     scale: Sf64,
@@ -0,0 +1,131 @@
+use crate::{
+    random,
+    sampler::Sample,
+    signal::{const_, Sf64, Signal},
+};
+use rand::{rngs::StdRng, Rng};
+use std::{cell::RefCell, f64::consts::PI};
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrainWindow {
+    #[default]
+    Hann,
+    Triangle,
+    /// Fades in and out over the first and last quarter of the grain
+    Trapezoid,
+    Gaussian,
+    /// No fade at all, which tends to click
+    Rectangle,
+}
+
+impl From<GrainWindow> for Signal<GrainWindow> {
+    fn from(value: GrainWindow) -> Self {
+        const_(value)
+    }
+}
+
+impl GrainWindow {
+    fn gain(self, position_01: f64) -> f64 {
+        match self {
+            GrainWindow::Hann => (1.0 - (position_01 * 2.0 * PI).cos()) / 2.0,
+            GrainWindow::Triangle => 1.0 - ((position_01 * 2.0) - 1.0).abs(),
+            GrainWindow::Trapezoid => (4.0 * position_01.min(1.0 - position_01)).min(1.0),
+            GrainWindow::Gaussian => {
+                const SIGMA: f64 = 0.15;
+                let x = (position_01 - 0.5) / SIGMA;
+                (-0.5 * x * x).exp()
+            }
+            GrainWindow::Rectangle => 1.0,
+        }
+    }
+}
+
+/// Plays many short overlapping fragments (grains) of a sample. Each grain starts at a position
+/// in the sample chosen from `position_01` and `position_jitter_01` and plays at a speed chosen
+/// from `pitch_semitones` and `pitch_jitter_semitones`. Moving the position slowly through
+/// the sample stretches it in time without changing its pitch.
+pub struct Granular {
+    pub sample: Sample,
+    /// Length of each grain in seconds
+    pub grain_size_s: Sf64,
+    /// Number of grains started per second
+    pub density_hz: Sf64,
+    /// Position in the sample where grains start, from 0 (the start) to 1 (the end)
+    pub position_01: Sf64,
+    /// Maximum random offset added to the start position of each grain, as a fraction of the
+    /// length of the sample
+    pub position_jitter_01: Sf64,
+    /// Playback speed of grains in semitones relative to the original speed
+    pub pitch_semitones: Sf64,
+    /// Maximum random offset added to the pitch of each grain
+    pub pitch_jitter_semitones: Sf64,
+    pub window: Signal<GrainWindow>,
+    /// New grains aren't started while this many grains are playing
+    pub max_grains: Sf64,
+}
+
+struct Grain {
+    /// Current index into the sample
+    index: f64,
+    /// Number of sample indices advanced per output sample
+    speed: f64,
+    age_samples: f64,
+    length_samples: f64,
+}
+
+struct GranularState {
+    rng: StdRng,
+    grains: Vec<Grain>,
+    /// Progress towards starting the next grain
+    phase_01: f64,
+}
+
+impl GranularState {
+    /// A random value between -1 and 1
+    fn jitter(&mut self) -> f64 {
+        (self.rng.gen::<f64>() * 2.0) - 1.0
+    }
+}
+
+impl Granular {
+    pub fn signal(self) -> Sf64 {
+        let state = RefCell::new(GranularState {
+            rng: random::new_rng(),
+            grains: Vec::new(),
+            phase_01: 1.0,
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let max_grains = self.max_grains.sample(ctx).max(0.0) as usize;
+            state.phase_01 += self.density_hz.sample(ctx).max(0.0) / ctx.sample_rate_hz;
+            if state.phase_01 >= 1.0 {
+                state.phase_01 = state.phase_01.fract();
+                let length_samples = self.grain_size_s.sample(ctx) * ctx.sample_rate_hz;
+                if state.grains.len() < max_grains && length_samples >= 1.0 {
+                    let position_01 = self.position_01.sample(ctx)
+                        + (self.position_jitter_01.sample(ctx) * state.jitter());
+                    let pitch_semitones = self.pitch_semitones.sample(ctx)
+                        + (self.pitch_jitter_semitones.sample(ctx) * state.jitter());
+                    state.grains.push(Grain {
+                        index: position_01.clamp(0.0, 1.0) * self.sample.len() as f64,
+                        speed: (pitch_semitones / 12.0).exp2(),
+                        age_samples: 0.0,
+                        length_samples,
+                    });
+                }
+            }
+            let window = self.window.sample(ctx);
+            let mut output = 0.0;
+            for grain in state.grains.iter_mut() {
+                let gain = window.gain(grain.age_samples / grain.length_samples);
+                output += self.sample.read_linear(grain.index) * gain;
+                grain.index += grain.speed;
+                grain.age_samples += 1.0;
+            }
+            state
+                .grains
+                .retain(|grain| grain.age_samples < grain.length_samples);
+            output
+        })
+    }
+}
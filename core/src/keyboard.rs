@@ -3,17 +3,39 @@ use crate::{
         chord::{Chord, Inversion},
         Note, Octave,
     },
-    signal::{const_, Gate, Sf64, Signal, SignalCtx, Trigger},
+    random,
+    signal::{const_, Freq, Gate, Sf64, Sfreq, Signal, SignalCtx, Trigger},
 };
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use std::{cell::RefCell, collections::HashSet, mem, rc::Rc};
+use rand::{rngs::StdRng, Rng};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    mem,
+    rc::Rc,
+};
+
+/// The timbre of voices which aren't driven by a source of per-note timbre. This is the centre of
+/// the range, matching the value MPE receivers assume before a controller sends any timbre.
+pub const DEFAULT_TIMBRE_01: f64 = 0.5;
 
 #[derive(Clone)]
 pub struct VoiceDesc {
     pub note: Signal<Note>,
     pub key_down: Gate,
     pub key_press: Trigger,
+    /// The velocity with which the key was pressed
     pub velocity_01: Sf64,
+    /// The velocity with which the key was most recently released
+    pub release_velocity_01: Sf64,
+    /// Per-voice pitch bend in semitones. This is 0 unless the voice is driven by a source of
+    /// per-note expression such as an MPE controller.
+    pub pitch_bend_semitones: Sf64,
+    /// Per-voice pressure from polyphonic aftertouch or an MPE controller
+    pub pressure_01: Sf64,
+    /// Pressure shared by all voices, such as midi channel aftertouch
+    pub channel_pressure_01: Sf64,
+    /// Per-voice timbre (MPE's third dimension, usually sent as CC 74)
+    pub timbre_01: Sf64,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -23,10 +45,38 @@ pub struct KeyEvent {
     pub velocity_01: f64,
 }
 
+/// A change in the pressure applied to a held key, such as from polyphonic aftertouch
+#[derive(Clone, Copy, Debug)]
+pub struct NotePressure {
+    pub note: Note,
+    pub pressure_01: f64,
+}
+
+/// Sources of pressure information for voices, which are separate from key events since not
+/// all keyboards are pressure sensitive
+struct PressureSources {
+    note_pressures: Signal<Vec<NotePressure>>,
+    channel_pressure_01: Sf64,
+}
+
+impl PressureSources {
+    fn new(
+        note_pressures: Option<Signal<Vec<NotePressure>>>,
+        channel_pressure_01: Option<Sf64>,
+    ) -> Self {
+        Self {
+            note_pressures: note_pressures.unwrap_or_else(|| const_(Vec::new())),
+            channel_pressure_01: channel_pressure_01.unwrap_or_else(|| const_(0.0)),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 struct HeldKey {
     note: Note,
     velocity_01: f64,
+    release_velocity_01: f64,
+    pressure_01: f64,
 }
 
 impl HeldKey {
@@ -34,29 +84,85 @@ impl HeldKey {
         Self {
             note: key_event.note,
             velocity_01: key_event.velocity_01,
+            release_velocity_01: 0.0,
+            pressure_01: 0.0,
         }
     }
 }
 
+/// The state of a voice used for polyphonic playback, which reuse policies inspect to decide
+/// which voice should play a new note.
 #[derive(Default)]
-struct PolyphonicVoice {
+pub struct PolyphonicVoice {
     key: HeldKey,
     /// Key information is retained until the voice is reused to let the envelope play out
     /// after a key is released.
     key_down: bool,
     key_press: bool,
     key_press_sample_index: u64,
+    key_release_sample_index: u64,
+    level_01: f64,
+    /// False until the voice plays its first note
+    has_played: bool,
+}
+
+impl PolyphonicVoice {
+    /// The note most recently played by this voice
+    pub fn note(&self) -> Note {
+        self.key.note
+    }
+
+    pub fn velocity_01(&self) -> f64 {
+        self.key.velocity_01
+    }
+
+    pub fn key_down(&self) -> bool {
+        self.key_down
+    }
+
+    pub fn key_press_sample_index(&self) -> u64 {
+        self.key_press_sample_index
+    }
+
+    /// Whether the voice has played a note since it was created
+    pub fn has_played(&self) -> bool {
+        self.has_played
+    }
+
+    pub fn key_release_sample_index(&self) -> u64 {
+        self.key_release_sample_index
+    }
+
+    /// Recent peak absolute value of the voice's output. This is only known for voices created
+    /// with `PolyphonyBuilder::build_with` and is 0 otherwise.
+    pub fn level_01(&self) -> f64 {
+        self.level_01
+    }
 }
 
 /// Choose the next voice to use to play a note. There may be multiple implementations of this
 /// because there's no one way to choose which voice to use to play a note when all available
-/// voices are currently playing notes.
-trait PolyphonicVoiceReusePolicy {
-    fn choose_voice_index(&mut self, voices: &[PolyphonicVoice]) -> Option<usize>;
+/// voices are currently playing notes. Returning `None` drops the new note.
+pub trait PolyphonicVoiceReusePolicy {
+    fn choose_voice_index(
+        &mut self,
+        key_event: &KeyEvent,
+        voices: &[PolyphonicVoice],
+    ) -> Option<usize>;
+}
+
+impl<P: PolyphonicVoiceReusePolicy + ?Sized> PolyphonicVoiceReusePolicy for Box<P> {
+    fn choose_voice_index(
+        &mut self,
+        key_event: &KeyEvent,
+        voices: &[PolyphonicVoice],
+    ) -> Option<usize> {
+        (**self).choose_voice_index(key_event, voices)
+    }
 }
 
-mod polyphonic_voice_reuse_policy {
-    use super::{PolyphonicVoice, PolyphonicVoiceReusePolicy};
+pub mod polyphonic_voice_reuse_policy {
+    use super::{KeyEvent, PolyphonicVoice, PolyphonicVoiceReusePolicy};
     use std::collections::BinaryHeap;
 
     #[derive(PartialEq, Eq)]
@@ -98,7 +204,11 @@ mod polyphonic_voice_reuse_policy {
     }
 
     impl PolyphonicVoiceReusePolicy for Generational {
-        fn choose_voice_index(&mut self, voices: &[PolyphonicVoice]) -> Option<usize> {
+        fn choose_voice_index(
+            &mut self,
+            _key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
             self.heap.clear();
             let mut oldest_available_voice: Option<GenerationalEntry> = None;
             let mut nth_oldest_unavailable_voice = None;
@@ -133,10 +243,204 @@ mod polyphonic_voice_reuse_policy {
                 .map(|entry| entry.index)
         }
     }
+
+    /// Returns the index of the voice minimizing `key` among voices whose key is not held, or
+    /// failing that among all voices
+    fn prefer_available_by_key<K: PartialOrd, F: Fn(&PolyphonicVoice) -> K>(
+        voices: &[PolyphonicVoice],
+        key: F,
+    ) -> Option<usize> {
+        let min_by_key = |key_down: bool| {
+            let mut best: Option<(usize, K)> = None;
+            for (i, voice) in voices.iter().enumerate() {
+                if voice.key_down != key_down {
+                    continue;
+                }
+                let k = key(voice);
+                match best {
+                    Some((_, ref best_k)) if k >= *best_k => (),
+                    _ => best = Some((i, k)),
+                }
+            }
+            best.map(|(i, _)| i)
+        };
+        min_by_key(false).or_else(|| min_by_key(true))
+    }
+
+    /// Use the voice whose key was released longest ago. If all keys are held, steal the voice
+    /// whose key was pressed longest ago.
+    pub struct Oldest;
+
+    impl PolyphonicVoiceReusePolicy for Oldest {
+        fn choose_voice_index(
+            &mut self,
+            _key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
+            prefer_available_by_key(voices, |voice| {
+                if voice.key_down {
+                    voice.key_press_sample_index
+                } else {
+                    voice.key_release_sample_index
+                }
+            })
+        }
+    }
+
+    /// Use the voice with the lowest output level, preferring voices whose key isn't held. This
+    /// relies on the level of each voice being known, which is only the case for voices created
+    /// with `PolyphonyBuilder::build_with`. Otherwise it behaves like `Oldest`.
+    pub struct Quietest;
+
+    impl PolyphonicVoiceReusePolicy for Quietest {
+        fn choose_voice_index(
+            &mut self,
+            _key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
+            prefer_available_by_key(voices, |voice| {
+                let sample_index = if voice.key_down {
+                    voice.key_press_sample_index
+                } else {
+                    voice.key_release_sample_index
+                };
+                (voice.level_01, sample_index)
+            })
+        }
+    }
+
+    /// Which notes to keep when all voices are held
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum NotePriorityKind {
+        Lowest,
+        Highest,
+    }
+
+    /// Prefer voices whose keys aren't held. When all keys are held, the voice playing the note
+    /// with the lowest priority is stolen, unless the new note has an even lower priority in
+    /// which case the new note is dropped.
+    pub struct NotePriority(pub NotePriorityKind);
+
+    impl PolyphonicVoiceReusePolicy for NotePriority {
+        fn choose_voice_index(
+            &mut self,
+            key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
+            if let Some(i) = Oldest.choose_voice_index(key_event, voices) {
+                if !voices[i].key_down {
+                    return Some(i);
+                }
+            }
+            // All keys are held. Find the held voice with the lowest priority.
+            let lowest_priority = match self.0 {
+                NotePriorityKind::Lowest => voices
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, voice)| voice.key.note),
+                NotePriorityKind::Highest => voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, voice)| voice.key.note),
+            };
+            lowest_priority.and_then(|(i, voice)| {
+                let new_note_has_priority = match self.0 {
+                    NotePriorityKind::Lowest => key_event.note < voice.key.note,
+                    NotePriorityKind::Highest => key_event.note > voice.key.note,
+                };
+                if new_note_has_priority {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+        }
+    }
+
+    /// Cycle through voices in order, skipping voices whose keys are held if possible
+    #[derive(Default)]
+    pub struct RoundRobin {
+        next_index: usize,
+    }
+
+    impl PolyphonicVoiceReusePolicy for RoundRobin {
+        fn choose_voice_index(
+            &mut self,
+            _key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
+            if voices.is_empty() {
+                return None;
+            }
+            let start = self.next_index % voices.len();
+            let index = (0..voices.len())
+                .map(|offset| (start + offset) % voices.len())
+                .find(|&i| !voices[i].key_down)
+                .unwrap_or(start);
+            self.next_index = (index + 1) % voices.len();
+            Some(index)
+        }
+    }
+
+    /// If a voice is already playing (or releasing) the note being pressed, retrigger that voice
+    /// rather than allocating a new one. Otherwise defer to another policy.
+    pub struct RetriggerSameNote<P: PolyphonicVoiceReusePolicy>(pub P);
+
+    impl<P: PolyphonicVoiceReusePolicy> PolyphonicVoiceReusePolicy for RetriggerSameNote<P> {
+        fn choose_voice_index(
+            &mut self,
+            key_event: &KeyEvent,
+            voices: &[PolyphonicVoice],
+        ) -> Option<usize> {
+            voices
+                .iter()
+                .enumerate()
+                .filter(|(_, voice)| voice.has_played && voice.key.note == key_event.note)
+                .max_by_key(|(_, voice)| voice.key_press_sample_index)
+                .map(|(i, _)| i)
+                .or_else(|| self.0.choose_voice_index(key_event, voices))
+        }
+    }
 }
 
 impl VoiceDesc {
-    fn monophonic_from_key_events(key_events: Signal<Vec<KeyEvent>>) -> Self {
+    /// A voice without any expression beyond its velocity. The release velocity, pitch bend and
+    /// pressure are 0 and the timbre is `DEFAULT_TIMBRE_01`. Use struct update syntax to add
+    /// expression to the voice.
+    pub fn new(
+        note: impl Into<Signal<Note>>,
+        key_down: impl Into<Gate>,
+        key_press: impl Into<Trigger>,
+        velocity_01: impl Into<Sf64>,
+    ) -> Self {
+        Self {
+            note: note.into(),
+            key_down: key_down.into(),
+            key_press: key_press.into(),
+            velocity_01: velocity_01.into(),
+            release_velocity_01: const_(0.0),
+            pitch_bend_semitones: const_(0.0),
+            pressure_01: const_(0.0),
+            channel_pressure_01: const_(0.0),
+            timbre_01: const_(DEFAULT_TIMBRE_01),
+        }
+    }
+
+    /// The frequency of the voice's note with its pitch bend applied
+    pub fn freq_hz(&self) -> Sf64 {
+        self.note
+            .zip(&self.pitch_bend_semitones)
+            .map(|(note, pitch_bend_semitones)| {
+                note.freq_hz() * (pitch_bend_semitones / 12.0).exp2()
+            })
+    }
+
+    fn monophonic_from_key_events(
+        key_events: Signal<Vec<KeyEvent>>,
+        pressure_sources: PressureSources,
+        note_priority: MonophonicNotePriority,
+        legato: bool,
+    ) -> Self {
         #[derive(Default)]
         struct State {
             held_keys: Vec<HeldKey>,
@@ -146,51 +450,85 @@ impl VoiceDesc {
         }
 
         impl State {
-            fn handle_key_event(&mut self, key_event: &KeyEvent) {
+            fn handle_key_event(
+                &mut self,
+                key_event: &KeyEvent,
+                note_priority: MonophonicNotePriority,
+                legato: bool,
+            ) {
                 // Remove the held key if it already exists. This assumes there are no duplicate
                 // keys in the vector.
+                let mut removed = None;
                 for (i, held_key) in self.held_keys.iter().enumerate() {
                     if held_key.note == key_event.note {
-                        self.held_keys.remove(i);
+                        removed = Some(self.held_keys.remove(i));
                         break;
                     }
                 }
-                self.sticky = HeldKey::from_key_event(key_event);
                 if key_event.pressed {
+                    self.sticky = HeldKey::from_key_event(key_event);
+                } else {
+                    self.sticky = removed.unwrap_or_else(|| HeldKey::from_key_event(key_event));
+                    self.sticky.release_velocity_01 = key_event.velocity_01;
+                }
+                if key_event.pressed {
+                    let other_keys_held = !self.held_keys.is_empty();
                     self.held_keys.push(self.sticky);
-                    self.key_just_pressed = true;
+                    // Only retrigger if the new key is the one that will sound. In legato mode
+                    // keys pressed while other keys are held never retrigger.
+                    let new_key_sounds = self
+                        .current(note_priority)
+                        .map(|key| key.note == key_event.note)
+                        .unwrap_or(false);
+                    if new_key_sounds && !(legato && other_keys_held) {
+                        self.key_just_pressed = true;
+                    }
+                }
+            }
+            fn handle_note_pressure(&mut self, note_pressure: &NotePressure) {
+                for held_key in &mut self.held_keys {
+                    if held_key.note == note_pressure.note {
+                        held_key.pressure_01 = note_pressure.pressure_01;
+                    }
                 }
             }
-            fn last(&self) -> Option<&HeldKey> {
-                self.held_keys.last()
+            fn current_or_sticky(&self, note_priority: MonophonicNotePriority) -> &HeldKey {
+                self.current(note_priority).unwrap_or(&self.sticky)
+            }
+            fn current(&self, note_priority: MonophonicNotePriority) -> Option<&HeldKey> {
+                match note_priority {
+                    MonophonicNotePriority::Last => self.held_keys.last(),
+                    MonophonicNotePriority::Lowest => {
+                        self.held_keys.iter().min_by_key(|key| key.note)
+                    }
+                    MonophonicNotePriority::Highest => {
+                        self.held_keys.iter().max_by_key(|key| key.note)
+                    }
+                }
             }
         }
 
         let state = Rc::new(RefCell::new(State::default()));
-        let update_state = key_events.map({
+        let update_state = key_events.zip(&pressure_sources.note_pressures).map({
             let state = Rc::clone(&state);
-            move |key_events_this_tick| {
+            move |(key_events_this_tick, note_pressures_this_tick)| {
                 let mut state = state.borrow_mut();
                 for key_event in &key_events_this_tick {
-                    state.handle_key_event(key_event);
+                    state.handle_key_event(key_event, note_priority, legato);
                 }
-            }
-        });
-        let note = update_state.then({
-            let state = Rc::clone(&state);
-            move || {
-                let state = state.borrow();
-                if let Some(last) = state.last() {
-                    last.note
-                } else {
-                    state.sticky.note
+                for note_pressure in &note_pressures_this_tick {
+                    state.handle_note_pressure(note_pressure);
                 }
             }
         });
+        let held_key = {
+            let state = Rc::clone(&state);
+            update_state.then(move || *state.borrow().current_or_sticky(note_priority))
+        };
         let key_down = update_state
             .then({
                 let state = Rc::clone(&state);
-                move || state.borrow().last().is_some()
+                move || !state.borrow().held_keys.is_empty()
             })
             .to_gate();
         let key_press = update_state
@@ -202,22 +540,16 @@ impl VoiceDesc {
                 }
             })
             .to_trigger_raw();
-        let velocity_01 = update_state.then({
-            let state = Rc::clone(&state);
-            move || {
-                let state = state.borrow();
-                if let Some(last) = state.last() {
-                    last.velocity_01
-                } else {
-                    state.sticky.velocity_01
-                }
-            }
-        });
         Self {
-            note,
+            note: held_key.map(|held_key| held_key.note),
             key_down,
             key_press,
-            velocity_01,
+            velocity_01: held_key.map(|held_key| held_key.velocity_01),
+            release_velocity_01: held_key.map(|held_key| held_key.release_velocity_01),
+            pitch_bend_semitones: const_(0.0),
+            pressure_01: held_key.map(|held_key| held_key.pressure_01),
+            channel_pressure_01: pressure_sources.channel_pressure_01,
+            timbre_01: const_(DEFAULT_TIMBRE_01),
         }
     }
 
@@ -225,6 +557,8 @@ impl VoiceDesc {
         reuse_policy: P,
         num_voices: usize,
         key_events: Signal<Vec<KeyEvent>>,
+        pressure_sources: PressureSources,
+        levels: Rc<Vec<Cell<f64>>>,
     ) -> Vec<Self> {
         struct State<P: PolyphonicVoiceReusePolicy> {
             reuse_policy: P,
@@ -241,43 +575,64 @@ impl VoiceDesc {
 
             fn handle_key_event(&mut self, key_event: &KeyEvent, ctx: &SignalCtx) {
                 if key_event.pressed {
-                    if let Some(i) = self.reuse_policy.choose_voice_index(&self.voices) {
+                    if let Some(i) = self
+                        .reuse_policy
+                        .choose_voice_index(key_event, &self.voices)
+                    {
                         let voice = &mut self.voices[i];
                         *voice = PolyphonicVoice {
                             key: HeldKey::from_key_event(key_event),
                             key_down: true,
                             key_press: true,
                             key_press_sample_index: ctx.sample_index,
+                            key_release_sample_index: voice.key_release_sample_index,
+                            level_01: voice.level_01,
+                            has_played: true,
                         };
                     }
                 } else {
                     for voice in &mut self.voices {
-                        if voice.key.note == key_event.note {
+                        if voice.key.note == key_event.note && voice.key_down {
                             voice.key_down = false;
-                            voice.key.velocity_01 = key_event.velocity_01;
+                            voice.key.release_velocity_01 = key_event.velocity_01;
+                            voice.key_release_sample_index = ctx.sample_index;
                         }
                     }
                 }
             }
+
+            fn handle_note_pressure(&mut self, note_pressure: &NotePressure) {
+                for voice in &mut self.voices {
+                    if voice.key.note == note_pressure.note && voice.key_down {
+                        voice.key.pressure_01 = note_pressure.pressure_01;
+                    }
+                }
+            }
         }
 
         let state = Rc::new(RefCell::new(State::new(reuse_policy, num_voices)));
-        let update_state = key_events.map_ctx({
+        let update_state = key_events.zip(&pressure_sources.note_pressures).map_ctx({
             let state = Rc::clone(&state);
-            move |key_events_this_tick, ctx| {
+            move |(key_events_this_tick, note_pressures_this_tick), ctx| {
                 let mut state = state.borrow_mut();
+                for (voice, level) in state.voices.iter_mut().zip(levels.iter()) {
+                    voice.level_01 = level.get();
+                }
                 for key_event in &key_events_this_tick {
                     state.handle_key_event(key_event, ctx);
                 }
+                for note_pressure in &note_pressures_this_tick {
+                    state.handle_note_pressure(note_pressure);
+                }
             }
         });
         (0..num_voices)
             .map(|i| {
-                let note = update_state.then({
+                let held_key = update_state.then({
                     let state = Rc::clone(&state);
                     move || {
                         let state = state.borrow();
-                        state.voices[i].key.note
+                        state.voices[i].key
                     }
                 });
                 let key_down = update_state
@@ -298,24 +653,379 @@ impl VoiceDesc {
                         }
                     })
                     .to_trigger_raw();
-                let velocity_01 = update_state.then({
-                    let state = Rc::clone(&state);
-                    move || {
-                        let state = state.borrow();
-                        state.voices[i].key.velocity_01
-                    }
-                });
                 Self {
-                    note,
+                    note: held_key.map(|held_key| held_key.note),
                     key_down,
                     key_press,
-                    velocity_01,
+                    velocity_01: held_key.map(|held_key| held_key.velocity_01),
+                    release_velocity_01: held_key.map(|held_key| held_key.release_velocity_01),
+                    pitch_bend_semitones: const_(0.0),
+                    pressure_01: held_key.map(|held_key| held_key.pressure_01),
+                    channel_pressure_01: pressure_sources.channel_pressure_01.clone(),
+                    timbre_01: const_(DEFAULT_TIMBRE_01),
                 }
             })
             .collect()
     }
 }
 
+/// Which of the currently-held keys a monophonic voice plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonophonicNotePriority {
+    /// The most recently pressed key
+    Last,
+    Lowest,
+    Highest,
+}
+
+/// How the glide time of a monophonic voice is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlideMode {
+    /// Every glide takes the glide time regardless of the interval
+    ConstantTime,
+    /// Glides move at a rate of one octave per glide time, so larger intervals take longer
+    ConstantRate,
+}
+
+/// A monophonic voice along with its frequency with glide applied
+#[derive(Clone)]
+pub struct MonophonicVoiceDesc {
+    pub voice_desc: VoiceDesc,
+    /// Smoothly follows the frequency of `voice_desc.note` in log-frequency space
+    pub freq: Sfreq,
+}
+
+pub struct MonophonyBuilder {
+    key_events: Signal<Vec<KeyEvent>>,
+    note_priority: Option<MonophonicNotePriority>,
+    legato: Option<bool>,
+    glide_s: Option<Sf64>,
+    glide_mode: Option<GlideMode>,
+    note_pressures: Option<Signal<Vec<NotePressure>>>,
+    channel_pressure_01: Option<Sf64>,
+}
+
+impl MonophonyBuilder {
+    pub fn new(key_events: Signal<Vec<KeyEvent>>) -> Self {
+        Self {
+            key_events,
+            note_priority: None,
+            legato: None,
+            glide_s: None,
+            glide_mode: None,
+            note_pressures: None,
+            channel_pressure_01: None,
+        }
+    }
+
+    pub fn note_priority(mut self, note_priority: MonophonicNotePriority) -> Self {
+        self.note_priority = Some(note_priority);
+        self
+    }
+
+    /// When set, pressing a key while another key is held changes the note without
+    /// retriggering the voice
+    pub fn legato(mut self, legato: bool) -> Self {
+        self.legato = Some(legato);
+        self
+    }
+
+    pub fn glide_s(mut self, glide_s: impl Into<Sf64>) -> Self {
+        self.glide_s = Some(glide_s.into());
+        self
+    }
+
+    pub fn glide_mode(mut self, glide_mode: GlideMode) -> Self {
+        self.glide_mode = Some(glide_mode);
+        self
+    }
+
+    pub fn note_pressures(mut self, note_pressures: impl Into<Signal<Vec<NotePressure>>>) -> Self {
+        self.note_pressures = Some(note_pressures.into());
+        self
+    }
+
+    pub fn channel_pressure_01(mut self, channel_pressure_01: impl Into<Sf64>) -> Self {
+        self.channel_pressure_01 = Some(channel_pressure_01.into());
+        self
+    }
+
+    pub fn build(self) -> MonophonicVoiceDesc {
+        let voice_desc = VoiceDesc::monophonic_from_key_events(
+            self.key_events,
+            PressureSources::new(self.note_pressures, self.channel_pressure_01),
+            self.note_priority.unwrap_or(MonophonicNotePriority::Last),
+            self.legato.unwrap_or(false),
+        );
+        let glide_s = self.glide_s.unwrap_or_else(|| const_(0.0));
+        let glide_mode = self.glide_mode.unwrap_or(GlideMode::ConstantTime);
+        let note = voice_desc.note.clone();
+        // (current log2 frequency, target log2 frequency, octaves per sample)
+        let state: RefCell<Option<(f64, f64, f64)>> = RefCell::new(None);
+        let freq = Signal::from_fn(move |ctx| {
+            let target = note.sample(ctx).freq_hz().log2();
+            let glide_samples = glide_s.sample(ctx).max(0.0) * ctx.sample_rate_hz;
+            let mut state = state.borrow_mut();
+            let (current, prev_target, step) = state.get_or_insert((target, target, 0.0));
+            if target != *prev_target {
+                *prev_target = target;
+                let octaves_per_glide = match glide_mode {
+                    GlideMode::ConstantTime => (target - *current).abs(),
+                    GlideMode::ConstantRate => 1.0,
+                };
+                *step = if glide_samples < 1.0 {
+                    f64::INFINITY
+                } else {
+                    octaves_per_glide / glide_samples
+                };
+            }
+            let delta = target - *current;
+            if delta.abs() <= *step {
+                *current = target;
+            } else {
+                *current += step.copysign(delta);
+            }
+            Freq::from_hz(current.exp2())
+        });
+        MonophonicVoiceDesc { voice_desc, freq }
+    }
+}
+
+pub struct PolyphonyBuilder {
+    key_events: Signal<Vec<KeyEvent>>,
+    num_voices: Option<usize>,
+    reuse_policy: Option<Box<dyn PolyphonicVoiceReusePolicy>>,
+    note_pressures: Option<Signal<Vec<NotePressure>>>,
+    channel_pressure_01: Option<Sf64>,
+}
+
+impl PolyphonyBuilder {
+    pub fn new(key_events: Signal<Vec<KeyEvent>>) -> Self {
+        Self {
+            key_events,
+            num_voices: None,
+            reuse_policy: None,
+            note_pressures: None,
+            channel_pressure_01: None,
+        }
+    }
+
+    pub fn num_voices(mut self, num_voices: usize) -> Self {
+        self.num_voices = Some(num_voices);
+        self
+    }
+
+    pub fn reuse_policy(mut self, reuse_policy: impl PolyphonicVoiceReusePolicy + 'static) -> Self {
+        self.reuse_policy = Some(Box::new(reuse_policy));
+        self
+    }
+
+    pub fn note_pressures(mut self, note_pressures: impl Into<Signal<Vec<NotePressure>>>) -> Self {
+        self.note_pressures = Some(note_pressures.into());
+        self
+    }
+
+    pub fn channel_pressure_01(mut self, channel_pressure_01: impl Into<Sf64>) -> Self {
+        self.channel_pressure_01 = Some(channel_pressure_01.into());
+        self
+    }
+
+    fn build_with_levels(self, levels: Rc<Vec<Cell<f64>>>) -> Vec<VoiceDesc> {
+        let num_voices = self.num_voices.unwrap_or(8);
+        let reuse_policy = self.reuse_policy.unwrap_or_else(|| {
+            Box::new(polyphonic_voice_reuse_policy::Generational::new(num_voices))
+        });
+        VoiceDesc::polyphonic_from_key_events(
+            reuse_policy,
+            num_voices,
+            self.key_events,
+            PressureSources::new(self.note_pressures, self.channel_pressure_01),
+            levels,
+        )
+    }
+
+    pub fn build(self) -> Vec<VoiceDesc> {
+        let num_voices = self.num_voices.unwrap_or(8);
+        self.build_with_levels(Rc::new((0..num_voices).map(|_| Cell::new(0.0)).collect()))
+    }
+
+    /// Create a signal for each voice with `f` and mix them together. The output level of each
+    /// voice is tracked and made available to the reuse policy.
+    pub fn build_with<F: Fn(VoiceDesc) -> Sf64>(self, f: F) -> Sf64 {
+        // Time taken for the level follower to fall by 60dB
+        const LEVEL_DECAY_S: f64 = 1.0;
+        let num_voices = self.num_voices.unwrap_or(8);
+        let levels = Rc::new((0..num_voices).map(|_| Cell::new(0.0)).collect::<Vec<_>>());
+        self.build_with_levels(Rc::clone(&levels))
+            .into_iter()
+            .enumerate()
+            .map(|(i, voice_desc)| {
+                let levels = Rc::clone(&levels);
+                // The sample rate and the decay coefficient for that sample rate
+                let decay = Cell::new((0.0, 0.0));
+                f(voice_desc).map_ctx(move |x, ctx| {
+                    let (sample_rate_hz, mut decay_coefficient) = decay.get();
+                    if sample_rate_hz != ctx.sample_rate_hz {
+                        decay_coefficient =
+                            0.001f64.powf(1.0 / (LEVEL_DECAY_S * ctx.sample_rate_hz));
+                        decay.set((ctx.sample_rate_hz, decay_coefficient));
+                    }
+                    let level = &levels[i];
+                    level.set(x.abs().max(level.get() * decay_coefficient));
+                    x
+                })
+            })
+            .sum()
+    }
+}
+
+/// A left and right channel
+#[derive(Clone)]
+pub struct StereoPair {
+    pub left: Sf64,
+    pub right: Sf64,
+}
+
+impl StereoPair {
+    /// The average of both channels
+    pub fn mono(&self) -> Sf64 {
+        self.left
+            .zip(&self.right)
+            .map(|(left, right)| (left + right) / 2.0)
+    }
+}
+
+/// One of the copies of a voice in a unison stack
+#[derive(Clone)]
+pub struct UnisonVoiceDesc {
+    /// The voice being stacked, with this copy's detune added to its pitch bend. Use
+    /// `VoiceDesc::freq_hz` to get the detuned frequency.
+    pub voice_desc: VoiceDesc,
+    /// A random value chosen each time the key is pressed, intended to be used as the initial
+    /// phase of oscillators so that copies don't start in phase with each other
+    pub phase_01: Sf64,
+}
+
+pub struct UnisonBuilder {
+    key_events: Signal<Vec<KeyEvent>>,
+    num_unison_voices: Option<usize>,
+    voice_budget: Option<usize>,
+    detune_cents: Option<Sf64>,
+    stereo_spread_01: Option<Sf64>,
+    reuse_policy: Option<Box<dyn PolyphonicVoiceReusePolicy>>,
+}
+
+impl UnisonBuilder {
+    pub fn new(key_events: Signal<Vec<KeyEvent>>) -> Self {
+        Self {
+            key_events,
+            num_unison_voices: None,
+            voice_budget: None,
+            detune_cents: None,
+            stereo_spread_01: None,
+            reuse_policy: None,
+        }
+    }
+
+    /// The number of copies of each voice
+    pub fn num_unison_voices(mut self, num_unison_voices: usize) -> Self {
+        self.num_unison_voices = Some(num_unison_voices);
+        self
+    }
+
+    /// The total number of voices to create. Each note uses `num_unison_voices` voices so the
+    /// number of notes that can play at once is the budget divided by the number of unison
+    /// voices.
+    pub fn voice_budget(mut self, voice_budget: usize) -> Self {
+        self.voice_budget = Some(voice_budget);
+        self
+    }
+
+    /// The distance in cents between the highest and lowest copies and the note. Copies are
+    /// spread evenly between `-detune_cents` and `detune_cents`.
+    pub fn detune_cents(mut self, detune_cents: impl Into<Sf64>) -> Self {
+        self.detune_cents = Some(detune_cents.into());
+        self
+    }
+
+    /// 0 places all copies in the center and 1 spreads them across the full stereo field
+    pub fn stereo_spread_01(mut self, stereo_spread_01: impl Into<Sf64>) -> Self {
+        self.stereo_spread_01 = Some(stereo_spread_01.into());
+        self
+    }
+
+    pub fn reuse_policy(mut self, reuse_policy: impl PolyphonicVoiceReusePolicy + 'static) -> Self {
+        self.reuse_policy = Some(Box::new(reuse_policy));
+        self
+    }
+
+    /// Create a signal with `f` for each copy of each voice and mix them together
+    pub fn build_with<F: Fn(UnisonVoiceDesc) -> Sf64>(self, f: F) -> StereoPair {
+        let num_unison_voices = self.num_unison_voices.unwrap_or(3).max(1);
+        let num_voices = (self.voice_budget.unwrap_or(16) / num_unison_voices).max(1);
+        let detune_cents = self.detune_cents.unwrap_or_else(|| const_(10.0));
+        let stereo_spread_01 = self.stereo_spread_01.unwrap_or_else(|| const_(1.0));
+        let mut polyphony = self.key_events.polyphony().num_voices(num_voices);
+        if let Some(reuse_policy) = self.reuse_policy {
+            polyphony = polyphony.reuse_policy(reuse_policy);
+        }
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for voice_desc in polyphony.build() {
+            for i in 0..num_unison_voices {
+                // Position of this copy between -1 and 1
+                let position = if num_unison_voices == 1 {
+                    0.0
+                } else {
+                    ((2 * i) as f64 / (num_unison_voices - 1) as f64) - 1.0
+                };
+                let pitch_bend_semitones = voice_desc.pitch_bend_semitones.zip(&detune_cents).map(
+                    move |(pitch_bend_semitones, detune_cents)| {
+                        pitch_bend_semitones + (position * detune_cents / 100.0)
+                    },
+                );
+                let phase_01 = {
+                    let rng = RefCell::new(random::new_rng());
+                    let phase_01 = Cell::new(0.0);
+                    let key_press = voice_desc.key_press.clone();
+                    Signal::from_fn(move |ctx| {
+                        if key_press.sample(ctx) {
+                            phase_01.set(rng.borrow_mut().gen::<f64>());
+                        }
+                        phase_01.get()
+                    })
+                };
+                let signal = f(UnisonVoiceDesc {
+                    voice_desc: VoiceDesc {
+                        pitch_bend_semitones,
+                        ..voice_desc.clone()
+                    },
+                    phase_01,
+                });
+                // Equal power panning
+                let pan_angle = stereo_spread_01.map(move |stereo_spread_01| {
+                    (position * stereo_spread_01 + 1.0) * std::f64::consts::FRAC_PI_4
+                });
+                left.push(
+                    signal
+                        .zip(&pan_angle)
+                        .map(|(x, pan_angle)| x * pan_angle.cos()),
+                );
+                right.push(
+                    signal
+                        .zip(&pan_angle)
+                        .map(|(x, pan_angle)| x * pan_angle.sin()),
+                );
+            }
+        }
+        let scale = 1.0 / num_unison_voices as f64;
+        StereoPair {
+            left: left.into_iter().sum::<Sf64>() * scale,
+            right: right.into_iter().sum::<Sf64>() * scale,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 struct ArpeggiatorNoteStoreEntry {
     note: Note,
@@ -383,15 +1093,72 @@ pub enum ArpeggiatorShape {
     DownUp,
     Random,
     Indices(Vec<Option<usize>>),
+    /// The order in which the keys were pressed
+    AsPlayed,
+}
+
+/// The order in which the arpeggio is transposed by octaves after each pass over the notes
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpeggiatorOctavePattern {
+    /// Play each octave from the lowest to the highest, then wrap back to the lowest
+    #[default]
+    Up,
+    /// Play each octave from the highest to the lowest, then wrap back to the highest
+    Down,
+    /// Play each octave from the lowest to the highest and back down again
+    Pendulum,
+}
+
+impl ArpeggiatorOctavePattern {
+    /// The number of octaves to transpose by on the given pass over the notes
+    fn octave_offset(self, pass: usize, octave_range: u8) -> i8 {
+        let num_octaves = octave_range as usize + 1;
+        let offset = match self {
+            Self::Up => pass % num_octaves,
+            Self::Down => octave_range as usize - (pass % num_octaves),
+            Self::Pendulum => {
+                if octave_range == 0 {
+                    0
+                } else {
+                    let period = 2 * octave_range as usize;
+                    let position = pass % period;
+                    if position <= octave_range as usize {
+                        position
+                    } else {
+                        period - position
+                    }
+                }
+            }
+        };
+        offset as i8
+    }
 }
 
 #[derive(Debug)]
 struct ArpeggiatorState {
     store: ArpeggiatorNoteStore,
     index: usize,
-    current_note: Option<Note>,
     ascending: bool,
     rng: StdRng,
+    /// Notes in the order they were added to the store
+    as_played: Vec<Note>,
+    as_played_index: usize,
+    /// Keys which are physically held down
+    held_keys: Vec<Note>,
+    /// Keys which have been released but remain in the store because the arpeggiator is latched
+    latched_keys: Vec<Note>,
+    /// Number of steps taken in the current pass over the notes
+    pass_step: usize,
+    pass: usize,
+    step: usize,
+    /// The note selected on the most recent step, transposed by the octave pattern
+    step_note: Option<Note>,
+    step_velocity_01: f64,
+    /// The note which was most recently pressed and not yet released
+    sounding_note: Option<Note>,
+    samples_since_step: u64,
+    step_period_samples: Option<u64>,
+    ratchet_index: u8,
 }
 
 impl ArpeggiatorState {
@@ -399,9 +1166,21 @@ impl ArpeggiatorState {
         Self {
             store: ArpeggiatorNoteStore::default(),
             index: 0,
-            current_note: None,
             ascending: true,
-            rng: StdRng::from_entropy(),
+            rng: random::new_rng(),
+            as_played: Vec::new(),
+            as_played_index: 0,
+            held_keys: Vec::new(),
+            latched_keys: Vec::new(),
+            pass_step: 0,
+            pass: 0,
+            step: 0,
+            step_note: None,
+            step_velocity_01: 0.0,
+            sounding_note: None,
+            samples_since_step: 0,
+            step_period_samples: None,
+            ratchet_index: 0,
         }
     }
 
@@ -410,6 +1189,7 @@ impl ArpeggiatorState {
             if self.index > index {
                 self.index += 1;
             }
+            self.as_played.push(note);
         }
     }
     fn remove_note(&mut self, note: Note) {
@@ -417,10 +1197,67 @@ impl ArpeggiatorState {
             if self.index > index {
                 self.index -= 1;
             }
+            if let Some(index) = self.as_played.iter().position(|&n| n == note) {
+                self.as_played.remove(index);
+                if self.as_played_index > index {
+                    self.as_played_index -= 1;
+                }
+            }
+        }
+    }
+    /// Apply a function to a key and each of its octave extensions
+    fn for_each_extension<F: FnMut(&mut Self, Note)>(
+        &mut self,
+        note: Note,
+        extend_octaves_high: u8,
+        extend_octaves_low: u8,
+        mut f: F,
+    ) {
+        f(self, note);
+        for i in 0..extend_octaves_high {
+            if let Some(note) = note.add_octaves_checked(i as i8 + 1) {
+                f(self, note);
+            }
+        }
+        for i in 0..extend_octaves_low {
+            if let Some(note) = note.add_octaves_checked(-(i as i8 + 1)) {
+                f(self, note);
+            }
+        }
+    }
+    fn press_key(&mut self, note: Note, latch: bool, extend_high: u8, extend_low: u8) {
+        if latch && self.held_keys.is_empty() {
+            // Starting a new chord while latched replaces the latched chord
+            self.release_latched_keys(extend_high, extend_low);
+        }
+        self.held_keys.push(note);
+        if let Some(i) = self.latched_keys.iter().position(|&n| n == note) {
+            // The key is still in the store from when it was latched
+            self.latched_keys.remove(i);
+        } else {
+            self.for_each_extension(note, extend_high, extend_low, Self::insert_note);
+        }
+    }
+    fn release_key(&mut self, note: Note, latch: bool, extend_high: u8, extend_low: u8) {
+        if let Some(i) = self.held_keys.iter().position(|&n| n == note) {
+            self.held_keys.remove(i);
+        }
+        if latch {
+            self.latched_keys.push(note);
+        } else {
+            self.for_each_extension(note, extend_high, extend_low, Self::remove_note);
+        }
+    }
+    fn release_latched_keys(&mut self, extend_high: u8, extend_low: u8) {
+        for note in mem::take(&mut self.latched_keys) {
+            self.for_each_extension(note, extend_high, extend_low, Self::remove_note);
         }
     }
-    fn reset(&mut self, shape: ArpeggiatorShape) {
+    fn reset(&mut self, shape: &ArpeggiatorShape) {
         self.index = 0;
+        self.as_played_index = 0;
+        self.pass_step = 0;
+        self.pass = 0;
         use ArpeggiatorShape::*;
         match shape {
             Up | UpDown => {
@@ -429,7 +1266,7 @@ impl ArpeggiatorState {
             Down | DownUp => {
                 self.ascending = false;
             }
-            Random | Indices(_) => (),
+            Random | Indices(_) | AsPlayed => (),
         }
     }
     fn tick_up(&mut self) -> Note {
@@ -447,52 +1284,113 @@ impl ArpeggiatorState {
         self.index -= 1;
         self.store.entries[self.index].note
     }
-    fn tick(&mut self, shape: ArpeggiatorShape) {
-        self.current_note = if self.store.entries.is_empty() {
+    /// The number of steps before the shape repeats
+    fn pass_length(&self, shape: &ArpeggiatorShape) -> usize {
+        use ArpeggiatorShape::*;
+        let num_notes = self.store.entries.len();
+        match shape {
+            Up | Down | Random | AsPlayed => num_notes,
+            UpDown | DownUp => (2 * num_notes).saturating_sub(2).max(1),
+            Indices(indices) => indices.len(),
+        }
+    }
+    /// Choose the next note of the arpeggio before octave transposition
+    fn next_note(&mut self, shape: &ArpeggiatorShape) -> Option<Note> {
+        if self.store.entries.is_empty() {
             self.reset(shape);
-            None
-        } else {
-            use ArpeggiatorShape::*;
-            let note = match shape {
-                Up => self.tick_up(),
-                Down => self.tick_down(),
-                UpDown | DownUp => {
-                    if self.ascending {
-                        let note = self.tick_up();
-                        if self.index >= self.store.entries.len() {
-                            self.ascending = false;
-                            self.index = self.store.entries.len() - 1;
-                        }
-                        note
-                    } else {
-                        let note = self.tick_down();
-                        if self.index == 0 {
-                            self.ascending = true;
-                            self.index = 1;
-                        }
-                        note
+            return None;
+        }
+        use ArpeggiatorShape::*;
+        let note = match shape {
+            Up => self.tick_up(),
+            Down => self.tick_down(),
+            UpDown | DownUp => {
+                if self.ascending {
+                    let note = self.tick_up();
+                    if self.index >= self.store.entries.len() {
+                        self.ascending = false;
+                        self.index = self.store.entries.len() - 1;
                     }
+                    note
+                } else {
+                    let note = self.tick_down();
+                    if self.index == 0 {
+                        self.ascending = true;
+                        self.index = 1;
+                    }
+                    note
                 }
-                Random => {
-                    let index = self.rng.gen_range(0..self.store.entries.len());
-                    self.store.entries[index].note
+            }
+            Random => {
+                let index = self.rng.gen_range(0..self.store.entries.len());
+                self.store.entries[index].note
+            }
+            Indices(indices) => {
+                if indices.is_empty() {
+                    return None;
                 }
-                Indices(indices) => {
-                    if indices.is_empty() {
-                        self.current_note = None;
-                        return;
-                    }
-                    if self.index >= indices.len() {
-                        self.index = 0;
-                    }
-                    self.current_note =
-                        indices[self.index].and_then(|i| self.store.entries.get(i).map(|e| e.note));
-                    self.index += 1;
-                    return;
+                if self.index >= indices.len() {
+                    self.index = 0;
                 }
-            };
-            Some(note)
+                let note =
+                    indices[self.index].and_then(|i| self.store.entries.get(i).map(|e| e.note));
+                self.index += 1;
+                return note;
+            }
+            AsPlayed => {
+                if self.as_played_index >= self.as_played.len() {
+                    self.as_played_index = 0;
+                }
+                let note = self.as_played[self.as_played_index];
+                self.as_played_index += 1;
+                note
+            }
         };
+        Some(note)
+    }
+    /// Advance to the next step of the arpeggio, choosing its note and velocity
+    fn step(&mut self, config: &ArpeggiatorConfig, ctx: &SignalCtx) {
+        let shape = config.shape.sample(ctx);
+        let note = self.next_note(&shape);
+        let octave_offset = config
+            .octave_pattern
+            .sample(ctx)
+            .octave_offset(self.pass, config.octave_range.sample(ctx));
+        if !self.store.entries.is_empty() {
+            self.pass_step += 1;
+            if self.pass_step >= self.pass_length(&shape) {
+                self.pass_step = 0;
+                self.pass += 1;
+            }
+        }
+        self.step_note = note.and_then(|note| note.add_octaves_checked(octave_offset));
+        let accents = config.accents.sample(ctx);
+        let accent = !accents.is_empty() && accents[self.step % accents.len()];
+        self.step_velocity_01 = if accent {
+            config.accent_velocity_01.sample(ctx)
+        } else {
+            config.velocity_01.sample(ctx)
+        };
+        self.step += 1;
+    }
+    fn release_sounding_note(&mut self, ret: &mut Vec<KeyEvent>) {
+        if let Some(note) = self.sounding_note.take() {
+            ret.push(KeyEvent {
+                note,
+                pressed: false,
+                velocity_01: self.step_velocity_01,
+            });
+        }
+    }
+    fn press_step_note(&mut self, ret: &mut Vec<KeyEvent>) {
+        if let Some(note) = self.step_note {
+            ret.push(KeyEvent {
+                note,
+                pressed: true,
+                velocity_01: self.step_velocity_01,
+            });
+            self.sounding_note = Some(note);
+        }
     }
 }
 
@@ -501,6 +1399,22 @@ pub struct ArpeggiatorConfig {
     pub extend_octaves_high: Signal<u8>,
     pub extend_octaves_low: Signal<u8>,
     pub shape: Signal<ArpeggiatorShape>,
+    /// While the latch gate is high, released keys keep playing until a new key is pressed
+    /// with no other keys held
+    pub latch: Gate,
+    /// The fraction of each step (or each ratchet within a step) for which the note is held.
+    /// At 1 notes are held until the next step.
+    pub gate_length_01: Sf64,
+    /// The number of times each step's note is repeated within the step
+    pub ratchets: Signal<u8>,
+    /// The number of octaves above the played notes that the arpeggio is transposed into on
+    /// successive passes over the notes
+    pub octave_range: Signal<u8>,
+    pub octave_pattern: Signal<ArpeggiatorOctavePattern>,
+    /// Steps where the accent pattern is `true` are played with `accent_velocity_01` rather
+    /// than `velocity_01`. The pattern repeats independently of the notes.
+    pub accents: Signal<Vec<bool>>,
+    pub accent_velocity_01: Sf64,
 }
 
 impl Default for ArpeggiatorConfig {
@@ -510,6 +1424,13 @@ impl Default for ArpeggiatorConfig {
             extend_octaves_high: const_(0),
             extend_octaves_low: const_(0),
             shape: const_(ArpeggiatorShape::default()),
+            latch: Gate::never(),
+            gate_length_01: const_(1.0),
+            ratchets: const_(1),
+            octave_range: const_(0),
+            octave_pattern: const_(ArpeggiatorOctavePattern::default()),
+            accents: const_(Vec::new()),
+            accent_velocity_01: const_(1.0),
         }
     }
 }
@@ -539,6 +1460,51 @@ impl ArpeggiatorConfig {
             ..self
         }
     }
+    pub fn latch(self, latch: impl Into<Gate>) -> Self {
+        Self {
+            latch: latch.into(),
+            ..self
+        }
+    }
+    pub fn gate_length_01(self, gate_length_01: impl Into<Sf64>) -> Self {
+        Self {
+            gate_length_01: gate_length_01.into(),
+            ..self
+        }
+    }
+    pub fn ratchets(self, ratchets: impl Into<Signal<u8>>) -> Self {
+        Self {
+            ratchets: ratchets.into(),
+            ..self
+        }
+    }
+    pub fn octave_range(self, octave_range: impl Into<Signal<u8>>) -> Self {
+        Self {
+            octave_range: octave_range.into(),
+            ..self
+        }
+    }
+    pub fn octave_pattern(
+        self,
+        octave_pattern: impl Into<Signal<ArpeggiatorOctavePattern>>,
+    ) -> Self {
+        Self {
+            octave_pattern: octave_pattern.into(),
+            ..self
+        }
+    }
+    pub fn accents(self, accents: impl Into<Signal<Vec<bool>>>) -> Self {
+        Self {
+            accents: accents.into(),
+            ..self
+        }
+    }
+    pub fn accent_velocity_01(self, accent_velocity_01: impl Into<Sf64>) -> Self {
+        Self {
+            accent_velocity_01: accent_velocity_01.into(),
+            ..self
+        }
+    }
 }
 
 impl From<ArpeggiatorShape> for Signal<ArpeggiatorShape> {
@@ -547,9 +1513,122 @@ impl From<ArpeggiatorShape> for Signal<ArpeggiatorShape> {
     }
 }
 
+impl From<ArpeggiatorOctavePattern> for Signal<ArpeggiatorOctavePattern> {
+    fn from(value: ArpeggiatorOctavePattern) -> Self {
+        const_(value)
+    }
+}
+
+impl From<Vec<bool>> for Signal<Vec<bool>> {
+    fn from(value: Vec<bool>) -> Self {
+        const_(value)
+    }
+}
+
+/// Tracks which key releases are being deferred by the sustain and sostenuto pedals
+#[derive(Default)]
+struct PedalState {
+    sustain: bool,
+    sostenuto: bool,
+    /// Keys which are physically held down
+    held: Vec<Note>,
+    /// Keys which were held when the sostenuto pedal was pressed
+    sostenuto_notes: Vec<Note>,
+    /// Release events which will be emitted once no pedal is holding their note
+    deferred_releases: Vec<KeyEvent>,
+}
+
+impl PedalState {
+    fn is_note_pedalled(&self, note: Note) -> bool {
+        self.sustain || (self.sostenuto && self.sostenuto_notes.contains(&note))
+    }
+
+    fn emit_releases_no_longer_pedalled(&mut self, out: &mut Vec<KeyEvent>) {
+        let deferred_releases = mem::take(&mut self.deferred_releases);
+        for key_event in deferred_releases {
+            if self.is_note_pedalled(key_event.note) {
+                self.deferred_releases.push(key_event);
+            } else {
+                out.push(key_event);
+            }
+        }
+    }
+
+    fn set_pedals(&mut self, sustain: bool, sostenuto: bool, out: &mut Vec<KeyEvent>) {
+        if sostenuto && !self.sostenuto {
+            self.sostenuto_notes.clone_from(&self.held);
+        }
+        if !sostenuto {
+            self.sostenuto_notes.clear();
+        }
+        let released = (self.sustain && !sustain) || (self.sostenuto && !sostenuto);
+        self.sustain = sustain;
+        self.sostenuto = sostenuto;
+        if released {
+            self.emit_releases_no_longer_pedalled(out);
+        }
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent, out: &mut Vec<KeyEvent>) {
+        self.held.retain(|&note| note != key_event.note);
+        if key_event.pressed {
+            // Pressing a key whose release is being deferred ends the previous note before
+            // starting the new one.
+            if let Some(i) = self
+                .deferred_releases
+                .iter()
+                .position(|deferred| deferred.note == key_event.note)
+            {
+                out.push(self.deferred_releases.remove(i));
+            }
+            self.held.push(key_event.note);
+            out.push(key_event);
+        } else if self.is_note_pedalled(key_event.note) {
+            self.deferred_releases.push(key_event);
+        } else {
+            out.push(key_event);
+        }
+    }
+}
+
 impl Signal<Vec<KeyEvent>> {
+    /// Defer key releases while the sustain pedal is down. Keys held when the sostenuto pedal
+    /// is pressed also have their releases deferred until the sostenuto pedal is released, but
+    /// keys pressed after that are unaffected. The pedals can be any gate, such as the state of
+    /// a midi controller or a key on a computer keyboard.
+    pub fn with_pedals(&self, sustain: Gate, sostenuto: Gate) -> Self {
+        let state = RefCell::new(PedalState::default());
+        let key_events = self.clone();
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let mut out = Vec::new();
+            state.set_pedals(sustain.sample(ctx), sostenuto.sample(ctx), &mut out);
+            for key_event in key_events.sample(ctx) {
+                state.handle_key_event(key_event, &mut out);
+            }
+            out
+        })
+    }
+
+    pub fn with_sustain_pedal(&self, sustain: Gate) -> Self {
+        self.with_pedals(sustain, Gate::never())
+    }
+
+    pub fn with_sostenuto_pedal(&self, sostenuto: Gate) -> Self {
+        self.with_pedals(Gate::never(), sostenuto)
+    }
+
     pub fn voice_desc_monophonic(&self) -> VoiceDesc {
-        VoiceDesc::monophonic_from_key_events(self.clone())
+        VoiceDesc::monophonic_from_key_events(
+            self.clone(),
+            PressureSources::new(None, None),
+            MonophonicNotePriority::Last,
+            false,
+        )
+    }
+
+    pub fn monophony(&self) -> MonophonyBuilder {
+        MonophonyBuilder::new(self.clone())
     }
 
     pub fn voice_descs_polyphonic(
@@ -557,12 +1636,12 @@ impl Signal<Vec<KeyEvent>> {
         num_persistent_voices: usize,
         num_transient_voices: usize,
     ) -> Vec<VoiceDesc> {
-        let reuse_policy = polyphonic_voice_reuse_policy::Generational::new(num_persistent_voices);
-        VoiceDesc::polyphonic_from_key_events(
-            reuse_policy,
-            num_persistent_voices + num_transient_voices,
-            self.clone(),
-        )
+        self.polyphony()
+            .num_voices(num_persistent_voices + num_transient_voices)
+            .reuse_policy(polyphonic_voice_reuse_policy::Generational::new(
+                num_persistent_voices,
+            ))
+            .build()
     }
 
     pub fn polyphonic_with<F: Fn(VoiceDesc) -> Sf64>(
@@ -577,59 +1656,64 @@ impl Signal<Vec<KeyEvent>> {
             .sum()
     }
 
+    pub fn polyphony(&self) -> PolyphonyBuilder {
+        PolyphonyBuilder::new(self.clone())
+    }
+
+    pub fn unison(&self) -> UnisonBuilder {
+        UnisonBuilder::new(self.clone())
+    }
+
     pub fn arpeggiate(&self, trigger: impl Into<Trigger>, config: ArpeggiatorConfig) -> Self {
         let trigger = trigger.into();
         let state = RefCell::new(ArpeggiatorState::new());
         self.map_ctx(move |key_events, ctx| {
             let mut state = state.borrow_mut();
+            let latch = config.latch.sample(ctx);
+            let extend_high = config.extend_octaves_high.sample(ctx);
+            let extend_low = config.extend_octaves_low.sample(ctx);
+            if !latch {
+                state.release_latched_keys(extend_high, extend_low);
+            }
             for key_event in key_events {
                 if key_event.pressed {
-                    state.insert_note(key_event.note);
-                    for i in 0..config.extend_octaves_high.sample(ctx) {
-                        if let Some(note) = key_event.note.add_octaves_checked(i as i8 + 1) {
-                            state.insert_note(note);
-                        }
-                    }
-                    for i in 0..config.extend_octaves_low.sample(ctx) {
-                        if let Some(note) = key_event.note.add_octaves_checked(-(i as i8 + 1)) {
-                            state.insert_note(note);
-                        }
-                    }
+                    state.press_key(key_event.note, latch, extend_high, extend_low);
                 } else {
-                    state.remove_note(key_event.note);
-                    for i in 0..config.extend_octaves_high.sample(ctx) {
-                        if let Some(note) = key_event.note.add_octaves_checked(i as i8 + 1) {
-                            state.remove_note(note);
-                        }
-                    }
-                    for i in 0..config.extend_octaves_low.sample(ctx) {
-                        if let Some(note) = key_event.note.add_octaves_checked(-(i as i8 + 1)) {
-                            state.remove_note(note);
-                        }
-                    }
+                    state.release_key(key_event.note, latch, extend_high, extend_low);
                 }
             }
+            let mut ret = Vec::new();
+            state.samples_since_step += 1;
             if trigger.sample(ctx) {
-                let mut ret = Vec::new();
-                if let Some(note) = state.current_note {
-                    ret.push(KeyEvent {
-                        note,
-                        pressed: false,
-                        velocity_01: config.velocity_01.sample(ctx),
-                    });
+                if state.step > 0 {
+                    state.step_period_samples = Some(state.samples_since_step);
                 }
-                state.tick(config.shape.sample(ctx));
-                if let Some(note) = state.current_note {
-                    ret.push(KeyEvent {
-                        note,
-                        pressed: true,
-                        velocity_01: config.velocity_01.sample(ctx),
-                    });
+                state.samples_since_step = 0;
+                state.ratchet_index = 0;
+                state.release_sounding_note(&mut ret);
+                state.step(&config, ctx);
+                state.press_step_note(&mut ret);
+            } else if let Some(step_period_samples) = state.step_period_samples {
+                // Gate lengths and ratchets are timed relative to the duration of the previous
+                // step, so they take effect from the second step onwards.
+                let ratchets = config.ratchets.sample(ctx).max(1);
+                let ratchet_period_samples = step_period_samples as f64 / ratchets as f64;
+                let samples_since_ratchet = state.samples_since_step as f64
+                    - (state.ratchet_index as f64 * ratchet_period_samples);
+                let gate_length_01 = config.gate_length_01.sample(ctx);
+                let is_last_ratchet = state.ratchet_index + 1 >= ratchets;
+                if (gate_length_01 < 1.0 || !is_last_ratchet)
+                    && samples_since_ratchet >= gate_length_01 * ratchet_period_samples
+                {
+                    state.release_sounding_note(&mut ret);
+                }
+                if !is_last_ratchet && samples_since_ratchet >= ratchet_period_samples {
+                    state.ratchet_index += 1;
+                    state.release_sounding_note(&mut ret);
+                    state.press_step_note(&mut ret);
                 }
-                ret
-            } else {
-                Vec::new()
             }
+            ret
         })
     }
 }
@@ -734,3 +1818,335 @@ impl Signal<Option<Chord>> {
         })
     }
 }
+
+#[cfg(test)]
+fn test_voice(
+    midi_index: u8,
+    key_down: bool,
+    key_press_sample_index: u64,
+    key_release_sample_index: u64,
+    level_01: f64,
+) -> PolyphonicVoice {
+    PolyphonicVoice {
+        key: HeldKey {
+            note: Note::from_midi_index(midi_index),
+            ..Default::default()
+        },
+        key_down,
+        key_press: false,
+        key_press_sample_index,
+        key_release_sample_index,
+        level_01,
+        has_played: true,
+    }
+}
+
+#[cfg(test)]
+fn test_key_press(midi_index: u8) -> KeyEvent {
+    KeyEvent {
+        note: Note::from_midi_index(midi_index),
+        pressed: true,
+        velocity_01: 1.0,
+    }
+}
+
+#[test]
+fn test_reuse_policy_generational() {
+    use polyphonic_voice_reuse_policy::Generational;
+    // The oldest released voice is preferred
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, false, 20, 30, 0.0),
+        test_voice(67, false, 5, 25, 0.0),
+    ];
+    assert_eq!(
+        Generational::new(1).choose_voice_index(&test_key_press(62), &voices),
+        Some(2)
+    );
+    // When all keys are held, the oldest n keys keep playing and the next oldest is stolen
+    let voices = [
+        test_voice(60, true, 5, 0, 0.0),
+        test_voice(64, true, 10, 0, 0.0),
+        test_voice(67, true, 20, 0, 0.0),
+    ];
+    assert_eq!(
+        Generational::new(1).choose_voice_index(&test_key_press(62), &voices),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_reuse_policy_oldest() {
+    use polyphonic_voice_reuse_policy::Oldest;
+    // The voice released longest ago is preferred, even if it was pressed more recently
+    let voices = [
+        test_voice(60, true, 1, 0, 0.0),
+        test_voice(64, false, 20, 25, 0.0),
+        test_voice(67, false, 5, 30, 0.0),
+    ];
+    assert_eq!(
+        Oldest.choose_voice_index(&test_key_press(62), &voices),
+        Some(1)
+    );
+    // When all keys are held, the voice pressed longest ago is stolen
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, true, 20, 0, 0.0),
+        test_voice(67, true, 5, 0, 0.0),
+    ];
+    assert_eq!(
+        Oldest.choose_voice_index(&test_key_press(62), &voices),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_reuse_policy_quietest() {
+    use polyphonic_voice_reuse_policy::Quietest;
+    // The quietest released voice is preferred over quieter held voices
+    let voices = [
+        test_voice(60, true, 10, 0, 0.1),
+        test_voice(64, false, 20, 30, 0.5),
+        test_voice(67, false, 5, 25, 0.8),
+    ];
+    assert_eq!(
+        Quietest.choose_voice_index(&test_key_press(62), &voices),
+        Some(1)
+    );
+    let voices = [
+        test_voice(60, true, 10, 0, 0.3),
+        test_voice(64, true, 20, 0, 0.2),
+        test_voice(67, true, 5, 0, 0.4),
+    ];
+    assert_eq!(
+        Quietest.choose_voice_index(&test_key_press(62), &voices),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_reuse_policy_note_priority() {
+    use polyphonic_voice_reuse_policy::{NotePriority, NotePriorityKind};
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, true, 20, 0, 0.0),
+        test_voice(67, true, 5, 0, 0.0),
+    ];
+    // The highest note is stolen by a lower note, and higher notes are dropped
+    let mut lowest = NotePriority(NotePriorityKind::Lowest);
+    assert_eq!(
+        lowest.choose_voice_index(&test_key_press(62), &voices),
+        Some(2)
+    );
+    assert_eq!(
+        lowest.choose_voice_index(&test_key_press(70), &voices),
+        None
+    );
+    // The lowest note is stolen by a higher note, and lower notes are dropped
+    let mut highest = NotePriority(NotePriorityKind::Highest);
+    assert_eq!(
+        highest.choose_voice_index(&test_key_press(62), &voices),
+        Some(0)
+    );
+    assert_eq!(
+        highest.choose_voice_index(&test_key_press(55), &voices),
+        None
+    );
+    // Released voices are used before stealing
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, false, 20, 30, 0.0),
+    ];
+    assert_eq!(
+        lowest.choose_voice_index(&test_key_press(70), &voices),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_reuse_policy_round_robin() {
+    use polyphonic_voice_reuse_policy::RoundRobin;
+    let mut round_robin = RoundRobin::default();
+    // Held voices are skipped
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, false, 20, 30, 0.0),
+        test_voice(67, false, 5, 25, 0.0),
+    ];
+    let chosen = (0..3)
+        .map(|_| round_robin.choose_voice_index(&test_key_press(62), &voices))
+        .collect::<Vec<_>>();
+    assert_eq!(chosen, vec![Some(1), Some(2), Some(1)]);
+    // When all keys are held the next voice in order is stolen
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, true, 20, 0, 0.0),
+        test_voice(67, true, 5, 0, 0.0),
+    ];
+    assert_eq!(
+        round_robin.choose_voice_index(&test_key_press(62), &voices),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_reuse_policy_retrigger_same_note() {
+    use polyphonic_voice_reuse_policy::{Oldest, RetriggerSameNote};
+    let mut retrigger = RetriggerSameNote(Oldest);
+    let voices = [
+        test_voice(60, true, 10, 0, 0.0),
+        test_voice(64, false, 20, 30, 0.0),
+        test_voice(67, false, 5, 25, 0.0),
+    ];
+    // A voice releasing the same note is retriggered rather than using the oldest voice
+    assert_eq!(
+        retrigger.choose_voice_index(&test_key_press(64), &voices),
+        Some(1)
+    );
+    assert_eq!(
+        retrigger.choose_voice_index(&test_key_press(62), &voices),
+        Some(2)
+    );
+    // Voices that have never played don't match the note they were initialized with
+    let voices = [
+        test_voice(60, false, 5, 10, 0.0),
+        PolyphonicVoice {
+            key_release_sample_index: 20,
+            ..Default::default()
+        },
+    ];
+    let default_midi_index = voices[1].note().to_midi_index();
+    assert!(!voices[1].has_played());
+    assert_eq!(
+        retrigger.choose_voice_index(&test_key_press(default_midi_index), &voices),
+        Some(0)
+    );
+}
+
+/// Arpeggiate key events with a step every 100 samples, returning the sample index, midi index
+/// and pressed state of each output event
+#[cfg(test)]
+fn test_arpeggiate(
+    key_events: impl Fn(u64) -> Vec<KeyEvent> + 'static,
+    config: ArpeggiatorConfig,
+    num_samples: u64,
+) -> Vec<(u64, u8, bool)> {
+    let output = Signal::from_fn(move |ctx| key_events(ctx.sample_index)).arpeggiate(
+        Signal::from_fn(|ctx| {
+            let sample_in_step = ctx.sample_index % 100;
+            sample_in_step == 0
+        })
+        .to_trigger_raw(),
+        config,
+    );
+    let mut events = Vec::new();
+    for sample_index in 0..num_samples {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        for key_event in output.sample(&ctx) {
+            events.push((
+                sample_index,
+                key_event.note.to_midi_index(),
+                key_event.pressed,
+            ));
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+fn test_key_release(midi_index: u8) -> KeyEvent {
+    KeyEvent {
+        pressed: false,
+        ..test_key_press(midi_index)
+    }
+}
+
+#[test]
+fn test_arpeggiator_latch() {
+    let events = test_arpeggiate(
+        |sample_index| match sample_index {
+            0 => vec![test_key_press(60), test_key_press(64)],
+            150 => vec![test_key_release(60), test_key_release(64)],
+            // Pressing a key with no keys held replaces the latched chord
+            350 => vec![test_key_press(67)],
+            _ => Vec::new(),
+        },
+        ArpeggiatorConfig::default().latch(const_(true).to_gate()),
+        600,
+    );
+    let presses = events
+        .iter()
+        .filter(|(_, _, pressed)| *pressed)
+        .map(|&(_, midi_index, _)| midi_index)
+        .collect::<Vec<_>>();
+    assert_eq!(presses, vec![60, 64, 60, 64, 67, 67]);
+}
+
+#[test]
+fn test_arpeggiator_ratchets() {
+    let events = test_arpeggiate(
+        |sample_index| {
+            if sample_index == 0 {
+                vec![test_key_press(60)]
+            } else {
+                Vec::new()
+            }
+        },
+        ArpeggiatorConfig::default().ratchets(2).gate_length_01(0.5),
+        300,
+    );
+    // Ratchets take effect once the step period is known after the second step
+    assert_eq!(
+        events,
+        vec![
+            (0, 60, true),
+            (100, 60, false),
+            (100, 60, true),
+            (125, 60, false),
+            (150, 60, true),
+            (175, 60, false),
+            (200, 60, true),
+            (225, 60, false),
+            (250, 60, true),
+            (275, 60, false),
+        ]
+    );
+}
+
+#[test]
+fn test_arpeggiator_octave_patterns() {
+    let presses = |notes: &'static [u8], octave_range: u8, octave_pattern| {
+        test_arpeggiate(
+            move |sample_index| {
+                if sample_index == 0 {
+                    notes.iter().map(|&note| test_key_press(note)).collect()
+                } else {
+                    Vec::new()
+                }
+            },
+            ArpeggiatorConfig::default()
+                .octave_range(octave_range)
+                .octave_pattern(octave_pattern),
+            600,
+        )
+        .into_iter()
+        .filter(|(_, _, pressed)| *pressed)
+        .map(|(_, midi_index, _)| midi_index)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        presses(&[60, 64], 1, ArpeggiatorOctavePattern::Up),
+        vec![60, 64, 72, 76, 60, 64]
+    );
+    assert_eq!(
+        presses(&[60, 64], 1, ArpeggiatorOctavePattern::Down),
+        vec![72, 76, 60, 64, 72, 76]
+    );
+    assert_eq!(
+        presses(&[60], 2, ArpeggiatorOctavePattern::Pendulum),
+        vec![60, 72, 84, 72, 60, 72]
+    );
+}
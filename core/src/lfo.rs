@@ -0,0 +1,251 @@
+use crate::{
+    random,
+    signal::{const_, Sf64, Sfreq, Signal, Trigger},
+};
+use rand::{rngs::StdRng, Rng};
+use std::{cell::RefCell, f64::consts::PI};
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    RampUp,
+    RampDown,
+    Square,
+    /// A new random value each cycle
+    SampleAndHold,
+    /// Moves smoothly between a new random value each cycle
+    SmoothRandom,
+    /// Rises slowly then quickly over each cycle
+    ExponentialUp,
+    /// Falls quickly then slowly over each cycle
+    ExponentialDown,
+}
+
+impl From<LfoShape> for Signal<LfoShape> {
+    fn from(value: LfoShape) -> Self {
+        const_(value)
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LfoPolarity {
+    /// Output between -1 and 1
+    #[default]
+    Bipolar,
+    /// Output between 0 and 1
+    Unipolar,
+}
+
+pub struct Lfo {
+    pub shape: Signal<LfoShape>,
+    pub freq: Sfreq,
+    pub polarity: LfoPolarity,
+    /// Added to the phase of the LFO, so that LFOs retriggered together can be offset from
+    /// one another
+    pub phase_offset_01: Sf64,
+    /// Time taken for the LFO to reach full depth after starting or being retriggered
+    pub fade_in_s: Sf64,
+    /// Restarts the LFO from the start of its cycle
+    pub retrigger: Trigger,
+    /// Play a single cycle after each retrigger and then hold the final value
+    pub one_shot: bool,
+}
+
+/// Controls the curvature of the exponential shapes
+const EXPONENTIAL_CURVATURE: f64 = 4.0;
+
+struct LfoState {
+    rng: StdRng,
+    phase_01: f64,
+    /// The phase after applying the offset on the previous sample. Random values change when
+    /// this phase wraps so that they stay in step with the shape.
+    previous_offset_phase_01: Option<f64>,
+    previous_random: f64,
+    current_random: f64,
+    samples_since_retrigger: u64,
+    finished: bool,
+}
+
+impl LfoState {
+    fn next_random(&mut self) {
+        self.previous_random = self.current_random;
+        self.current_random = (self.rng.gen::<f64>() * 2.0) - 1.0;
+    }
+
+    fn sample_shape(&self, shape: LfoShape, phase_01: f64) -> f64 {
+        match shape {
+            LfoShape::Sine => (phase_01 * PI * 2.0).sin(),
+            LfoShape::Triangle => (((phase_01 * 2.0) - 1.0).abs() * 2.0) - 1.0,
+            LfoShape::RampUp => (phase_01 * 2.0) - 1.0,
+            LfoShape::RampDown => 1.0 - (phase_01 * 2.0),
+            LfoShape::Square => {
+                if phase_01 < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SampleAndHold => self.current_random,
+            LfoShape::SmoothRandom => {
+                let t = (1.0 - (phase_01 * PI).cos()) / 2.0;
+                self.previous_random + ((self.current_random - self.previous_random) * t)
+            }
+            LfoShape::ExponentialUp | LfoShape::ExponentialDown => {
+                let x = if shape == LfoShape::ExponentialUp {
+                    phase_01
+                } else {
+                    1.0 - phase_01
+                };
+                let y =
+                    ((EXPONENTIAL_CURVATURE * x).exp() - 1.0) / (EXPONENTIAL_CURVATURE.exp() - 1.0);
+                (y * 2.0) - 1.0
+            }
+        }
+    }
+}
+
+impl Lfo {
+    pub fn signal(self) -> Sf64 {
+        let mut rng = random::new_rng();
+        let current_random = (rng.gen::<f64>() * 2.0) - 1.0;
+        let state = RefCell::new(LfoState {
+            rng,
+            phase_01: 0.0,
+            previous_offset_phase_01: None,
+            previous_random: current_random,
+            current_random,
+            samples_since_retrigger: 0,
+            finished: false,
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            if self.retrigger.sample(ctx) {
+                state.phase_01 = 0.0;
+                state.samples_since_retrigger = 0;
+                state.finished = false;
+                state.previous_offset_phase_01 = None;
+                state.next_random();
+            }
+            let phase_01 = if state.finished {
+                // Hold the value from the end of the cycle
+                1.0 - f64::EPSILON
+            } else {
+                state.phase_01
+            };
+            let phase_01 = (phase_01 + self.phase_offset_01.sample(ctx)).rem_euclid(1.0);
+            if let Some(previous_offset_phase_01) = state.previous_offset_phase_01 {
+                // A jump back of more than half a cycle means the phase wrapped around
+                if previous_offset_phase_01 - phase_01 > 0.5 {
+                    state.next_random();
+                }
+            }
+            state.previous_offset_phase_01 = Some(phase_01);
+            let bipolar = state.sample_shape(self.shape.sample(ctx), phase_01);
+            let fade_in_samples = self.fade_in_s.sample(ctx) * ctx.sample_rate_hz;
+            let depth = if fade_in_samples > 0.0 {
+                (state.samples_since_retrigger as f64 / fade_in_samples).min(1.0)
+            } else {
+                1.0
+            };
+            state.samples_since_retrigger += 1;
+            if !state.finished {
+                state.phase_01 += self.freq.sample(ctx).hz() / ctx.sample_rate_hz;
+                if state.phase_01 >= 1.0 {
+                    if self.one_shot {
+                        state.finished = true;
+                    } else {
+                        state.phase_01 = state.phase_01.fract();
+                    }
+                }
+            }
+            match self.polarity {
+                LfoPolarity::Bipolar => bipolar * depth,
+                LfoPolarity::Unipolar => ((bipolar + 1.0) / 2.0) * depth,
+            }
+        })
+    }
+}
+
+/// Render the first `num_samples` samples of a signal at 1024Hz, a rate at which LFO phase
+/// increments of power-of-two frequencies are exact
+#[cfg(test)]
+fn test_render(signal: &Sf64, num_samples: u64) -> Vec<f64> {
+    use crate::signal::SignalCtx;
+    (0..num_samples)
+        .map(|sample_index| {
+            signal.sample(&SignalCtx {
+                sample_index,
+                sample_rate_hz: 1024.0,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_lfo_tempo_sync() {
+    use crate::builder::lfo::lfo;
+    // A cycle every 16th note at 120bpm is 8Hz, or 128 samples
+    let output = test_render(&lfo(LfoShape::RampUp).tempo_sync(120.0, 0.25).build(), 512);
+    let wrap_sample_indices = (1..output.len())
+        .filter(|&i| output[i] < output[i - 1])
+        .collect::<Vec<_>>();
+    assert_eq!(wrap_sample_indices, vec![128, 256, 384]);
+    assert_eq!(output[64], 0.0);
+}
+
+#[test]
+fn test_lfo_fade_in() {
+    use crate::builder::lfo::lfo;
+    let retrigger = Signal::from_fn(|ctx| ctx.sample_index == 256).to_trigger_raw();
+    // The square wave stays at 1 for the first half of each 1 second cycle
+    let output = test_render(
+        &lfo(LfoShape::Square)
+            .freq_hz(1.0)
+            .fade_in_s(0.125)
+            .retrigger(retrigger)
+            .build(),
+        512,
+    );
+    assert_eq!(output[0], 0.0);
+    assert_eq!(output[64], 0.5);
+    assert_eq!(output[128], 1.0);
+    assert_eq!(output[200], 1.0);
+    // Retriggering restarts the fade
+    assert_eq!(output[256], 0.0);
+    assert_eq!(output[320], 0.5);
+    assert_eq!(output[384], 1.0);
+}
+
+#[test]
+fn test_lfo_random_reseed() {
+    use crate::builder::lfo::lfo;
+    // Returns the sample indices at which the output changes
+    let changes = |output: Vec<f64>| {
+        (1..output.len())
+            .filter(|&i| output[i] != output[i - 1])
+            .collect::<Vec<_>>()
+    };
+    let retrigger = || Signal::from_fn(|ctx| ctx.sample_index == 300).to_trigger_raw();
+    let output = random::with_random_seed(42, || {
+        lfo(LfoShape::SampleAndHold)
+            .freq_hz(8.0)
+            .retrigger(retrigger())
+            .build()
+    });
+    // A new value is chosen at the end of each 128 sample cycle and when retriggered
+    assert_eq!(changes(test_render(&output, 512)), vec![128, 256, 300, 428]);
+    // With a phase offset the value changes when the offset phase wraps
+    let output = random::with_random_seed(42, || {
+        lfo(LfoShape::SampleAndHold)
+            .freq_hz(8.0)
+            .phase_offset_01(0.5)
+            .retrigger(retrigger())
+            .build()
+    });
+    assert_eq!(
+        changes(test_render(&output, 512)),
+        vec![64, 192, 300, 364, 492]
+    );
+}
@@ -1,12 +1,19 @@
+pub mod additive;
 pub mod builder;
 pub mod clock;
 pub mod envelope;
 pub mod filters;
+pub mod granular;
 pub mod keyboard;
+pub mod lfo;
 pub mod loopers;
+pub mod multisample;
+pub mod noise;
 pub mod patches;
+pub mod random;
 pub mod sampler;
 pub mod sequencers;
+pub mod tap_tempo;
 pub mod templates;
 pub mod util;
 
@@ -15,6 +22,8 @@ mod freeverb;
 #[cfg(feature = "midi")]
 pub mod midi;
 mod moog_ladder_low_pass_filter;
+#[cfg(feature = "midi")]
+pub mod mpe;
 pub mod music;
 pub mod oscillator;
 pub mod signal;
@@ -22,9 +31,16 @@ pub mod signal_arithmetic;
 
 pub mod prelude {
     #[cfg(feature = "midi")]
-    pub use crate::midi::{MidiControllerTable, MidiEvent, MidiEvents, MidiMessage, MidiMessages};
+    pub use crate::midi::{
+        MidiClockSync, MidiControllerTable, MidiEvent, MidiEvents, MidiMessage, MidiMessages,
+        MidiTransport, VelocityCurve,
+    };
+    #[cfg(feature = "midi")]
+    pub use crate::mpe::{MpeZone, MpeZoneKind};
     pub use crate::{
+        additive::{PartialRatio, Spectrum},
         builder::{
+            additive::{additive, additive_hz},
             env::adsr_linear_01,
             filter::{
                 compress, delay, delay_s, down_sample, echo, high_pass_butterworth,
@@ -34,17 +50,33 @@ pub mod prelude {
             },
             gate::{
                 periodic_gate, periodic_gate_hz, periodic_gate_s, periodic_trigger,
-                periodic_trigger_hz, periodic_trigger_s,
+                periodic_trigger_hz, periodic_trigger_s, tap_tempo, tap_tempo_gate,
+            },
+            granular::granular,
+            lfo::lfo,
+            loopers::{
+                clocked_audio_looper, clocked_key_event_looper,
+                clocked_midi_note_monophonic_looper, clocked_trigger_looper,
             },
-            loopers::{clocked_midi_note_monophonic_looper, clocked_trigger_looper},
             oscillator::{oscillator, oscillator_hz, oscillator_s},
             patches::{
-                hat_closed, kick, pulse_pwm, pulse_pwm_hz, snare, supersaw, supersaw_hz,
-                triggerable,
+                blown_pipe, blown_pipe_hz, blown_pipe_voice, bowed_string, bowed_string_hz,
+                bowed_string_voice, hat_closed, kick, pluck, pluck_hz, pluck_voice, pulse_pwm,
+                pulse_pwm_hz, snare, struck_bar, struck_bar_hz, struck_bar_voice, supersaw,
+                supersaw_hz, triggerable,
             },
             sampler::sampler,
+            sequencers::arrangement,
+        },
+        granular::GrainWindow,
+        keyboard::{
+            polyphonic_voice_reuse_policy, ArpeggiatorConfig, ArpeggiatorOctavePattern,
+            ArpeggiatorShape, ChordVoiceConfig, GlideMode, KeyEvent, MonophonicNotePriority,
+            NotePressure, PolyphonicVoiceReusePolicy, StereoPair, UnisonVoiceDesc, VoiceDesc,
         },
-        keyboard::{ArpeggiatorConfig, ArpeggiatorShape, ChordVoiceConfig, KeyEvent, VoiceDesc},
+        lfo::{LfoPolarity, LfoShape},
+        loopers::{LooperSequence, MidiNoteLooperEntry},
+        multisample::{MultiSampleInstrument, SampleRegion},
         music::{
             chord::{
                 chord, Chord, ChordPosition, ChordType, Inversion, DIMINISHED, MAJOR, MINOR, OPEN,
@@ -54,9 +86,15 @@ pub mod prelude {
             octave::*,
             semitone_ratio, Note, NoteName, Octave,
         },
-        oscillator::Waveform,
-        sampler::{Sample, Sampler},
-        sequencers::{bitwise_pattern_triggers_8, drum_loop_8},
+        noise::{
+            blue_noise, brown_noise, pink_noise, random_smooth, random_stepped, random_walk,
+            velvet_noise, violet_noise,
+        },
+        oscillator::{OscillatorOutputs, SubOscillatorWaveform, Waveform},
+        patches::physical::ModalMode,
+        random::{clear_random_seed, set_random_seed, with_random_seed},
+        sampler::{Interpolation, LoopMode, Sample, SampleLoop, Sampler},
+        sequencers::{bitwise_pattern_triggers_8, drum_loop_8, SequencedTriggers},
         signal::{
             const_, first_some, freq_hz, freq_s, mean, noise, noise_01, sfreq_hz, sfreq_s,
             sfreq_to_hz, sfreq_to_s, sum, triggerable, Freq, Gate, Sf64, Sfreq, Signal, Su8,
@@ -1,11 +1,69 @@
-use crate::signal::*;
-use std::cell::RefCell;
+use crate::{clock::ClockPeriodTracker, keyboard::KeyEvent, music::Note, signal::*};
+use std::{cell::RefCell, rc::Rc};
+
+/// Shared handle to the sequence recorded by a looper. Cloning the handle creates a reference to
+/// the same sequence, so a clone can be kept by the caller to save the contents of a looper while
+/// it is playing (e.g. when the program exits), and a handle created from a saved sequence can be
+/// passed to a looper to resume a previous session.
+pub struct LooperSequence<T>(Rc<RefCell<Vec<T>>>);
+
+impl<T> Clone for LooperSequence<T> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+impl<T: Clone + Default> LooperSequence<T> {
+    pub fn new(entries: Vec<T>) -> Self {
+        assert!(!entries.is_empty(), "looper sequence may not be empty");
+        Self(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn new_empty(length: usize) -> Self {
+        Self::new((0..length).map(|_| T::default()).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Returns a copy of the current contents of the sequence
+    pub fn entries(&self) -> Vec<T> {
+        self.0.borrow().clone()
+    }
+
+    /// Replace the contents of the sequence. The length of the sequence may not change while it
+    /// is being played by a looper.
+    pub fn set_entries(&self, entries: Vec<T>) {
+        let mut current = self.0.borrow_mut();
+        assert_eq!(
+            current.len(),
+            entries.len(),
+            "looper sequence length may not change"
+        );
+        *current = entries;
+    }
+
+    /// Loopers only borrow the sequence for the duration of a single read or write so that the
+    /// caller is free to access the sequence from other signals
+    fn get(&self, index: usize) -> T {
+        self.0.borrow()[index].clone()
+    }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut T)) {
+        f(&mut self.0.borrow_mut()[index]);
+    }
+}
 
 pub struct ClockedTriggerLooper {
     pub clock: Trigger,
     pub add: Gate,
     pub remove: Gate,
-    pub length: usize,
+    pub sequence: LooperSequence<bool>,
 }
 
 impl ClockedTriggerLooper {
@@ -14,16 +72,15 @@ impl ClockedTriggerLooper {
             clock,
             add,
             remove,
-            length,
+            sequence,
         } = self;
+        let length = sequence.len();
         struct State {
-            sequence: Vec<bool>,
             samples_since_last_clock_pulse: usize,
             samples_since_last_add: usize,
             next_index: usize,
         }
         let state = RefCell::new(State {
-            sequence: (0..length).map(|_| false).collect::<Vec<_>>(),
             samples_since_last_clock_pulse: 0,
             samples_since_last_add: 0,
             next_index: 0,
@@ -41,20 +98,20 @@ impl ClockedTriggerLooper {
             if clock.sample(ctx) {
                 let next_index = state.next_index;
                 if remove.sample(ctx) {
-                    state.sequence[next_index] = false;
+                    sequence.update(next_index, |entry| *entry = false);
                 }
                 // Set the output before updating the sequence. The sound plays when a key is
                 // pressed, and on the clock pulse imediately after we don't want to play the sound
                 // a second time. Setting the output here prevents this.
-                output = state.sequence[next_index];
+                output = sequence.get(next_index);
                 if state.samples_since_last_add < state.samples_since_last_clock_pulse / 2 {
-                    state.sequence[next_index] = true;
+                    sequence.update(next_index, |entry| *entry = true);
                 } else {
                     if state.samples_since_last_add < state.samples_since_last_clock_pulse {
-                        state.sequence[(next_index + length - 1) % length] = true;
+                        sequence.update((next_index + length - 1) % length, |entry| *entry = true);
                     }
                     if add.sample(ctx) {
-                        state.sequence[next_index] = true;
+                        sequence.update(next_index, |entry| *entry = true);
                         // Explicitly set the output here. When holding a button we fill the
                         // sequence on each clock tick and also play the sound.
                         output = true;
@@ -74,12 +131,23 @@ impl ClockedTriggerLooper {
     }
 }
 
+/// A single step of the sequence recorded by `ClockedMidiNoteMonophonicLooper`. If `key_down` is
+/// set then the note is played on this step. It continues to play through subsequent steps with
+/// `key_down` set unless `key_up` is also set, in which case it's released shortly after being
+/// played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MidiNoteLooperEntry {
+    pub midi_index: u8,
+    pub key_down: bool,
+    pub key_up: bool,
+}
+
 pub struct ClockedMidiNoteMonophonicLooper {
     pub clock: Trigger,
     pub input_gate: Gate,
     pub input_midi_index: Su8,
     pub clear: Gate,
-    pub length: usize,
+    pub sequence: LooperSequence<MidiNoteLooperEntry>,
 }
 
 impl ClockedMidiNoteMonophonicLooper {
@@ -89,16 +157,10 @@ impl ClockedMidiNoteMonophonicLooper {
             input_gate,
             input_midi_index,
             clear,
-            length,
+            sequence,
         } = self;
-        #[derive(Clone, Copy)]
-        struct Entry {
-            midi_index: u8,
-            key_down: bool,
-            key_up: bool,
-        }
+        let length = sequence.len();
         struct State {
-            sequence: Vec<Entry>,
             samples_since_last_clock_pulse: usize,
             next_index: usize,
             gate_state: bool,
@@ -107,13 +169,6 @@ impl ClockedMidiNoteMonophonicLooper {
             last_period_samples: usize,
         }
         let state = RefCell::new(State {
-            sequence: (0..length)
-                .map(|_| Entry {
-                    midi_index: 0,
-                    key_down: false,
-                    key_up: false,
-                })
-                .collect::<Vec<_>>(),
             samples_since_last_clock_pulse: 0,
             next_index: 0,
             gate_state: false,
@@ -131,17 +186,20 @@ impl ClockedMidiNoteMonophonicLooper {
             if clock.sample(ctx) {
                 let next_index = state.next_index;
                 if clear.sample(ctx) {
-                    let entry = &mut state.sequence[next_index];
-                    entry.key_down = false;
-                    entry.key_up = true;
+                    sequence.update(next_index, |entry| {
+                        entry.key_down = false;
+                        entry.key_up = true;
+                    });
                 }
                 if input_gate.sample(ctx) {
-                    let entry = &mut state.sequence[next_index];
-                    entry.midi_index = input_midi_index.sample(ctx);
-                    entry.key_down = true;
-                    entry.key_up = false;
+                    let midi_index = input_midi_index.sample(ctx);
+                    sequence.update(next_index, |entry| {
+                        entry.midi_index = midi_index;
+                        entry.key_down = true;
+                        entry.key_up = false;
+                    });
                 }
-                let entry = state.sequence[next_index];
+                let entry = sequence.get(next_index);
                 if entry.key_down {
                     state.output = (true, entry.midi_index);
                     if entry.key_up {
@@ -164,10 +222,12 @@ impl ClockedMidiNoteMonophonicLooper {
                         } else {
                             state.next_index
                         };
-                    let entry = &mut state.sequence[index_to_update];
-                    entry.midi_index = input_midi_index.sample(ctx);
-                    entry.key_down = true;
-                    entry.key_up = false;
+                    let midi_index = input_midi_index.sample(ctx);
+                    sequence.update(index_to_update, |entry| {
+                        entry.midi_index = midi_index;
+                        entry.key_down = true;
+                        entry.key_up = false;
+                    });
                 }
                 state.output = (true, input_midi_index.sample(ctx));
             } else {
@@ -180,8 +240,7 @@ impl ClockedMidiNoteMonophonicLooper {
                         } else {
                             state.next_index
                         };
-                    let entry = &mut state.sequence[index_to_update];
-                    entry.key_up = true;
+                    sequence.update(index_to_update, |entry| entry.key_up = true);
                 }
             }
             state.gate_state = next_gate_state;
@@ -192,3 +251,476 @@ impl ClockedMidiNoteMonophonicLooper {
         (gate, midi_index)
     }
 }
+
+/// A note recorded by `ClockedKeyEventLooper`. Times are measured in clock steps from the start
+/// of the loop. Notes are stored as press/release pairs so that erasing or undoing never leaves a
+/// note hanging.
+#[derive(Clone, Copy, Debug)]
+struct LoopedNote {
+    note: Note,
+    velocity_01: f64,
+    release_velocity_01: f64,
+    start: f64,
+    duration: f64,
+    /// Set when quantization moves a note later than the point it was played live, so that
+    /// playback doesn't sound it a second time on the pass during which it was recorded
+    suppress_next_press: bool,
+}
+
+/// A note whose key is still held down while recording
+struct PendingNote {
+    layer_index: usize,
+    note: Note,
+    velocity_01: f64,
+    start: f64,
+    raw_start: f64,
+    suppress_next_press: bool,
+}
+
+/// Records overlapping notes from a stream of key events into a loop of `length` clock steps.
+/// Each time `record` is pressed a new layer is started which is overdubbed over the existing
+/// layers. The most recent layer can be removed with `undo`, and notes are removed from all
+/// layers as the playhead passes them while `erase` is held. Note times are recorded with
+/// sub-step precision based on the measured clock period, and moved towards the nearest step by
+/// `quantize_01` (0 leaves timing as played, 1 snaps to steps). The output contains the live
+/// input followed by the looped notes.
+pub struct ClockedKeyEventLooper {
+    pub clock: Trigger,
+    pub input: Signal<Vec<KeyEvent>>,
+    pub record: Gate,
+    pub erase: Gate,
+    pub undo: Trigger,
+    pub length: usize,
+    pub quantize_01: Sf64,
+}
+
+/// Returns true if `t` lies in the half-open interval `(from, to]` of a loop, taking wrapping into
+/// account
+fn in_loop_window(t: f64, from: f64, to: f64) -> bool {
+    if from <= to {
+        t > from && t <= to
+    } else {
+        t > from || t <= to
+    }
+}
+
+impl ClockedKeyEventLooper {
+    pub fn signal(self) -> Signal<Vec<KeyEvent>> {
+        let Self {
+            clock,
+            input,
+            record,
+            erase,
+            undo,
+            length,
+            quantize_01,
+        } = self;
+        assert!(length > 0, "length must be positive");
+        let length_f = length as f64;
+        struct State {
+            layers: Vec<Vec<LoopedNote>>,
+            pending: Vec<PendingNote>,
+            step_index: usize,
+            clock_period_tracker: ClockPeriodTracker,
+            prev_position: f64,
+        }
+        impl State {
+            fn position(&self, length_f: f64) -> f64 {
+                let fraction = match self.clock_period_tracker.period_samples() {
+                    Some(period) if period > 0 => {
+                        (self.clock_period_tracker.samples_since_last_pulse() as f64
+                            / period as f64)
+                            .min(0.999)
+                    }
+                    _ => 0.0,
+                };
+                (self.step_index as f64 + fraction) % length_f
+            }
+        }
+        fn is_sounding(note: &LoopedNote, position: f64, length_f: f64) -> bool {
+            let offset = (position - note.start).rem_euclid(length_f);
+            offset < note.duration
+        }
+        let state = RefCell::new(State {
+            layers: Vec::new(),
+            pending: Vec::new(),
+            step_index: 0,
+            clock_period_tracker: ClockPeriodTracker::default(),
+            prev_position: 0.0,
+        });
+        let record_press = record.to_trigger_rising_edge();
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let mut output = input.sample(ctx);
+            let mut releases = Vec::new();
+            let mut presses = Vec::new();
+            if state.clock_period_tracker.tick(&clock, ctx) {
+                state.step_index = (state.step_index + 1) % length;
+            }
+            let position = state.position(length_f);
+            let prev_position = state.prev_position;
+            state.prev_position = position;
+            if undo.sample(ctx) {
+                if let Some(layer) = state.layers.pop() {
+                    let layer_index = state.layers.len();
+                    state
+                        .pending
+                        .retain(|pending| pending.layer_index != layer_index);
+                    for note in layer {
+                        if is_sounding(&note, prev_position, length_f) {
+                            releases.push(KeyEvent {
+                                note: note.note,
+                                pressed: false,
+                                velocity_01: note.release_velocity_01,
+                            });
+                        }
+                    }
+                }
+            }
+            if erase.sample(ctx) {
+                for layer in state.layers.iter_mut() {
+                    layer.retain(|note| {
+                        if in_loop_window(note.start, prev_position, position) {
+                            if is_sounding(note, prev_position, length_f) {
+                                releases.push(KeyEvent {
+                                    note: note.note,
+                                    pressed: false,
+                                    velocity_01: note.release_velocity_01,
+                                });
+                            }
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+            }
+            // Playback
+            for layer in state.layers.iter_mut() {
+                for note in layer.iter_mut() {
+                    let end = (note.start + note.duration) % length_f;
+                    if in_loop_window(end, prev_position, position) {
+                        releases.push(KeyEvent {
+                            note: note.note,
+                            pressed: false,
+                            velocity_01: note.release_velocity_01,
+                        });
+                    }
+                    if in_loop_window(note.start, prev_position, position) {
+                        if note.suppress_next_press {
+                            note.suppress_next_press = false;
+                        } else {
+                            presses.push(KeyEvent {
+                                note: note.note,
+                                pressed: true,
+                                velocity_01: note.velocity_01,
+                            });
+                        }
+                    }
+                }
+            }
+            // Recording
+            if record_press.sample(ctx) {
+                state.layers.push(Vec::new());
+            }
+            let recording = record.sample(ctx);
+            for key_event in &output {
+                if key_event.pressed {
+                    if !recording || state.layers.is_empty() {
+                        continue;
+                    }
+                    let quantize_01 = quantize_01.sample(ctx).clamp(0.0, 1.0);
+                    let quantized = position + ((position.round() - position) * quantize_01);
+                    let layer_index = state.layers.len() - 1;
+                    state.pending.push(PendingNote {
+                        layer_index,
+                        note: key_event.note,
+                        velocity_01: key_event.velocity_01,
+                        start: quantized.rem_euclid(length_f),
+                        raw_start: position,
+                        suppress_next_press: quantized > position,
+                    });
+                } else if let Some(i) = state
+                    .pending
+                    .iter()
+                    .position(|pending| pending.note == key_event.note)
+                {
+                    let pending = state.pending.remove(i);
+                    let duration = (position - pending.raw_start).rem_euclid(length_f);
+                    state.layers[pending.layer_index].push(LoopedNote {
+                        note: pending.note,
+                        velocity_01: pending.velocity_01,
+                        release_velocity_01: key_event.velocity_01,
+                        start: pending.start,
+                        // Avoid zero-length notes so that a press is never emitted after its
+                        // release within the same frame
+                        duration: duration.max(1.0 / 64.0),
+                        suppress_next_press: pending.suppress_next_press,
+                    });
+                }
+            }
+            output.extend(releases);
+            output.extend(presses);
+            output
+        })
+    }
+}
+
+/// Records audio into a loop whose length is a whole number of clock pulses. Recording starts on
+/// the first clock pulse while `record` is held and stops on the first clock pulse after it is
+/// released. After that the loop plays back while `play` is held. While `overdub` is held the
+/// input is mixed into the loop, with existing material scaled by `feedback_01` each pass.
+/// `multiply` doubles the length of the loop by repeating its contents and `clear` empties it.
+/// To avoid clicks, a short amount of audio is recorded past the end of the loop and crossfaded
+/// with its start, and overdubbing fades in and out over the same duration.
+pub struct ClockedAudioLooper {
+    pub clock: Trigger,
+    pub input: Sf64,
+    pub record: Gate,
+    pub play: Gate,
+    pub overdub: Gate,
+    pub clear: Gate,
+    pub multiply: Trigger,
+    pub feedback_01: Sf64,
+    pub half_speed: Gate,
+    pub reverse: Gate,
+    pub crossfade_s: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AudioLooperMode {
+    Empty,
+    Recording,
+    Looping,
+}
+
+impl ClockedAudioLooper {
+    pub fn signal(self) -> Sf64 {
+        let Self {
+            clock,
+            input,
+            record,
+            play,
+            overdub,
+            clear,
+            multiply,
+            feedback_01,
+            half_speed,
+            reverse,
+            crossfade_s,
+        } = self;
+        struct State {
+            mode: AudioLooperMode,
+            buffer: Vec<f64>,
+            /// Audio recorded after the end of the loop is crossfaded into its start as the start
+            /// is played for the first time, so that the first pass continues seamlessly from the
+            /// recording. This is the index of the next sample of the start to crossfade.
+            crossfade_index: usize,
+            crossfade_length: usize,
+            playhead: f64,
+            overdub_level_01: f64,
+            /// The index most recently overdubbed. At half speed the playhead stays on each
+            /// index for two samples, and each index must only be written once per pass.
+            last_overdub_index: Option<usize>,
+        }
+        impl State {
+            fn read(&self, position: f64) -> f64 {
+                let len = self.buffer.len();
+                let index = position.floor() as usize % len;
+                let next_index = (index + 1) % len;
+                let fraction = position - position.floor();
+                (self.buffer[index] * (1.0 - fraction)) + (self.buffer[next_index] * fraction)
+            }
+
+            fn crossfade_tail_sample(&mut self, tail_sample: f64) {
+                let i = self.crossfade_index;
+                let fade_in_01 = i as f64 / self.crossfade_length as f64;
+                self.buffer[i] = (self.buffer[i] * fade_in_01) + (tail_sample * (1.0 - fade_in_01));
+                self.crossfade_index += 1;
+            }
+        }
+        let state = RefCell::new(State {
+            mode: AudioLooperMode::Empty,
+            buffer: Vec::new(),
+            crossfade_index: 0,
+            crossfade_length: 0,
+            playhead: 0.0,
+            overdub_level_01: 0.0,
+            last_overdub_index: None,
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let input_sample = input.sample(ctx);
+            let clock_pulse = clock.sample(ctx);
+            let crossfade_samples = ((crossfade_s * ctx.sample_rate_hz) as usize).max(1);
+            if clear.sample(ctx) {
+                state.mode = AudioLooperMode::Empty;
+                state.buffer.clear();
+                state.last_overdub_index = None;
+                state.crossfade_index = 0;
+                state.crossfade_length = 0;
+            }
+            match state.mode {
+                AudioLooperMode::Empty => {
+                    if clock_pulse && record.sample(ctx) {
+                        state.mode = AudioLooperMode::Recording;
+                        state.buffer.push(input_sample);
+                    }
+                    return 0.0;
+                }
+                AudioLooperMode::Recording => {
+                    if clock_pulse && !record.sample(ctx) {
+                        state.mode = AudioLooperMode::Looping;
+                        state.playhead = 0.0;
+                        state.crossfade_index = 0;
+                        state.crossfade_length = crossfade_samples.min(state.buffer.len());
+                    } else {
+                        state.buffer.push(input_sample);
+                        return 0.0;
+                    }
+                }
+                AudioLooperMode::Looping => (),
+            }
+            // This happens before reading so the playhead, which starts at the beginning of the
+            // loop, never reaches a sample of the start before it has been crossfaded
+            if state.crossfade_index < state.crossfade_length {
+                state.crossfade_tail_sample(input_sample);
+            }
+            if multiply.sample(ctx) {
+                state.buffer.extend_from_within(..);
+            }
+            let len = state.buffer.len() as f64;
+            let playhead = state.playhead;
+            let output = state.read(playhead);
+            // Ramp the overdub level to avoid clicks when starting and stopping overdubbing
+            let overdub_target_01 = if overdub.sample(ctx) { 1.0 } else { 0.0 };
+            let overdub_step = 1.0 / crossfade_samples as f64;
+            state.overdub_level_01 = if state.overdub_level_01 < overdub_target_01 {
+                (state.overdub_level_01 + overdub_step).min(overdub_target_01)
+            } else {
+                (state.overdub_level_01 - overdub_step).max(overdub_target_01)
+            };
+            if state.overdub_level_01 > 0.0 {
+                let level_01 = state.overdub_level_01;
+                let feedback_01 = feedback_01.sample(ctx);
+                let index = playhead.round() as usize % state.buffer.len();
+                if state.last_overdub_index != Some(index) {
+                    let existing = state.buffer[index];
+                    state.buffer[index] = (existing * (1.0 + ((feedback_01 - 1.0) * level_01)))
+                        + (input_sample * level_01);
+                    state.last_overdub_index = Some(index);
+                }
+            } else {
+                state.last_overdub_index = None;
+            }
+            let speed = if half_speed.sample(ctx) { 0.5 } else { 1.0 };
+            let direction = if reverse.sample(ctx) { -1.0 } else { 1.0 };
+            state.playhead = (playhead + (speed * direction)).rem_euclid(len);
+            if play.sample(ctx) {
+                output
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+#[test]
+fn test_clocked_key_event_looper_playback_timing() {
+    let note = Note::from_midi_index(60);
+    let input = Signal::from_fn(move |ctx| match ctx.sample_index {
+        250 => vec![KeyEvent {
+            note,
+            pressed: true,
+            velocity_01: 1.0,
+        }],
+        300 => vec![KeyEvent {
+            note,
+            pressed: false,
+            velocity_01: 0.0,
+        }],
+        _ => Vec::new(),
+    });
+    let output = ClockedKeyEventLooper {
+        clock: Signal::from_fn(|ctx| {
+            let sample_in_step = ctx.sample_index % 100;
+            sample_in_step == 0
+        })
+        .to_trigger_raw(),
+        input,
+        record: Signal::from_fn(|ctx| ctx.sample_index < 400).to_gate(),
+        erase: Gate::never(),
+        undo: Trigger::never(),
+        length: 4,
+        quantize_01: Signal::from(0.0),
+    }
+    .signal();
+    let mut events = Vec::new();
+    for sample_index in 0..1200 {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        for key_event in output.sample(&ctx) {
+            events.push((sample_index, key_event.pressed));
+        }
+    }
+    // The loop is 400 samples long so the recorded note is played back at the same offset into
+    // each subsequent pass
+    assert_eq!(
+        events,
+        vec![
+            (250, true),
+            (300, false),
+            (650, true),
+            (700, false),
+            (1050, true),
+            (1100, false),
+        ]
+    );
+}
+
+#[test]
+fn test_clocked_audio_looper_first_pass_seam() {
+    use std::f64::consts::PI;
+    let input = Signal::from_fn(|ctx| (ctx.sample_index as f64 * 2.0 * PI / 37.0).sin());
+    let output = ClockedAudioLooper {
+        clock: Signal::from_fn(|ctx| {
+            let sample_in_step = ctx.sample_index % 100;
+            sample_in_step == 0
+        })
+        .to_trigger_raw(),
+        input: input.clone(),
+        record: Signal::from_fn(|ctx| ctx.sample_index < 250).to_gate(),
+        play: const_(true).to_gate(),
+        overdub: Gate::never(),
+        clear: Gate::never(),
+        multiply: Trigger::never(),
+        feedback_01: Signal::from(1.0),
+        half_speed: Gate::never(),
+        reverse: Gate::never(),
+        crossfade_s: 0.01,
+    }
+    .signal();
+    // Recording stops at sample 300, after which the loop takes over from the input. Without a
+    // crossfade there would be a jump wherever the end of the recording meets its start.
+    let max_step = 2.0 * PI / 37.0;
+    let mut previous = input.sample(&SignalCtx {
+        sample_index: 299,
+        sample_rate_hz: 1000.0,
+    });
+    for sample_index in 0..1000 {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        let sample = output.sample(&ctx);
+        if sample_index >= 300 {
+            assert!(
+                (sample - previous).abs() <= max_step,
+                "jump at sample {sample_index}: {previous} to {sample}"
+            );
+            previous = sample;
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use crate::{
-    keyboard::KeyEvent,
+    keyboard::{KeyEvent, NotePressure},
     music::{self, Note},
-    signal::{Sf64, Signal, SignalCtx},
+    signal::{Gate, Sf64, Signal, SignalCtx, Trigger},
+};
+use midly::{
+    live::{LiveEvent, SystemCommon, SystemRealtime},
+    MetaMessage, PitchBend, Timing, TrackEvent, TrackEventKind,
 };
 pub use midly::{
     num::{u14, u4, u7},
     MidiMessage,
 };
-use midly::{MetaMessage, PitchBend, Timing, TrackEvent, TrackEventKind};
 use std::{
     cell::{Cell, RefCell},
     rc::Rc,
@@ -19,9 +22,38 @@ pub struct MidiEvent {
     pub message: MidiMessage,
 }
 
+/// Messages used to synchronize playback with an external device. These aren't associated with
+/// any channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiRealtimeMessage {
+    /// Sent 24 times per quarter note
+    TimingClock,
+    Start,
+    Continue,
+    Stop,
+    /// The number of 16th notes (6 timing clocks) since the start of the song
+    SongPosition(u14),
+}
+
+impl MidiRealtimeMessage {
+    pub fn from_live_event(live_event: &LiveEvent) -> Option<Self> {
+        match live_event {
+            LiveEvent::Realtime(SystemRealtime::TimingClock) => Some(Self::TimingClock),
+            LiveEvent::Realtime(SystemRealtime::Start) => Some(Self::Start),
+            LiveEvent::Realtime(SystemRealtime::Continue) => Some(Self::Continue),
+            LiveEvent::Realtime(SystemRealtime::Stop) => Some(Self::Stop),
+            LiveEvent::Common(SystemCommon::SongPosition(position)) => {
+                Some(Self::SongPosition(*position))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct MidiEvents {
     events: Vec<MidiEvent>,
+    realtime_messages: Vec<MidiRealtimeMessage>,
 }
 
 impl MidiEvents {
@@ -29,6 +61,14 @@ impl MidiEvents {
         self.events.push(midi_event);
     }
 
+    fn add_realtime(&mut self, realtime_message: MidiRealtimeMessage) {
+        self.realtime_messages.push(realtime_message);
+    }
+
+    pub fn for_each_realtime_message<F: FnMut(MidiRealtimeMessage)>(&self, f: F) {
+        self.realtime_messages.iter().cloned().for_each(f)
+    }
+
     pub fn for_each_event<F: FnMut(MidiEvent)>(&self, f: F) {
         self.events.iter().cloned().for_each(f)
     }
@@ -44,6 +84,8 @@ impl MidiEvents {
     pub fn filter_channel(&self, channel: u8) -> MidiMessages {
         let mut midi_messages = MidiMessages::default();
         self.for_each_message_on_channel(channel, |message| midi_messages.add(message));
+        // Realtime messages apply to all channels
+        midi_messages.realtime_messages = self.realtime_messages.clone();
         midi_messages
     }
 }
@@ -51,6 +93,7 @@ impl MidiEvents {
 #[derive(Debug, Clone, Default)]
 pub struct MidiMessages {
     messages: Vec<MidiMessage>,
+    realtime_messages: Vec<MidiRealtimeMessage>,
 }
 
 impl MidiMessages {
@@ -58,12 +101,20 @@ impl MidiMessages {
         self.messages.push(midi_message);
     }
 
+    fn add_realtime(&mut self, realtime_message: MidiRealtimeMessage) {
+        self.realtime_messages.push(realtime_message);
+    }
+
+    pub fn for_each_realtime_message<F: FnMut(MidiRealtimeMessage)>(&self, f: F) {
+        self.realtime_messages.iter().cloned().for_each(f)
+    }
+
     pub fn for_each<F: FnMut(MidiMessage)>(&self, f: F) {
         self.messages.iter().cloned().for_each(f)
     }
 }
 
-fn u7_to_01(u7: u7) -> f64 {
+pub(crate) fn u7_to_01(u7: u7) -> f64 {
     u7.as_int() as f64 / 127.0
 }
 
@@ -111,6 +162,14 @@ pub trait MidiEventSource {
     fn for_each_new_event<F>(&mut self, ctx: &SignalCtx, f: F)
     where
         F: FnMut(MidiEvent);
+
+    /// Called after `for_each_new_event` each frame. Sources which don't receive clock or
+    /// transport messages needn't implement this.
+    fn for_each_new_realtime_message<F>(&mut self, _ctx: &SignalCtx, _f: F)
+    where
+        F: FnMut(MidiRealtimeMessage),
+    {
+    }
 }
 
 pub struct TrackEventSource {
@@ -188,9 +247,11 @@ pub fn event_source_to_signal(event_source: impl MidiEventSource + 'static) -> S
     let event_source = RefCell::new(event_source);
     Signal::from_fn(move |ctx| {
         let mut midi_events = MidiEvents::default();
-        event_source
-            .borrow_mut()
-            .for_each_new_event(ctx, |midi_event| midi_events.add(midi_event));
+        let mut event_source = event_source.borrow_mut();
+        event_source.for_each_new_event(ctx, |midi_event| midi_events.add(midi_event));
+        event_source.for_each_new_realtime_message(ctx, |realtime_message| {
+            midi_events.add_realtime(realtime_message)
+        });
         midi_events
     })
 }
@@ -208,21 +269,62 @@ pub fn event_source_to_signal_single_channel(
     let event_source = RefCell::new(event_source);
     Signal::from_fn(move |ctx| {
         let mut midi_messages = MidiMessages::default();
-        event_source
-            .borrow_mut()
-            .for_each_new_event(ctx, |midi_event| {
-                if midi_event.channel == channel {
-                    midi_messages.add(midi_event.message);
-                }
-            });
+        let mut event_source = event_source.borrow_mut();
+        event_source.for_each_new_event(ctx, |midi_event| {
+            if midi_event.channel == channel {
+                midi_messages.add(midi_event.message);
+            }
+        });
+        event_source.for_each_new_realtime_message(ctx, |realtime_message| {
+            midi_messages.add_realtime(realtime_message)
+        });
         midi_messages
     })
 }
 
-fn midi_note_message_to_key_event(key: u7, vel: u7, pressed: bool) -> KeyEvent {
+/// Maps midi note velocities to the velocities of key events
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum VelocityCurve {
+    #[default]
+    Linear,
+    /// The linear velocity raised to the given power. Powers above 1 make soft notes softer
+    /// and powers below 1 make soft notes louder.
+    Exponential(f64),
+    /// Ignore the velocity of notes and always use the given value
+    Fixed(f64),
+    /// Interpolate linearly between values evenly spaced across the range of midi velocities.
+    /// The first value is used for velocity 0 and the last for velocity 127.
+    Table(Vec<f64>),
+}
+
+impl VelocityCurve {
+    pub fn apply(&self, velocity: u7) -> f64 {
+        let linear_01 = u7_to_01(velocity);
+        match self {
+            Self::Linear => linear_01,
+            Self::Exponential(power) => linear_01.powf(*power),
+            Self::Fixed(velocity_01) => *velocity_01,
+            Self::Table(table) => match table.len() {
+                0 => linear_01,
+                1 => table[0],
+                len => {
+                    let position = linear_01 * (len - 1) as f64;
+                    let index = (position.floor() as usize).min(len - 2);
+                    let fraction = position - index as f64;
+                    table[index] + ((table[index + 1] - table[index]) * fraction)
+                }
+            },
+        }
+    }
+}
+
+pub const MIDI_CONTROLLER_SUSTAIN_PEDAL: u8 = 64;
+pub const MIDI_CONTROLLER_SOSTENUTO_PEDAL: u8 = 66;
+
+fn midi_note_message_to_key_event(velocity_01: f64, key: u7, pressed: bool) -> KeyEvent {
     KeyEvent {
         note: Note::from_midi_index(key),
-        velocity_01: u7_to_01(vel),
+        velocity_01,
         pressed,
     }
 }
@@ -233,15 +335,39 @@ fn midi_pitch_bend_to_pitch_bend_multiplier_hz(midi_pitch_bend: u14) -> f64 {
 }
 
 impl Signal<MidiMessages> {
+    /// Every note on message is treated as a key press, even with a velocity of 0. Use
+    /// `key_events_with_velocity_curve` to treat those as key releases instead.
     pub fn key_events(&self) -> Signal<Vec<KeyEvent>> {
         self.map(|messages| {
             let mut ret = Vec::new();
             messages.for_each(|message| match message {
                 MidiMessage::NoteOn { key, vel } => {
-                    ret.push(midi_note_message_to_key_event(key, vel, true))
+                    ret.push(midi_note_message_to_key_event(u7_to_01(vel), key, true))
                 }
                 MidiMessage::NoteOff { key, vel } => {
-                    ret.push(midi_note_message_to_key_event(key, vel, false))
+                    ret.push(midi_note_message_to_key_event(u7_to_01(vel), key, false))
+                }
+                _ => (),
+            });
+            ret
+        })
+    }
+
+    /// Key events with the velocity curve applied to the velocities of key presses. Release
+    /// velocities are always linear. Unlike `key_events`, note on messages with a velocity of 0
+    /// are treated as key releases, as many keyboards send them instead of note off messages.
+    pub fn key_events_with_velocity_curve(
+        &self,
+        velocity_curve: VelocityCurve,
+    ) -> Signal<Vec<KeyEvent>> {
+        self.map(move |messages| {
+            let mut ret = Vec::new();
+            messages.for_each(|message| match message {
+                MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => ret.push(
+                    midi_note_message_to_key_event(velocity_curve.apply(vel), key, true),
+                ),
+                MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel } => {
+                    ret.push(midi_note_message_to_key_event(u7_to_01(vel), key, false))
                 }
                 _ => (),
             });
@@ -249,6 +375,79 @@ impl Signal<MidiMessages> {
         })
     }
 
+    /// Polyphonic aftertouch messages
+    pub fn note_pressures(&self) -> Signal<Vec<NotePressure>> {
+        self.map(|messages| {
+            let mut ret = Vec::new();
+            messages.for_each(|message| {
+                if let MidiMessage::Aftertouch { key, vel } = message {
+                    ret.push(NotePressure {
+                        note: Note::from_midi_index(key),
+                        pressure_01: u7_to_01(vel),
+                    });
+                }
+            });
+            ret
+        })
+    }
+
+    /// The most recent channel aftertouch value
+    pub fn channel_pressure_01(&self) -> Sf64 {
+        let state = Rc::new(Cell::new(0.0));
+        self.map({
+            let state = Rc::clone(&state);
+            move |messages| {
+                messages.for_each(|message| {
+                    if let MidiMessage::ChannelAftertouch { vel } = message {
+                        state.set(u7_to_01(vel));
+                    }
+                });
+            }
+        })
+        .then({
+            let state = Rc::clone(&state);
+            move || state.get()
+        })
+    }
+
+    /// Key events with releases deferred according to the sustain (CC 64) and sostenuto
+    /// (CC 66) pedals. Note on messages with a velocity of 0 are treated as key releases.
+    pub fn key_events_with_pedals(&self) -> Signal<Vec<KeyEvent>> {
+        self.key_events_with_velocity_curve(VelocityCurve::Linear)
+            .with_pedals(self.sustain_pedal(), self.sostenuto_pedal())
+    }
+
+    /// Follows the state of a pedal controller, which is considered down for values of 64 and
+    /// above
+    pub fn controller_pedal(&self, controller_index: u8) -> Gate {
+        let state = Rc::new(Cell::new(false));
+        self.map({
+            let state = Rc::clone(&state);
+            move |messages| {
+                messages.for_each(|message| {
+                    if let MidiMessage::Controller { controller, value } = message {
+                        if controller.as_int() == controller_index {
+                            state.set(value.as_int() >= 64);
+                        }
+                    }
+                });
+            }
+        })
+        .then({
+            let state = Rc::clone(&state);
+            move || state.get()
+        })
+        .to_gate()
+    }
+
+    pub fn sustain_pedal(&self) -> Gate {
+        self.controller_pedal(MIDI_CONTROLLER_SUSTAIN_PEDAL)
+    }
+
+    pub fn sostenuto_pedal(&self) -> Gate {
+        self.controller_pedal(MIDI_CONTROLLER_SOSTENUTO_PEDAL)
+    }
+
     pub fn pitch_bend_multiplier_hz(&self) -> Sf64 {
         let state = Rc::new(Cell::new(1.0));
         self.map({
@@ -295,3 +494,205 @@ impl Signal<MidiMessages> {
         }
     }
 }
+
+/// Number of timing clock messages sent per quarter note
+pub const MIDI_CLOCK_PULSES_PER_QUARTER_NOTE: u64 = 24;
+
+/// Number of timing clock messages per unit of the song position pointer (a 16th note)
+const MIDI_CLOCK_PULSES_PER_SONG_POSITION: u64 = 6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MidiTransport {
+    #[default]
+    Stopped,
+    Playing,
+}
+
+/// Signals derived from the clock and transport messages sent by an external device such as a
+/// drum machine or DAW.
+#[derive(Clone)]
+pub struct MidiClockSync {
+    /// Pulses on every timing clock message (24 per quarter note) regardless of transport state
+    pub clock: Trigger,
+    /// Pulses on the first clock of each quarter note while the transport is playing
+    pub beat: Trigger,
+    pub start: Trigger,
+    pub stop: Trigger,
+    pub continue_: Trigger,
+    pub transport: Signal<MidiTransport>,
+    pub running: Gate,
+    /// Number of clock pulses since the start of the song, as set by song position pointer
+    /// messages and advanced by clock messages while playing
+    pub position_pulses: Signal<u64>,
+    /// Tempo estimated from the time between clock messages, smoothed over roughly one beat
+    pub bpm: Sf64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct MidiClockSyncFrame {
+    clock: bool,
+    beat: bool,
+    start: bool,
+    stop: bool,
+    continue_: bool,
+    transport: MidiTransport,
+    position_pulses: u64,
+    bpm: f64,
+}
+
+#[derive(Default)]
+struct MidiClockSyncState {
+    transport: MidiTransport,
+    position_pulses: u64,
+    samples_since_last_clock: Option<u64>,
+    bpm: Option<f64>,
+}
+
+impl MidiClockSyncState {
+    fn handle_realtime_message(
+        &mut self,
+        realtime_message: MidiRealtimeMessage,
+        frame: &mut MidiClockSyncFrame,
+        ctx: &SignalCtx,
+    ) {
+        match realtime_message {
+            MidiRealtimeMessage::TimingClock => {
+                frame.clock = true;
+                if let Some(samples) = self.samples_since_last_clock {
+                    if samples > 0 {
+                        let beat_s = (samples as f64 * MIDI_CLOCK_PULSES_PER_QUARTER_NOTE as f64)
+                            / ctx.sample_rate_hz;
+                        let instant_bpm = 60.0 / beat_s;
+                        let smoothing = 1.0 / MIDI_CLOCK_PULSES_PER_QUARTER_NOTE as f64;
+                        self.bpm = Some(match self.bpm {
+                            Some(bpm) => bpm + (instant_bpm - bpm) * smoothing,
+                            None => instant_bpm,
+                        });
+                    }
+                }
+                self.samples_since_last_clock = Some(0);
+                if self.transport == MidiTransport::Playing {
+                    let pulse_in_beat = self.position_pulses % MIDI_CLOCK_PULSES_PER_QUARTER_NOTE;
+                    if pulse_in_beat == 0 {
+                        frame.beat = true;
+                    }
+                    self.position_pulses += 1;
+                }
+            }
+            MidiRealtimeMessage::Start => {
+                frame.start = true;
+                self.position_pulses = 0;
+                self.transport = MidiTransport::Playing;
+            }
+            MidiRealtimeMessage::Continue => {
+                frame.continue_ = true;
+                self.transport = MidiTransport::Playing;
+            }
+            MidiRealtimeMessage::Stop => {
+                frame.stop = true;
+                self.transport = MidiTransport::Stopped;
+            }
+            MidiRealtimeMessage::SongPosition(position) => {
+                self.position_pulses =
+                    position.as_int() as u64 * MIDI_CLOCK_PULSES_PER_SONG_POSITION;
+            }
+        }
+    }
+}
+
+fn midi_clock_sync<F: Fn(&mut dyn FnMut(MidiRealtimeMessage), &SignalCtx) + 'static>(
+    for_each_realtime_message: F,
+) -> MidiClockSync {
+    let state = RefCell::new(MidiClockSyncState::default());
+    let frame = Signal::from_fn(move |ctx| {
+        let mut state = state.borrow_mut();
+        let mut frame = MidiClockSyncFrame::default();
+        if let Some(samples) = state.samples_since_last_clock.as_mut() {
+            *samples += 1;
+        }
+        for_each_realtime_message(
+            &mut |realtime_message| {
+                state.handle_realtime_message(realtime_message, &mut frame, ctx)
+            },
+            ctx,
+        );
+        frame.transport = state.transport;
+        frame.position_pulses = state.position_pulses;
+        frame.bpm = state.bpm.unwrap_or(0.0);
+        frame
+    });
+    let transport = frame.map(|frame| frame.transport);
+    MidiClockSync {
+        clock: frame.map(|frame| frame.clock).to_trigger_raw(),
+        beat: frame.map(|frame| frame.beat).to_trigger_raw(),
+        start: frame.map(|frame| frame.start).to_trigger_raw(),
+        stop: frame.map(|frame| frame.stop).to_trigger_raw(),
+        continue_: frame.map(|frame| frame.continue_).to_trigger_raw(),
+        running: transport
+            .map(|transport| transport == MidiTransport::Playing)
+            .to_gate(),
+        transport,
+        position_pulses: frame.map(|frame| frame.position_pulses),
+        bpm: frame.map(|frame| frame.bpm),
+    }
+}
+
+impl Signal<MidiEvents> {
+    pub fn clock_sync(&self) -> MidiClockSync {
+        let signal = self.clone();
+        midi_clock_sync(move |f, ctx| signal.sample(ctx).for_each_realtime_message(f))
+    }
+}
+
+impl Signal<MidiMessages> {
+    pub fn clock_sync(&self) -> MidiClockSync {
+        let signal = self.clone();
+        midi_clock_sync(move |f, ctx| signal.sample(ctx).for_each_realtime_message(f))
+    }
+}
+
+#[test]
+fn test_key_events_note_on_velocity_0() {
+    let messages = MidiMessages {
+        messages: vec![
+            MidiMessage::NoteOn {
+                key: u7::new(60),
+                vel: u7::new(0),
+            },
+            MidiMessage::NoteOff {
+                key: u7::new(62),
+                vel: u7::new(127),
+            },
+        ],
+        realtime_messages: Vec::new(),
+    };
+    let messages = Signal::from_fn(move |_| messages.clone());
+    let ctx = SignalCtx {
+        sample_index: 0,
+        sample_rate_hz: 44100.0,
+    };
+    let summarize = |key_events: Vec<KeyEvent>| {
+        key_events
+            .into_iter()
+            .map(|key_event| {
+                (
+                    key_event.note.to_midi_index(),
+                    key_event.pressed,
+                    key_event.velocity_01,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        summarize(messages.key_events().sample(&ctx)),
+        vec![(60, true, 0.0), (62, false, 1.0)]
+    );
+    assert_eq!(
+        summarize(
+            messages
+                .key_events_with_velocity_curve(VelocityCurve::Fixed(0.5))
+                .sample(&ctx)
+        ),
+        vec![(60, false, 0.0), (62, false, 1.0)]
+    );
+}
@@ -0,0 +1,310 @@
+use crate::{
+    keyboard::{VoiceDesc, DEFAULT_TIMBRE_01},
+    midi::{u7_to_01, MidiEvents, MidiMessage},
+    music::Note,
+    signal::Signal,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const CONTROLLER_DATA_ENTRY_MSB: u8 = 6;
+const CONTROLLER_DATA_ENTRY_LSB: u8 = 38;
+const CONTROLLER_TIMBRE: u8 = 74;
+const CONTROLLER_NRPN_LSB: u8 = 98;
+const CONTROLLER_NRPN_MSB: u8 = 99;
+const CONTROLLER_RPN_LSB: u8 = 100;
+const CONTROLLER_RPN_MSB: u8 = 101;
+
+/// (MSB, LSB) of the registered parameter numbers used by MPE
+const RPN_PITCH_BEND_SENSITIVITY: (u8, u8) = (0, 0);
+const RPN_MPE_CONFIGURATION: (u8, u8) = (0, 6);
+const RPN_NULL: (u8, u8) = (127, 127);
+
+const MAX_MEMBER_CHANNELS: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeZoneKind {
+    /// Master channel is channel 1 (index 0) and member channels count up from channel 2
+    Lower,
+    /// Master channel is channel 16 (index 15) and member channels count down from channel 15
+    Upper,
+}
+
+/// An MPE zone is a master channel and a range of adjacent member channels. The controller
+/// plays each note on its own member channel so that pitch bend, pressure and timbre messages
+/// on that channel apply to that note alone. Messages on the master channel apply to the
+/// whole zone.
+#[derive(Debug, Clone, Copy)]
+pub struct MpeZone {
+    pub kind: MpeZoneKind,
+    pub num_member_channels: u8,
+    pub member_pitch_bend_range_semitones: f64,
+    pub master_pitch_bend_range_semitones: f64,
+}
+
+impl MpeZone {
+    pub const DEFAULT_MEMBER_PITCH_BEND_RANGE_SEMITONES: f64 = 48.0;
+    pub const DEFAULT_MASTER_PITCH_BEND_RANGE_SEMITONES: f64 = 2.0;
+
+    fn new(kind: MpeZoneKind, num_member_channels: u8) -> Self {
+        Self {
+            kind,
+            num_member_channels: num_member_channels.min(MAX_MEMBER_CHANNELS),
+            member_pitch_bend_range_semitones: Self::DEFAULT_MEMBER_PITCH_BEND_RANGE_SEMITONES,
+            master_pitch_bend_range_semitones: Self::DEFAULT_MASTER_PITCH_BEND_RANGE_SEMITONES,
+        }
+    }
+
+    pub fn lower(num_member_channels: u8) -> Self {
+        Self::new(MpeZoneKind::Lower, num_member_channels)
+    }
+
+    pub fn upper(num_member_channels: u8) -> Self {
+        Self::new(MpeZoneKind::Upper, num_member_channels)
+    }
+
+    pub fn member_pitch_bend_range_semitones(mut self, semitones: f64) -> Self {
+        self.member_pitch_bend_range_semitones = semitones;
+        self
+    }
+
+    pub fn master_pitch_bend_range_semitones(mut self, semitones: f64) -> Self {
+        self.master_pitch_bend_range_semitones = semitones;
+        self
+    }
+
+    /// The 0-based index of the zone's master channel
+    pub fn master_channel(&self) -> u8 {
+        match self.kind {
+            MpeZoneKind::Lower => 0,
+            MpeZoneKind::Upper => 15,
+        }
+    }
+
+    /// If the 0-based channel index is one of the zone's member channels, returns the position
+    /// of the channel among the member channels, counting outwards from the master channel
+    pub fn member_index(&self, channel: u8) -> Option<usize> {
+        let member_index = match self.kind {
+            MpeZoneKind::Lower => channel.checked_sub(1)?,
+            MpeZoneKind::Upper => 14u8.checked_sub(channel)?,
+        };
+        if member_index < self.num_member_channels {
+            Some(member_index as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct MpeVoice {
+    note: Note,
+    key_down: bool,
+    key_press: bool,
+    velocity_01: f64,
+    release_velocity_01: f64,
+    /// Between -1 and 1
+    pitch_bend: f64,
+    pressure_01: f64,
+    timbre_01: f64,
+}
+
+#[derive(Clone, Copy)]
+struct ChannelRpn {
+    selected: (u8, u8),
+    /// Parameter numbers are selected with two messages which may arrive in either order
+    pending_msb: u8,
+    pending_lsb: u8,
+}
+
+impl Default for ChannelRpn {
+    fn default() -> Self {
+        Self {
+            selected: RPN_NULL,
+            pending_msb: RPN_NULL.0,
+            pending_lsb: RPN_NULL.1,
+        }
+    }
+}
+
+struct MpeState {
+    zone: MpeZone,
+    voices: Vec<MpeVoice>,
+    /// Between -1 and 1
+    master_pitch_bend: f64,
+    master_pressure_01: f64,
+    rpn: [ChannelRpn; 16],
+}
+
+impl MpeState {
+    fn new(zone: MpeZone) -> Self {
+        Self {
+            zone,
+            voices: vec![
+                MpeVoice {
+                    timbre_01: DEFAULT_TIMBRE_01,
+                    ..Default::default()
+                };
+                zone.num_member_channels as usize
+            ],
+            master_pitch_bend: 0.0,
+            master_pressure_01: 0.0,
+            rpn: [ChannelRpn::default(); 16],
+        }
+    }
+
+    fn handle_data_entry(&mut self, channel: u8, msb: Option<u8>, lsb: Option<u8>) {
+        let is_master = channel == self.zone.master_channel();
+        match self.rpn[channel as usize].selected {
+            RPN_MPE_CONFIGURATION if is_master => {
+                if let Some(msb) = msb {
+                    // The number of voices is fixed when the signals are created so member
+                    // channels beyond that number are ignored.
+                    self.zone.num_member_channels = msb.min(MAX_MEMBER_CHANNELS);
+                    self.zone.member_pitch_bend_range_semitones =
+                        MpeZone::DEFAULT_MEMBER_PITCH_BEND_RANGE_SEMITONES;
+                }
+            }
+            RPN_PITCH_BEND_SENSITIVITY => {
+                let range = if is_master {
+                    &mut self.zone.master_pitch_bend_range_semitones
+                } else if self.zone.member_index(channel).is_some() {
+                    &mut self.zone.member_pitch_bend_range_semitones
+                } else {
+                    return;
+                };
+                // The MSB is in semitones and the LSB is in cents
+                if let Some(msb) = msb {
+                    *range = msb as f64;
+                }
+                if let Some(lsb) = lsb {
+                    *range = range.trunc() + (lsb as f64 / 100.0);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_controller(&mut self, channel: u8, controller: u8, value: u8) {
+        let rpn = &mut self.rpn[channel as usize];
+        match controller {
+            CONTROLLER_RPN_MSB => {
+                rpn.pending_msb = value;
+                rpn.selected = (rpn.pending_msb, rpn.pending_lsb);
+            }
+            CONTROLLER_RPN_LSB => {
+                rpn.pending_lsb = value;
+                rpn.selected = (rpn.pending_msb, rpn.pending_lsb);
+            }
+            CONTROLLER_NRPN_MSB | CONTROLLER_NRPN_LSB => *rpn = ChannelRpn::default(),
+            CONTROLLER_DATA_ENTRY_MSB => self.handle_data_entry(channel, Some(value), None),
+            CONTROLLER_DATA_ENTRY_LSB => self.handle_data_entry(channel, None, Some(value)),
+            _ => (),
+        }
+    }
+
+    fn handle_event(&mut self, channel: u8, message: MidiMessage) {
+        if let MidiMessage::Controller { controller, value } = message {
+            self.handle_controller(channel, controller.as_int(), value.as_int());
+        }
+        if channel == self.zone.master_channel() {
+            match message {
+                MidiMessage::PitchBend { bend } => self.master_pitch_bend = bend.as_f64(),
+                MidiMessage::ChannelAftertouch { vel } => self.master_pressure_01 = u7_to_01(vel),
+                _ => (),
+            }
+            return;
+        }
+        let Some(voice) = self
+            .zone
+            .member_index(channel)
+            .and_then(|i| self.voices.get_mut(i))
+        else {
+            return;
+        };
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                voice.note = Note::from_midi_index(key);
+                voice.key_down = true;
+                voice.key_press = true;
+                voice.velocity_01 = u7_to_01(vel);
+                // Pitch bend and timbre are usually sent just before the note so they are
+                // kept, but pressure starts from zero for each note.
+                voice.pressure_01 = 0.0;
+            }
+            MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel }
+                if voice.key_down && voice.note == Note::from_midi_index(key) =>
+            {
+                voice.key_down = false;
+                voice.release_velocity_01 = u7_to_01(vel);
+            }
+            MidiMessage::PitchBend { bend } => voice.pitch_bend = bend.as_f64(),
+            MidiMessage::ChannelAftertouch { vel } => voice.pressure_01 = u7_to_01(vel),
+            MidiMessage::Aftertouch { key, vel } if voice.note == Note::from_midi_index(key) => {
+                voice.pressure_01 = u7_to_01(vel);
+            }
+            MidiMessage::Controller { controller, value }
+                if controller.as_int() == CONTROLLER_TIMBRE =>
+            {
+                voice.timbre_01 = u7_to_01(value);
+            }
+            _ => (),
+        }
+    }
+
+    fn pitch_bend_semitones(&self, i: usize) -> f64 {
+        (self.voices[i].pitch_bend * self.zone.member_pitch_bend_range_semitones)
+            + (self.master_pitch_bend * self.zone.master_pitch_bend_range_semitones)
+    }
+}
+
+impl Signal<MidiEvents> {
+    /// Creates a voice for each member channel of an MPE zone. Each voice carries the pitch
+    /// bend, pressure and timbre of the note playing on its channel, combined with any pitch
+    /// bend on the zone's master channel. The zone's configuration can be changed at runtime
+    /// by MPE configuration messages (RPN 6) and pitch bend sensitivity messages (RPN 0), but
+    /// the number of voices remains the number of member channels in `zone`.
+    pub fn mpe_voice_descs(&self, zone: MpeZone) -> Vec<VoiceDesc> {
+        let state = Rc::new(RefCell::new(MpeState::new(zone)));
+        let update_state = self.map({
+            let state = Rc::clone(&state);
+            move |events| {
+                let mut state = state.borrow_mut();
+                events.for_each_event(|event| {
+                    state.handle_event(event.channel.as_int(), event.message);
+                });
+            }
+        });
+        (0..zone.num_member_channels as usize)
+            .map(|i| {
+                let voice = {
+                    let state = Rc::clone(&state);
+                    update_state.then(move || state.borrow().voices[i])
+                };
+                let pitch_bend_semitones = {
+                    let state = Rc::clone(&state);
+                    update_state.then(move || state.borrow().pitch_bend_semitones(i))
+                };
+                let key_press = update_state
+                    .then({
+                        let state = Rc::clone(&state);
+                        move || std::mem::take(&mut state.borrow_mut().voices[i].key_press)
+                    })
+                    .to_trigger_raw();
+                VoiceDesc {
+                    note: voice.map(|voice| voice.note),
+                    key_down: voice.map(|voice| voice.key_down).to_gate(),
+                    key_press,
+                    velocity_01: voice.map(|voice| voice.velocity_01),
+                    release_velocity_01: voice.map(|voice| voice.release_velocity_01),
+                    pitch_bend_semitones,
+                    pressure_01: voice.map(|voice| voice.pressure_01),
+                    channel_pressure_01: {
+                        let state = Rc::clone(&state);
+                        update_state.then(move || state.borrow().master_pressure_01)
+                    },
+                    timbre_01: voice.map(|voice| voice.timbre_01),
+                }
+            })
+            .collect()
+    }
+}
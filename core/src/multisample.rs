@@ -0,0 +1,272 @@
+use crate::{
+    keyboard::{KeyEvent, VoiceDesc},
+    sampler::{Interpolation, Playhead, Sample, SampleLoop},
+    signal::{Sf64, Signal, SignalCtx},
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// A sample along with the notes and velocities that play it and how it's played. Keys and
+/// velocities are in midi units (0 to 127) as in instrument file formats.
+#[derive(Clone)]
+pub struct SampleRegion {
+    pub sample: Sample,
+    pub lo_key: u8,
+    pub hi_key: u8,
+    pub lo_velocity: u8,
+    pub hi_velocity: u8,
+    /// The key at which the sample plays at its original pitch
+    pub root_key: u8,
+    pub tune_cents: f64,
+    /// Change in pitch per key. 100 plays the sample chromatically and 0 plays it at the same
+    /// pitch for every key, as is common for drums.
+    pub key_tracking_cents: f64,
+    pub volume_db: f64,
+    /// How much the velocity affects the volume, from 0 (not at all) to 1 (the volume is the
+    /// square of the velocity)
+    pub velocity_tracking_01: f64,
+    /// Index of the sample where playback starts
+    pub start: usize,
+    /// Index of the sample where playback stops, or the end of the sample if `None`
+    pub end: Option<usize>,
+    pub sample_loop: SampleLoop,
+    /// Ignore the release of the key and play to the end of the sample
+    pub one_shot: bool,
+    pub attack_s: f64,
+    pub hold_s: f64,
+    pub decay_s: f64,
+    pub sustain_01: f64,
+    pub release_s: f64,
+    /// Regions with a round robin length greater than 1 take turns with other regions playing
+    /// the same notes. Of every `round_robin_length` times that the region's notes are
+    /// played, it plays on the `round_robin_position`th time (counting from 1).
+    pub round_robin_length: usize,
+    pub round_robin_position: usize,
+}
+
+impl SampleRegion {
+    /// A region which plays the sample chromatically across the whole keyboard, at its
+    /// original pitch at middle C
+    pub fn new(sample: &Sample) -> Self {
+        Self {
+            sample: sample.clone(),
+            lo_key: 0,
+            hi_key: 127,
+            lo_velocity: 0,
+            hi_velocity: 127,
+            root_key: 60,
+            tune_cents: 0.0,
+            key_tracking_cents: 100.0,
+            volume_db: 0.0,
+            velocity_tracking_01: 1.0,
+            start: 0,
+            end: None,
+            sample_loop: SampleLoop::default(),
+            one_shot: false,
+            attack_s: 0.0,
+            hold_s: 0.0,
+            decay_s: 0.0,
+            sustain_01: 1.0,
+            release_s: 0.001,
+            round_robin_length: 1,
+            round_robin_position: 1,
+        }
+    }
+
+    fn contains(&self, key: u8, velocity: u8) -> bool {
+        (self.lo_key..=self.hi_key).contains(&key)
+            && (self.lo_velocity..=self.hi_velocity).contains(&velocity)
+    }
+
+    fn rate(&self, key: u8, pitch_bend_semitones: f64) -> f64 {
+        let cents = ((key as f64 - self.root_key as f64) * self.key_tracking_cents)
+            + self.tune_cents
+            + (pitch_bend_semitones * 100.0);
+        (cents / 1200.0).exp2()
+    }
+
+    fn gain(&self, velocity_01: f64) -> f64 {
+        let velocity_gain = 1.0 - self.velocity_tracking_01
+            + (self.velocity_tracking_01 * velocity_01 * velocity_01);
+        10f64.powf(self.volume_db / 20.0) * velocity_gain
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EnvelopeStage {
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+    Finished,
+}
+
+/// A linear attack, hold, decay, sustain, release envelope whose parameters are fixed when a
+/// note starts
+struct Envelope {
+    stage: EnvelopeStage,
+    level_01: f64,
+    hold_remaining_s: f64,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            stage: EnvelopeStage::Attack,
+            level_01: 0.0,
+            hold_remaining_s: 0.0,
+        }
+    }
+
+    fn next(&mut self, region: &SampleRegion, key_down: bool, ctx: &SignalCtx) -> f64 {
+        let dt = 1.0 / ctx.sample_rate_hz;
+        if !key_down && !region.one_shot && self.stage != EnvelopeStage::Finished {
+            self.stage = EnvelopeStage::Release;
+        }
+        match self.stage {
+            EnvelopeStage::Attack => {
+                self.level_01 += dt / region.attack_s.max(dt);
+                if self.level_01 >= 1.0 {
+                    self.level_01 = 1.0;
+                    self.hold_remaining_s = region.hold_s;
+                    self.stage = EnvelopeStage::Hold;
+                }
+            }
+            EnvelopeStage::Hold => {
+                self.hold_remaining_s -= dt;
+                if self.hold_remaining_s <= 0.0 {
+                    self.stage = EnvelopeStage::Decay;
+                }
+            }
+            EnvelopeStage::Decay => {
+                self.level_01 -= (1.0 - region.sustain_01) * dt / region.decay_s.max(dt);
+                if self.level_01 <= region.sustain_01 {
+                    self.level_01 = region.sustain_01;
+                    self.stage = EnvelopeStage::Sustain;
+                }
+            }
+            EnvelopeStage::Sustain => (),
+            EnvelopeStage::Release => {
+                self.level_01 -= dt / region.release_s.max(dt);
+                if self.level_01 <= 0.0 {
+                    self.level_01 = 0.0;
+                    self.stage = EnvelopeStage::Finished;
+                }
+            }
+            EnvelopeStage::Finished => (),
+        }
+        self.level_01
+    }
+}
+
+/// A region being played by a voice
+struct RegionPlayback {
+    region_index: usize,
+    playhead: Playhead,
+    envelope: Envelope,
+    gain: f64,
+}
+
+/// An instrument made of samples, each played over a range of notes and velocities
+pub struct MultiSampleInstrument {
+    pub regions: Vec<SampleRegion>,
+    pub interpolation: Signal<Interpolation>,
+}
+
+impl MultiSampleInstrument {
+    pub fn new(regions: Vec<SampleRegion>) -> Self {
+        Self {
+            regions,
+            interpolation: Interpolation::Linear.into(),
+        }
+    }
+
+    /// Play the instrument with a voice. Each key press starts every region matching the
+    /// voice's note and velocity. Round robin positions are shared between all the voices
+    /// created with the same counters.
+    fn voice_with_round_robin_counters(
+        regions: Rc<Vec<SampleRegion>>,
+        interpolation: Signal<Interpolation>,
+        round_robin_counters: Rc<RefCell<Vec<usize>>>,
+        voice_desc: VoiceDesc,
+    ) -> Sf64 {
+        let playbacks = RefCell::new(Vec::<RegionPlayback>::new());
+        Signal::from_fn(move |ctx| {
+            let mut playbacks = playbacks.borrow_mut();
+            let key = voice_desc.note.sample(ctx).to_midi_index();
+            if voice_desc.key_press.sample(ctx) {
+                playbacks.clear();
+                let velocity_01 = voice_desc.velocity_01.sample(ctx);
+                let velocity = (velocity_01 * 127.0).round() as u8;
+                let mut round_robin_counters = round_robin_counters.borrow_mut();
+                for (region_index, region) in regions.iter().enumerate() {
+                    if !region.contains(key, velocity) {
+                        continue;
+                    }
+                    let counter = &mut round_robin_counters[region_index];
+                    let round_robin_length = region.round_robin_length.max(1);
+                    let plays = *counter % round_robin_length
+                        == (region.round_robin_position.max(1) - 1) % round_robin_length;
+                    *counter += 1;
+                    if !plays {
+                        continue;
+                    }
+                    let mut playhead = Playhead::new(
+                        &region.sample,
+                        region.start,
+                        region.end,
+                        region.sample_loop,
+                        false,
+                    );
+                    playhead.restart();
+                    playbacks.push(RegionPlayback {
+                        region_index,
+                        playhead,
+                        envelope: Envelope::new(),
+                        gain: region.gain(velocity_01),
+                    });
+                }
+            }
+            if playbacks.is_empty() {
+                return 0.0;
+            }
+            let key_down = voice_desc.key_down.sample(ctx);
+            let pitch_bend_semitones = voice_desc.pitch_bend_semitones.sample(ctx);
+            let interpolation = interpolation.sample(ctx);
+            let mut output = 0.0;
+            for playback in playbacks.iter_mut() {
+                let region = &regions[playback.region_index];
+                let level_01 = playback.envelope.next(region, key_down, ctx);
+                let sample = playback.playhead.next(
+                    region.rate(key, pitch_bend_semitones),
+                    key_down,
+                    interpolation,
+                    ctx,
+                );
+                output += sample * level_01 * playback.gain;
+            }
+            playbacks.retain(|playback| {
+                playback.playhead.is_playing() && playback.envelope.stage != EnvelopeStage::Finished
+            });
+            output
+        })
+    }
+
+    /// Play the instrument from key events with up to `num_voices` notes sounding at once
+    pub fn signal(self, key_events: Signal<Vec<KeyEvent>>, num_voices: usize) -> Sf64 {
+        let round_robin_counters = Rc::new(RefCell::new(vec![0; self.regions.len()]));
+        let regions = Rc::new(self.regions);
+        let interpolation = self.interpolation;
+        key_events
+            .polyphony()
+            .num_voices(num_voices)
+            .build_with(|voice_desc| {
+                Self::voice_with_round_robin_counters(
+                    Rc::clone(&regions),
+                    interpolation.clone(),
+                    Rc::clone(&round_robin_counters),
+                    voice_desc,
+                )
+            })
+    }
+}
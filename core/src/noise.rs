@@ -0,0 +1,282 @@
+use crate::{
+    random,
+    signal::{Sf64, Sfreq, Signal},
+};
+use rand::{rngs::StdRng, Rng};
+use std::cell::RefCell;
+
+// Like `noise`, each of these produces values between -1 and 1 and draws from a random number
+// generator created with `random::new_rng`, so their output is reproducible when a random seed
+// is set.
+
+fn white(rng: &mut StdRng) -> f64 {
+    (rng.gen::<f64>() * 2.0) - 1.0
+}
+
+/// The standard deviation of `white`, which is uniformly distributed between -1 and 1
+const WHITE_STD_DEV: f64 = 0.577_350_269_189_625_8;
+
+/// Number of random sources summed by the pink noise generator. Each additional row extends
+/// the pink spectrum down by an octave.
+const PINK_NOISE_NUM_ROWS: usize = 16;
+
+struct PinkNoiseState {
+    rng: StdRng,
+    rows: [f64; PINK_NOISE_NUM_ROWS],
+    running_sum: f64,
+    counter: u32,
+}
+
+impl PinkNoiseState {
+    fn new() -> Self {
+        Self {
+            rng: random::new_rng(),
+            rows: [0.0; PINK_NOISE_NUM_ROWS],
+            running_sum: 0.0,
+            counter: 0,
+        }
+    }
+
+    /// Voss-McCartney algorithm: row i is updated every 2^i samples, with exactly one row
+    /// updated per sample, chosen by the number of trailing zeroes in a counter.
+    fn next(&mut self) -> f64 {
+        self.counter = self.counter.wrapping_add(1);
+        let row = self.counter.trailing_zeros() as usize;
+        if row < PINK_NOISE_NUM_ROWS {
+            let value = white(&mut self.rng);
+            self.running_sum += value - self.rows[row];
+            self.rows[row] = value;
+        }
+        // Add a white noise sample to fill in the highest octave
+        let sum = self.running_sum + white(&mut self.rng);
+        sum / (PINK_NOISE_NUM_ROWS + 1) as f64
+    }
+}
+
+/// Noise with equal power per octave (-3dB per octave)
+pub fn pink_noise() -> Sf64 {
+    let state = RefCell::new(PinkNoiseState::new());
+    Signal::from_fn(move |_ctx| state.borrow_mut().next())
+}
+
+/// Noise with power falling by 6dB per octave, made by integrating white noise
+pub fn brown_noise() -> Sf64 {
+    // The leak keeps the integrator from drifting
+    const LEAK: f64 = 0.02;
+    // The output is scaled to this standard deviation so that only peaks of more than 4 standard
+    // deviations are clipped
+    const OUTPUT_STD_DEV: f64 = 0.25;
+    // Each sample the integrator is multiplied by `decay` and white noise scaled by `input_gain`
+    // is added, so its variance settles where `variance = (decay^2 * variance) + (input_gain^2 *
+    // white_variance)`.
+    let decay = 1.0 / (1.0 + LEAK);
+    let input_gain = LEAK * decay;
+    let integrator_std_dev = (input_gain * WHITE_STD_DEV) / (1.0 - (decay * decay)).sqrt();
+    let gain = OUTPUT_STD_DEV / integrator_std_dev;
+    let rng = RefCell::new(random::new_rng());
+    let state = RefCell::new(0.0);
+    Signal::from_fn(move |_ctx| {
+        let mut state = state.borrow_mut();
+        *state = (*state + (LEAK * white(&mut rng.borrow_mut()))) * decay;
+        (*state * gain).clamp(-1.0, 1.0)
+    })
+}
+
+/// Noise with power rising by 3dB per octave, made by differentiating pink noise
+pub fn blue_noise() -> Sf64 {
+    // Consecutive pink noise samples differ in at most one row and the white noise sample, each
+    // of which changes by at most 2, so the difference is at most 4 divided by the number of
+    // sources. Scaling by the inverse uses the full range without clipping.
+    const GAIN: f64 = (PINK_NOISE_NUM_ROWS + 1) as f64 / 4.0;
+    let state = RefCell::new(PinkNoiseState::new());
+    let previous = RefCell::new(0.0);
+    Signal::from_fn(move |_ctx| {
+        let pink = state.borrow_mut().next();
+        let mut previous = previous.borrow_mut();
+        let output = (pink - *previous) * GAIN;
+        *previous = pink;
+        output
+    })
+}
+
+/// Noise with power rising by 6dB per octave, made by differentiating white noise
+pub fn violet_noise() -> Sf64 {
+    let rng = RefCell::new(random::new_rng());
+    let previous = RefCell::new(0.0);
+    Signal::from_fn(move |_ctx| {
+        let white = white(&mut rng.borrow_mut());
+        let mut previous = previous.borrow_mut();
+        let output = (white - *previous) / 2.0;
+        *previous = white;
+        output
+    })
+}
+
+/// Sparse noise made of impulses of 1 or -1 with 0 between them. One impulse occurs at a random
+/// position within each period of `density`, so the density is the number of impulses per
+/// second. Velvet noise sounds smoother than white noise at densities above about 2kHz.
+pub fn velvet_noise(density: impl Into<Sfreq>) -> Sf64 {
+    struct State {
+        rng: StdRng,
+        /// Position within the current period, from 0 to 1
+        phase_01: f64,
+        impulse_phase_01: f64,
+        impulse_sign: f64,
+        impulse_done: bool,
+    }
+    impl State {
+        /// Choose the position and sign of the impulse in a new period
+        fn start_period(&mut self) {
+            self.impulse_phase_01 = self.rng.gen::<f64>();
+            self.impulse_sign = if self.rng.gen::<bool>() { 1.0 } else { -1.0 };
+            self.impulse_done = false;
+        }
+    }
+    let density = density.into();
+    let mut state = State {
+        rng: random::new_rng(),
+        phase_01: 0.0,
+        impulse_phase_01: 0.0,
+        impulse_sign: 1.0,
+        impulse_done: false,
+    };
+    state.start_period();
+    let state = RefCell::new(state);
+    Signal::from_fn(move |ctx| {
+        let mut state = state.borrow_mut();
+        let mut output = 0.0;
+        if !state.impulse_done && state.phase_01 >= state.impulse_phase_01 {
+            output = state.impulse_sign;
+            state.impulse_done = true;
+        }
+        state.phase_01 += density.sample(ctx).hz() / ctx.sample_rate_hz;
+        if state.phase_01 >= 1.0 {
+            state.phase_01 = state.phase_01.fract();
+            state.start_period();
+        }
+        output
+    })
+}
+
+/// A value which wanders randomly, reflecting off -1 and 1. `rate` controls how quickly it
+/// moves: the typical distance moved in one period of `rate` is about 1.
+pub fn random_walk(rate: impl Into<Sfreq>) -> Sf64 {
+    let rate = rate.into();
+    let rng = RefCell::new(random::new_rng());
+    let value = RefCell::new(0.0);
+    Signal::from_fn(move |ctx| {
+        let step_size = (rate.sample(ctx).hz() / ctx.sample_rate_hz).sqrt();
+        let mut value = value.borrow_mut();
+        // Scale the uniform distribution to have unit variance.
+        *value += white(&mut rng.borrow_mut()) * 3f64.sqrt() * step_size;
+        if *value > 1.0 {
+            *value = 2.0 - *value;
+        } else if *value < -1.0 {
+            *value = -2.0 - *value;
+        }
+        value.clamp(-1.0, 1.0)
+    })
+}
+
+struct RandomStepsState {
+    rng: StdRng,
+    phase_01: f64,
+    previous: f64,
+    current: f64,
+}
+
+impl RandomStepsState {
+    fn new() -> Self {
+        let mut rng = random::new_rng();
+        let current = white(&mut rng);
+        Self {
+            rng,
+            phase_01: 0.0,
+            previous: current,
+            current,
+        }
+    }
+
+    fn tick(&mut self, freq_hz: f64, sample_rate_hz: f64) {
+        self.phase_01 += freq_hz / sample_rate_hz;
+        if self.phase_01 >= 1.0 {
+            self.phase_01 = self.phase_01.fract();
+            self.previous = self.current;
+            self.current = white(&mut self.rng);
+        }
+    }
+}
+
+/// Sample and hold of white noise, choosing a new random value at `freq`
+pub fn random_stepped(freq: impl Into<Sfreq>) -> Sf64 {
+    let freq = freq.into();
+    let state = RefCell::new(RandomStepsState::new());
+    Signal::from_fn(move |ctx| {
+        let mut state = state.borrow_mut();
+        state.tick(freq.sample(ctx).hz(), ctx.sample_rate_hz);
+        state.current
+    })
+}
+
+/// Chooses a new random value at `freq` like `random_stepped`, but moves smoothly from the
+/// previous value to each new value over the course of a period
+pub fn random_smooth(freq: impl Into<Sfreq>) -> Sf64 {
+    let freq = freq.into();
+    let state = RefCell::new(RandomStepsState::new());
+    Signal::from_fn(move |ctx| {
+        let mut state = state.borrow_mut();
+        state.tick(freq.sample(ctx).hz(), ctx.sample_rate_hz);
+        // Cosine interpolation so that the output has no corners at the sample points
+        let t = (1.0 - (state.phase_01 * std::f64::consts::PI).cos()) / 2.0;
+        state.previous + ((state.current - state.previous) * t)
+    })
+}
+
+#[test]
+fn test_noise_range() {
+    use crate::signal::SignalCtx;
+    const NUM_SAMPLES: u64 = 100_000;
+    for (name, signal) in [
+        ("brown", random::with_random_seed(42, brown_noise)),
+        ("blue", random::with_random_seed(42, blue_noise)),
+    ] {
+        let mut num_clipped = 0;
+        let mut sum_squares = 0.0;
+        for sample_index in 0..NUM_SAMPLES {
+            let x = signal.sample(&SignalCtx {
+                sample_index,
+                sample_rate_hz: 44100.0,
+            });
+            assert!((-1.0..=1.0).contains(&x), "{name} noise out of range: {x}");
+            if x.abs() >= 1.0 {
+                num_clipped += 1;
+            }
+            sum_squares += x * x;
+        }
+        let std_dev = (sum_squares / NUM_SAMPLES as f64).sqrt();
+        assert!(
+            num_clipped < NUM_SAMPLES / 1000,
+            "{name} noise clipped {num_clipped} times"
+        );
+        assert!(
+            (0.15..0.4).contains(&std_dev),
+            "{name} noise has standard deviation {std_dev}"
+        );
+    }
+}
+
+#[test]
+fn test_velvet_noise_first_period() {
+    use crate::signal::{Freq, SignalCtx};
+    // One impulse per 100 samples
+    let signal = random::with_random_seed(42, || velvet_noise(Freq::from_hz(441.0)));
+    let num_impulses = (0..100)
+        .filter(|&sample_index| {
+            signal.sample(&SignalCtx {
+                sample_index,
+                sample_rate_hz: 44100.0,
+            }) != 0.0
+        })
+        .count();
+    assert_eq!(num_impulses, 1);
+}
@@ -1,5 +1,5 @@
-use crate::signal::{const_, Sf64, Sfreq, Signal, Trigger};
-use std::{cell::Cell, f64::consts::PI};
+use crate::signal::{const_, Sf64, Sfreq, Signal, SignalCtx, Trigger};
+use std::{cell::RefCell, f64::consts::PI};
 
 #[derive(Default, Clone, Copy, Debug)]
 pub enum Waveform {
@@ -16,51 +16,290 @@ impl From<Waveform> for Signal<Waveform> {
     }
 }
 
+impl Waveform {
+    fn sample(self, phase_01: f64, pulse_width_01: f64) -> f64 {
+        match self {
+            Waveform::Sine => (phase_01 * PI * 2.0).sin(),
+            Waveform::Saw => (phase_01 * 2.0) - 1.0,
+            Waveform::Triangle => (((phase_01 * 2.0) - 1.0).abs() * 2.0) - 1.0,
+            Waveform::Pulse => {
+                if phase_01 < pulse_width_01 {
+                    -1.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubOscillatorWaveform {
+    #[default]
+    Square,
+    Sine,
+}
+
+/// An oscillator an octave or two below the main oscillator whose phase is locked to it
+pub struct SubOscillator {
+    pub waveform: SubOscillatorWaveform,
+    /// Typically 1 or 2
+    pub octaves_below: u8,
+    /// How much of the sub oscillator is mixed into the oscillator's signal
+    pub level_01: Sf64,
+}
+
 pub struct Oscillator {
     pub waveform: Signal<Waveform>,
     pub freq: Sfreq,
     pub pulse_width_01: Sf64,
     pub reset_trigger: Trigger,
     pub reset_offset_01: Sf64,
+    /// The phase of a master oscillator. Each time it wraps around, this oscillator's phase is
+    /// reset to 0 (hard sync). The reset is timed to a fraction of a sample and the resulting
+    /// discontinuities are smoothed to reduce aliasing, at the cost of delaying the output by
+    /// one sample.
+    pub hard_sync_phase_01: Option<Sf64>,
+    pub sub_oscillator: Option<SubOscillator>,
+    /// Added to the frequency after exponential FM. Negative frequencies make the phase run
+    /// backwards (through-zero FM).
+    pub linear_fm_hz: Sf64,
+    /// The frequency is multiplied by 2 to the power of this value, like a 1V/octave input
+    pub exponential_fm_octaves: Sf64,
+}
+
+/// All the signals produced by an oscillator
+pub struct OscillatorOutputs {
+    /// The oscillator's waveform mixed with its sub oscillator
+    pub signal: Sf64,
+    /// The sub oscillator alone
+    pub sub: Sf64,
+    /// The phase of the oscillator from 0 to 1, for driving waveshapers or syncing other
+    /// oscillators
+    pub phase_01: Sf64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct OscillatorSample {
+    value: f64,
+    sub: f64,
+    phase_01: f64,
+}
+
+#[derive(Default)]
+struct OscillatorState {
+    phase_01: Option<f64>,
+    prev_sample_index: u64,
+    /// Counts cycles of the main oscillator to derive the phase of the sub oscillator
+    cycle_count: u64,
+    prev_hard_sync_phase_01: Option<f64>,
+    /// Hard synced oscillators output each sample one sample late so that corrections for
+    /// discontinuities can be applied on both sides of them
+    pending: OscillatorSample,
+}
+
+/// Smooths out a step discontinuity with a polynomial approximation of a band-limited step
+/// (PolyBLEP). The step of `height` happened `samples_ago` samples before the current sample
+/// (between 0 and 1). Returns corrections to add to the previous and current samples.
+fn poly_blep(height: f64, samples_ago: f64) -> (f64, f64) {
+    let d = samples_ago.clamp(0.0, 1.0);
+    (height * d * d / 2.0, -height * (1.0 - d) * (1.0 - d) / 2.0)
+}
+
+/// Accumulates corrections for the discontinuities of a waveform while its phase advances by
+/// `phase_delta` over `duration_samples` samples from `start_phase_01`, ending
+/// `samples_after_end` samples before the current sample. The phase may run in either direction.
+fn waveform_discontinuities(
+    waveform: Waveform,
+    pulse_width_01: f64,
+    start_phase_01: f64,
+    phase_delta: f64,
+    duration_samples: f64,
+    samples_after_end: f64,
+    corrections: &mut (f64, f64),
+) {
+    if phase_delta == 0.0 || duration_samples <= 0.0 {
+        return;
+    }
+    // The phase at which each discontinuity occurs and its height when the phase is increasing
+    let steps: &[(f64, f64)] = match waveform {
+        Waveform::Saw => &[(1.0, -2.0)],
+        Waveform::Pulse if pulse_width_01 > 0.0 && pulse_width_01 < 1.0 => {
+            &[(pulse_width_01, 2.0), (1.0, -2.0)]
+        }
+        _ => &[],
+    };
+    let end_phase_01 = start_phase_01 + phase_delta;
+    for &(step_phase_01, height) in steps {
+        let (step_phase_01, height, crossed) = if phase_delta > 0.0 {
+            let crossed = start_phase_01 < step_phase_01 && end_phase_01 >= step_phase_01;
+            (step_phase_01, height, crossed)
+        } else {
+            // Running backwards the phase wraps from 0 to 1, and each step is reversed
+            let step_phase_01 = if step_phase_01 == 1.0 {
+                0.0
+            } else {
+                step_phase_01
+            };
+            let crossed = start_phase_01 >= step_phase_01 && end_phase_01 < step_phase_01;
+            (step_phase_01, -height, crossed)
+        };
+        if crossed {
+            let samples_into_segment =
+                duration_samples * (step_phase_01 - start_phase_01) / phase_delta;
+            let (previous, current) = poly_blep(
+                height,
+                duration_samples - samples_into_segment + samples_after_end,
+            );
+            corrections.0 += previous;
+            corrections.1 += current;
+        }
+    }
 }
 
 impl Oscillator {
-    pub fn signal(self) -> Sf64 {
-        let state_opt = Cell::new(None);
-        let prev_sample_inedx = Cell::new(0);
-        Signal::from_fn(move |ctx| {
-            let sample_index_delta = ctx.sample_index - prev_sample_inedx.get();
-            prev_sample_inedx.set(ctx.sample_index);
-            if sample_index_delta == 0 {
-                return 0.0;
+    fn tick(&self, state: &mut OscillatorState, ctx: &SignalCtx) -> OscillatorSample {
+        let sample_index_delta = ctx.sample_index - state.prev_sample_index;
+        state.prev_sample_index = ctx.sample_index;
+        if sample_index_delta == 0 {
+            return OscillatorSample::default();
+        }
+        let phase_01 = match state.phase_01 {
+            None => self.reset_offset_01.sample(ctx),
+            Some(phase_01) => {
+                if self.reset_trigger.sample(ctx) {
+                    self.reset_offset_01.sample(ctx)
+                } else {
+                    phase_01
+                }
             }
-            let state = match state_opt.get() {
-                None => self.reset_offset_01.sample(ctx),
-                Some(state) => {
-                    if self.reset_trigger.sample(ctx) {
-                        self.reset_offset_01.sample(ctx)
-                    } else {
-                        state
-                    }
+        };
+        let waveform = self.waveform.sample(ctx);
+        let pulse_width_01 = self.pulse_width_01.sample(ctx);
+        // Exponential FM scales the frequency and linear FM offsets it, possibly taking it
+        // below zero, in which case the phase runs backwards
+        let freq_hz = (self.freq.sample(ctx).hz() * self.exponential_fm_octaves.sample(ctx).exp2())
+            + self.linear_fm_hz.sample(ctx);
+        let phase_delta = (sample_index_delta as f64 * freq_hz) / ctx.sample_rate_hz;
+        let try_phase_01 = (phase_01 + phase_delta).rem_euclid(1.0);
+        let mut next_phase_01 = if try_phase_01.is_nan() {
+            phase_01
+        } else {
+            try_phase_01
+        };
+        // +1 when the phase wraps forwards and -1 when it wraps backwards
+        let mut cycle_delta = if phase_01 + phase_delta >= 1.0 {
+            1
+        } else if phase_01 + phase_delta < 0.0 {
+            -1
+        } else {
+            0
+        };
+        // Corrections to the previous and current samples for discontinuities
+        let mut corrections = (0.0, 0.0);
+        if let Some(hard_sync_phase_01) = self.hard_sync_phase_01.as_ref() {
+            let master_phase_01 = hard_sync_phase_01.sample(ctx);
+            let mut samples_since_sync = None;
+            if let Some(prev_master_phase_01) = state.prev_hard_sync_phase_01 {
+                // A jump of more than half a cycle means the master wrapped around, either
+                // forwards or backwards if its frequency is negative
+                if prev_master_phase_01 - master_phase_01 > 0.5 {
+                    let master_phase_delta = master_phase_01 + 1.0 - prev_master_phase_01;
+                    samples_since_sync = Some((master_phase_01 / master_phase_delta).min(1.0));
+                } else if master_phase_01 - prev_master_phase_01 > 0.5 {
+                    let master_phase_delta = prev_master_phase_01 + 1.0 - master_phase_01;
+                    samples_since_sync =
+                        Some(((1.0 - master_phase_01) / master_phase_delta).min(1.0));
                 }
-            };
-            let state_delta =
-                (sample_index_delta as f64 * self.freq.sample(ctx).hz()) / ctx.sample_rate_hz;
-            let try_state = (state + state_delta).rem_euclid(1.0);
-            let state = if try_state.is_nan() { state } else { try_state };
-            state_opt.set(Some(state));
-            match self.waveform.sample(ctx) {
-                Waveform::Sine => (state * PI * 2.0).sin(),
-                Waveform::Saw => (state * 2.0) - 1.0,
-                Waveform::Triangle => (((state * 2.0) - 1.0).abs() * 2.0) - 1.0,
-                Waveform::Pulse => {
-                    if state < self.pulse_width_01.sample(ctx) {
-                        -1.0
-                    } else {
-                        1.0
-                    }
+            }
+            state.prev_hard_sync_phase_01 = Some(master_phase_01);
+            match samples_since_sync {
+                Some(samples_since_sync) => {
+                    // Advance up to the moment of the reset, then from 0 for the rest of the
+                    // sample
+                    let phase_delta_before_sync = phase_delta * (1.0 - samples_since_sync);
+                    waveform_discontinuities(
+                        waveform,
+                        pulse_width_01,
+                        phase_01,
+                        phase_delta_before_sync,
+                        1.0 - samples_since_sync,
+                        samples_since_sync,
+                        &mut corrections,
+                    );
+                    let phase_at_sync_01 = (phase_01 + phase_delta_before_sync).rem_euclid(1.0);
+                    let step = waveform.sample(0.0, pulse_width_01)
+                        - waveform.sample(phase_at_sync_01, pulse_width_01);
+                    let (previous, current) = poly_blep(step, samples_since_sync);
+                    corrections.0 += previous;
+                    corrections.1 += current;
+                    let phase_delta_after_sync = phase_delta * samples_since_sync;
+                    waveform_discontinuities(
+                        waveform,
+                        pulse_width_01,
+                        0.0,
+                        phase_delta_after_sync,
+                        samples_since_sync,
+                        0.0,
+                        &mut corrections,
+                    );
+                    next_phase_01 = phase_delta_after_sync.rem_euclid(1.0);
+                    cycle_delta = 1;
                 }
+                None => waveform_discontinuities(
+                    waveform,
+                    pulse_width_01,
+                    phase_01,
+                    phase_delta,
+                    1.0,
+                    0.0,
+                    &mut corrections,
+                ),
             }
-        })
+        }
+        state.cycle_count = state.cycle_count.wrapping_add_signed(cycle_delta);
+        state.phase_01 = Some(next_phase_01);
+        let (sub, sub_level_01) = match self.sub_oscillator.as_ref() {
+            None => (0.0, 0.0),
+            Some(sub_oscillator) => {
+                let num_cycles = 1u64 << sub_oscillator.octaves_below.min(8);
+                let sub_phase_01 =
+                    ((state.cycle_count % num_cycles) as f64 + next_phase_01) / num_cycles as f64;
+                let sub = match sub_oscillator.waveform {
+                    SubOscillatorWaveform::Square => Waveform::Pulse.sample(sub_phase_01, 0.5),
+                    SubOscillatorWaveform::Sine => Waveform::Sine.sample(sub_phase_01, 0.5),
+                };
+                (sub, sub_oscillator.level_01.sample(ctx))
+            }
+        };
+        let sample = OscillatorSample {
+            value: waveform.sample(next_phase_01, pulse_width_01)
+                + corrections.1
+                + (sub * sub_level_01),
+            sub,
+            phase_01: next_phase_01,
+        };
+        if self.hard_sync_phase_01.is_some() {
+            let mut output = state.pending;
+            output.value += corrections.0;
+            state.pending = sample;
+            output
+        } else {
+            sample
+        }
+    }
+
+    pub fn outputs(self) -> OscillatorOutputs {
+        let state = RefCell::new(OscillatorState::default());
+        let outputs = Signal::from_fn(move |ctx| self.tick(&mut state.borrow_mut(), ctx));
+        OscillatorOutputs {
+            signal: outputs.map(|sample| sample.value),
+            sub: outputs.map(|sample| sample.sub),
+            phase_01: outputs.map(|sample| sample.phase_01),
+        }
+    }
+
+    pub fn signal(self) -> Sf64 {
+        self.outputs().signal
     }
 }
@@ -145,3 +145,534 @@ pub mod drum {
         noise.mix(|dry| temporary_reverb(&trigger, dry, 1.0)) * 0.5
     }
 }
+
+/// Physical models of acoustic instruments. Each model simulates the vibrating part of an
+/// instrument with delay lines or resonators tuned to `freq`, so they respond to changes in
+/// frequency while playing. The tuning of the delay line models depends on reading their delay
+/// lines at fractional positions, which keeps them in tune across the keyboard.
+pub mod physical {
+    use crate::{
+        filters::FractionalDelayLine,
+        random,
+        signal::{Gate, Sf64, Sfreq, Signal, SignalCtx, Trigger},
+    };
+    use rand::{rngs::StdRng, Rng};
+    use std::{cell::RefCell, f64::consts::PI};
+
+    /// Frequencies are clamped above this value to limit the size of delay lines
+    const MIN_FREQ_HZ: f64 = 20.0;
+
+    fn period_samples(freq: &Sfreq, ctx: &SignalCtx) -> f64 {
+        let max_freq_hz = ctx.sample_rate_hz / 4.0;
+        ctx.sample_rate_hz / freq.sample(ctx).hz().clamp(MIN_FREQ_HZ, max_freq_hz)
+    }
+
+    /// Moves linearly towards 1 while a gate is held and back to 0 when it's released
+    struct LinearEnvelope {
+        value: f64,
+    }
+
+    impl LinearEnvelope {
+        fn tick(&mut self, gate: bool, attack_s: f64, release_s: f64, ctx: &SignalCtx) -> f64 {
+            if gate {
+                self.value = (self.value + (1.0 / (attack_s * ctx.sample_rate_hz))).min(1.0);
+            } else {
+                self.value = (self.value - (1.0 / (release_s * ctx.sample_rate_hz))).max(0.0);
+            }
+            self.value
+        }
+    }
+
+    /// A value derived from the inputs of a model which is only recomputed when they change, so
+    /// that functions like `powf` aren't evaluated on every sample
+    struct Cached<K, V> {
+        inputs: Option<K>,
+        value: V,
+    }
+
+    impl<K: PartialEq + Copy, V: Default> Cached<K, V> {
+        fn new() -> Self {
+            Self {
+                inputs: None,
+                value: V::default(),
+            }
+        }
+
+        fn get(&mut self, inputs: K, f: impl FnOnce(K) -> V) -> &V {
+            if self.inputs != Some(inputs) {
+                self.value = f(inputs);
+                self.inputs = Some(inputs);
+            }
+            &self.value
+        }
+    }
+
+    /// Removes any constant offset that builds up in the feedback loop of a model
+    #[derive(Default)]
+    struct DcBlocker {
+        previous_input: f64,
+        previous_output: f64,
+    }
+
+    impl DcBlocker {
+        fn run(&mut self, input: f64) -> f64 {
+            let output = input - self.previous_input + (0.995 * self.previous_output);
+            self.previous_input = input;
+            self.previous_output = output;
+            output
+        }
+    }
+
+    struct PluckState {
+        rng: StdRng,
+        delay_line: FractionalDelayLine,
+        previous_loop_sample: f64,
+        excitation_remaining_samples: f64,
+        excitation_filter: f64,
+        /// Keyed by the period, damping and sample rate
+        gain_per_period: Cached<(f64, f64, f64), f64>,
+        dc_blocker: DcBlocker,
+    }
+
+    /// Karplus-Strong plucked string. Each trigger fills the string with a period of noise
+    /// which circulates around a delay line one period long, losing high frequencies and
+    /// energy on each trip. `damping_01` controls how quickly the note dies away. `stretch_01`
+    /// is the Jaffe-Smith decay stretching factor: at 0 high harmonics die away much faster
+    /// than the fundamental and at 1 they decay at a similar rate, sounding brighter and more
+    /// metallic. `brightness_01` controls the high frequency content of the pluck itself.
+    pub fn pluck(
+        freq: Sfreq,
+        trigger: Trigger,
+        damping_01: Sf64,
+        stretch_01: Sf64,
+        brightness_01: Sf64,
+    ) -> Sf64 {
+        // Decay times (until the level has fallen by 60dB) at the extremes of damping
+        const MAX_DECAY_S: f64 = 20.0;
+        const MIN_DECAY_S: f64 = 0.05;
+        let state = RefCell::new(PluckState {
+            rng: random::new_rng(),
+            delay_line: FractionalDelayLine::new(),
+            previous_loop_sample: 0.0,
+            excitation_remaining_samples: 0.0,
+            excitation_filter: 0.0,
+            gain_per_period: Cached::new(),
+            dc_blocker: DcBlocker::default(),
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let period_samples = period_samples(&freq, ctx);
+            state.delay_line.reserve(ctx.sample_rate_hz / MIN_FREQ_HZ);
+            if trigger.sample(ctx) {
+                state.excitation_remaining_samples = period_samples;
+            }
+            let excitation = if state.excitation_remaining_samples > 0.0 {
+                state.excitation_remaining_samples -= 1.0;
+                let coefficient = 0.05 + (0.95 * brightness_01.sample(ctx).clamp(0.0, 1.0));
+                let white = (state.rng.gen::<f64>() * 2.0) - 1.0;
+                state.excitation_filter += (white - state.excitation_filter) * coefficient;
+                state.excitation_filter
+            } else {
+                0.0
+            };
+            // The loop filter averages adjacent samples weighted by `s`, which delays the
+            // signal by `s` samples. The remainder of the period is made up by the delay line,
+            // which already delays by one sample since it's read before being written.
+            let s = 0.5 * (1.0 - stretch_01.sample(ctx).clamp(0.0, 0.99));
+            let loop_sample = state.delay_line.read(period_samples - 1.0 - s);
+            let filtered = ((1.0 - s) * loop_sample) + (s * state.previous_loop_sample);
+            state.previous_loop_sample = loop_sample;
+            let gain_per_period = *state.gain_per_period.get(
+                (
+                    period_samples,
+                    damping_01.sample(ctx).clamp(0.0, 1.0),
+                    ctx.sample_rate_hz,
+                ),
+                |(period_samples, damping_01, sample_rate_hz)| {
+                    let decay_s = MAX_DECAY_S * (MIN_DECAY_S / MAX_DECAY_S).powf(damping_01);
+                    0.001f64.powf(period_samples / (decay_s * sample_rate_hz))
+                },
+            );
+            let output = excitation + (filtered * gain_per_period);
+            state.delay_line.push(output);
+            state.dc_blocker.run(output)
+        })
+    }
+
+    struct BowedStringState {
+        /// Waves travelling between the bow and the nut
+        neck: FractionalDelayLine,
+        /// Waves travelling between the bow and the bridge
+        bridge: FractionalDelayLine,
+        neck_output: f64,
+        bridge_output: f64,
+        string_filter: f64,
+        /// The pole of the string filter and the total delay of the delay lines, keyed by the
+        /// period and sample rate
+        string_filter_pole_and_loop_samples: Cached<(f64, f64), (f64, f64)>,
+        envelope: LinearEnvelope,
+        dc_blocker: DcBlocker,
+    }
+
+    /// Bowed string waveguide. The string is split at the bow into two delay lines which meet
+    /// at the bow, where the friction between bow and string is modelled with a nonlinear
+    /// function of the difference between their velocities. The string is bowed while `gate`
+    /// is held. Higher `bow_pressure_01` makes a grittier tone and `bow_velocity_01` controls
+    /// the loudness.
+    pub fn bowed_string(
+        freq: Sfreq,
+        gate: Gate,
+        bow_pressure_01: Sf64,
+        bow_velocity_01: Sf64,
+    ) -> Sf64 {
+        /// Position of the bow along the string, measured from the bridge
+        const BOW_POSITION_01: f64 = 0.127236;
+        const STRING_FILTER_GAIN: f64 = 0.95;
+        let state = RefCell::new(BowedStringState {
+            neck: FractionalDelayLine::new(),
+            bridge: FractionalDelayLine::new(),
+            neck_output: 0.0,
+            bridge_output: 0.0,
+            string_filter: 0.0,
+            string_filter_pole_and_loop_samples: Cached::new(),
+            envelope: LinearEnvelope { value: 0.0 },
+            dc_blocker: DcBlocker::default(),
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let period_samples = period_samples(&freq, ctx);
+            let max_delay_samples = ctx.sample_rate_hz / MIN_FREQ_HZ;
+            state.neck.reserve(max_delay_samples);
+            state.bridge.reserve(max_delay_samples);
+            let (pole, loop_samples) = *state.string_filter_pole_and_loop_samples.get(
+                (period_samples, ctx.sample_rate_hz),
+                |(period_samples, sample_rate_hz)| {
+                    // One-pole lowpass filter modelling the losses at the bridge. Its pole is
+                    // scaled so that it has a similar effect at different sample rates.
+                    let pole = 0.75 - (0.2 * 22050.0 / sample_rate_hz);
+                    // Each delay line delays by an extra sample since it's read before being
+                    // written, and the string filter adds its phase delay at the fundamental.
+                    let omega = 2.0 * PI / period_samples;
+                    let filter_delay_samples =
+                        (pole * omega.sin()).atan2(1.0 - (pole * omega.cos())) / omega;
+                    let loop_samples = (period_samples - 2.0 - filter_delay_samples).max(0.0);
+                    (pole, loop_samples)
+                },
+            );
+            state.string_filter = ((1.0 - pole) * state.bridge_output * STRING_FILTER_GAIN)
+                + (pole * state.string_filter);
+            let bridge_reflection = -state.string_filter;
+            let nut_reflection = -state.neck_output;
+            let string_velocity = bridge_reflection + nut_reflection;
+            let envelope = state.envelope.tick(gate.sample(ctx), 0.05, 0.1, ctx);
+            let bow_velocity =
+                (0.03 + (0.2 * bow_velocity_01.sample(ctx).clamp(0.0, 1.0))) * envelope;
+            let velocity_difference = bow_velocity - string_velocity;
+            // Friction is highest when the bow and string move together, so that the bow
+            // drags the string along, and falls away as they slip past one another.
+            let slope = 5.0 - (4.0 * bow_pressure_01.sample(ctx).clamp(0.0, 1.0));
+            let friction = (((velocity_difference * slope).abs() + 0.75).powi(-4)).min(1.0);
+            let new_velocity = velocity_difference * friction;
+            state.neck.push(bridge_reflection + new_velocity);
+            state.bridge.push(nut_reflection + new_velocity);
+            state.neck_output = state.neck.read(loop_samples * (1.0 - BOW_POSITION_01));
+            state.bridge_output = state.bridge.read(loop_samples * BOW_POSITION_01);
+            let output = state.bridge_output * 2.5;
+            state.dc_blocker.run(output)
+        })
+    }
+
+    struct BlownPipeState {
+        rng: StdRng,
+        delay_line: FractionalDelayLine,
+        delay_output: f64,
+        previous_delay_output: f64,
+        envelope: LinearEnvelope,
+        dc_blocker: DcBlocker,
+    }
+
+    /// Reed instrument waveguide, like a clarinet. A single delay line models the bore of the
+    /// pipe, and the reed at one end lets through more or less air depending on the difference
+    /// in pressure across it. The pipe is blown while `gate` is held. Higher
+    /// `breath_pressure_01` makes a louder and brighter tone, and `breath_noise_01` adds
+    /// turbulence to the breath.
+    pub fn blown_pipe(
+        freq: Sfreq,
+        gate: Gate,
+        breath_pressure_01: Sf64,
+        breath_noise_01: Sf64,
+    ) -> Sf64 {
+        let state = RefCell::new(BlownPipeState {
+            rng: random::new_rng(),
+            delay_line: FractionalDelayLine::new(),
+            delay_output: 0.0,
+            previous_delay_output: 0.0,
+            envelope: LinearEnvelope { value: 0.0 },
+            dc_blocker: DcBlocker::default(),
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            let period_samples = period_samples(&freq, ctx);
+            state.delay_line.reserve(ctx.sample_rate_hz / MIN_FREQ_HZ);
+            let envelope = state.envelope.tick(gate.sample(ctx), 0.01, 0.02, ctx);
+            let white = (state.rng.gen::<f64>() * 2.0) - 1.0;
+            let breath_pressure = (0.55 + (0.4 * breath_pressure_01.sample(ctx).clamp(0.0, 1.0)))
+                * envelope
+                * (1.0 + (0.2 * breath_noise_01.sample(ctx).clamp(0.0, 1.0) * white));
+            // The open end of the pipe inverts and lowpass filters the reflected wave
+            let reflection = -0.95 * 0.5 * (state.delay_output + state.previous_delay_output);
+            let pressure_difference = reflection - breath_pressure;
+            let reed = (0.7 - (0.3 * pressure_difference)).clamp(-1.0, 1.0);
+            state
+                .delay_line
+                .push(breath_pressure + (pressure_difference * reed));
+            // The inverting reflection means a wave makes two trips along the pipe in each
+            // period. The delay line is read before being written, adding a sample, and the
+            // reflection filter adds half a sample.
+            let delay_samples = ((period_samples / 2.0) - 1.5).max(0.0);
+            state.previous_delay_output = state.delay_output;
+            state.delay_output = state.delay_line.read(delay_samples);
+            let output = state.delay_output;
+            state.dc_blocker.run(output)
+        })
+    }
+
+    /// A mode of vibration of a resonating object
+    #[derive(Debug, Clone, Copy)]
+    pub struct ModalMode {
+        /// Frequency of the mode relative to the fundamental
+        pub ratio: f64,
+        pub gain: f64,
+        /// Decay time of the mode relative to that of the fundamental
+        pub decay_scale: f64,
+    }
+
+    /// Modes of a uniform bar free at both ends, like the bars of a marimba or glockenspiel
+    pub const STRUCK_BAR_MODES: &[ModalMode] = &[
+        ModalMode {
+            ratio: 1.0,
+            gain: 1.0,
+            decay_scale: 1.0,
+        },
+        ModalMode {
+            ratio: 2.756,
+            gain: 0.5,
+            decay_scale: 0.6,
+        },
+        ModalMode {
+            ratio: 5.404,
+            gain: 0.33,
+            decay_scale: 0.4,
+        },
+        ModalMode {
+            ratio: 8.933,
+            gain: 0.25,
+            decay_scale: 0.3,
+        },
+    ];
+
+    #[derive(Default, Clone, Copy)]
+    struct Resonator {
+        y1: f64,
+        y2: f64,
+    }
+
+    /// Coefficients of the two-pole filter resonating at a mode's frequency
+    #[derive(Clone, Copy)]
+    struct ResonatorCoefficients {
+        input_gain: f64,
+        a1: f64,
+        a2: f64,
+    }
+
+    struct ModalState {
+        resonators: Vec<Resonator>,
+        /// The coefficients of each mode, or `None` for modes above the Nyquist frequency,
+        /// keyed by the frequency, decay time and sample rate
+        coefficients: Cached<(f64, f64, f64), Vec<Option<ResonatorCoefficients>>>,
+        mallet_position_samples: f64,
+        mallet_duration_samples: f64,
+    }
+
+    /// A bank of resonators, one per mode, struck by a mallet each time `trigger` fires.
+    /// `decay_s` is the time taken for the fundamental to fall by 60dB. Harder mallets
+    /// (higher `hardness_01`) make shorter impulses which excite the higher modes more.
+    pub fn modal_resonator_bank(
+        freq: Sfreq,
+        trigger: Trigger,
+        modes: Vec<ModalMode>,
+        decay_s: Sf64,
+        hardness_01: Sf64,
+    ) -> Sf64 {
+        const SOFTEST_MALLET_S: f64 = 0.005;
+        const HARDEST_MALLET_S: f64 = 0.0002;
+        let state = RefCell::new(ModalState {
+            resonators: vec![Resonator::default(); modes.len()],
+            coefficients: Cached::new(),
+            mallet_position_samples: 0.0,
+            mallet_duration_samples: 0.0,
+        });
+        Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            if trigger.sample(ctx) {
+                let mallet_s = SOFTEST_MALLET_S
+                    * (HARDEST_MALLET_S / SOFTEST_MALLET_S)
+                        .powf(hardness_01.sample(ctx).clamp(0.0, 1.0));
+                state.mallet_position_samples = 0.0;
+                state.mallet_duration_samples = (mallet_s * ctx.sample_rate_hz).max(1.0);
+            }
+            // The mallet's force is a raised cosine pulse with unit area
+            let excitation = if state.mallet_position_samples < state.mallet_duration_samples {
+                let duration = state.mallet_duration_samples;
+                let t = state.mallet_position_samples / duration;
+                state.mallet_position_samples += 1.0;
+                (1.0 - (t * 2.0 * PI).cos()) / duration
+            } else {
+                0.0
+            };
+            let ModalState {
+                resonators,
+                coefficients,
+                ..
+            } = &mut *state;
+            let coefficients = coefficients.get(
+                (
+                    freq.sample(ctx).hz(),
+                    decay_s.sample(ctx).max(0.001),
+                    ctx.sample_rate_hz,
+                ),
+                |(freq_hz, decay_s, sample_rate_hz)| {
+                    modes
+                        .iter()
+                        .map(|mode| {
+                            let mode_freq_hz = freq_hz * mode.ratio;
+                            if mode_freq_hz >= sample_rate_hz / 2.0 {
+                                return None;
+                            }
+                            let omega = 2.0 * PI * mode_freq_hz / sample_rate_hz;
+                            let r =
+                                0.001f64.powf(1.0 / (decay_s * mode.decay_scale * sample_rate_hz));
+                            Some(ResonatorCoefficients {
+                                // Scaling the input by sin(omega) gives each mode a peak level
+                                // of about 1
+                                input_gain: omega.sin(),
+                                a1: 2.0 * r * omega.cos(),
+                                a2: r * r,
+                            })
+                        })
+                        .collect()
+                },
+            );
+            let mut output = 0.0;
+            for ((mode, resonator), coefficients) in
+                modes.iter().zip(resonators.iter_mut()).zip(coefficients)
+            {
+                let Some(coefficients) = coefficients else {
+                    *resonator = Resonator::default();
+                    continue;
+                };
+                let y = (excitation * coefficients.input_gain) + (coefficients.a1 * resonator.y1)
+                    - (coefficients.a2 * resonator.y2);
+                resonator.y2 = resonator.y1;
+                resonator.y1 = y;
+                output += y * mode.gain;
+            }
+            output
+        })
+    }
+
+    /// A modal resonator bank with the modes of a struck bar
+    pub fn struck_bar(freq: Sfreq, trigger: Trigger, decay_s: Sf64, hardness_01: Sf64) -> Sf64 {
+        modal_resonator_bank(
+            freq,
+            trigger,
+            STRUCK_BAR_MODES.to_vec(),
+            decay_s,
+            hardness_01,
+        )
+    }
+
+    /// Render a signal at 44.1kHz and return the difference in cents between its fundamental and
+    /// `freq_hz`, starting from sample `start`. The fundamental is measured from the change in
+    /// phase of the signal's component at `freq_hz` between two overlapping windows.
+    #[cfg(test)]
+    fn test_tuning_error_cents(signal: &Sf64, freq_hz: f64, start: u64) -> f64 {
+        const SAMPLE_RATE_HZ: f64 = 44100.0;
+        const WINDOW_LEN: usize = 4096;
+        // Short enough that the phase changes by less than half a cycle for errors of up to
+        // about a third of a semitone
+        const WINDOW_SPACING: usize = 1024;
+        // Signals must be rendered from the beginning to reach the window
+        let samples = (0..(start + (WINDOW_LEN + WINDOW_SPACING) as u64))
+            .map(|sample_index| {
+                signal.sample(&SignalCtx {
+                    sample_index,
+                    sample_rate_hz: SAMPLE_RATE_HZ,
+                })
+            })
+            .skip(start as usize)
+            .collect::<Vec<_>>();
+        let omega = 2.0 * PI * freq_hz / SAMPLE_RATE_HZ;
+        let phase = |offset: usize| {
+            let (re, im) = (0..WINDOW_LEN).fold((0.0, 0.0), |(re, im), i| {
+                let hann = 0.5 - (0.5 * (2.0 * PI * i as f64 / WINDOW_LEN as f64).cos());
+                let x = hann * samples[offset + i];
+                let angle = omega * i as f64;
+                (re + (x * angle.cos()), im - (x * angle.sin()))
+            });
+            im.atan2(re)
+        };
+        let expected_phase_change = omega * WINDOW_SPACING as f64;
+        let phase_error = (phase(WINDOW_SPACING) - phase(0) - expected_phase_change + PI)
+            .rem_euclid(2.0 * PI)
+            - PI;
+        let measured_omega = omega + (phase_error / WINDOW_SPACING as f64);
+        1200.0 * (measured_omega / omega).log2()
+    }
+
+    #[test]
+    fn test_physical_model_tuning() {
+        use crate::signal::{const_, sfreq_hz};
+        // The bowed string needs time to settle into a stable motion, and the interaction with
+        // the bow makes it slightly sharp, by about a sixth of a sample per period
+        for freq_hz in [130.81, 220.0, 440.0, 659.26] {
+            let models = [
+                (
+                    "pluck",
+                    pluck(
+                        sfreq_hz(freq_hz),
+                        Signal::from_fn(|ctx| ctx.sample_index == 0).to_trigger_raw(),
+                        const_(0.2),
+                        const_(0.5),
+                        const_(0.5),
+                    ),
+                ),
+                (
+                    "bowed string",
+                    bowed_string(
+                        sfreq_hz(freq_hz),
+                        const_(true).to_gate(),
+                        const_(0.5),
+                        const_(0.5),
+                    ),
+                ),
+                (
+                    "blown pipe",
+                    blown_pipe(
+                        sfreq_hz(freq_hz),
+                        const_(true).to_gate(),
+                        const_(0.5),
+                        const_(0.0),
+                    ),
+                ),
+            ];
+            for (name, signal) in models {
+                let error_cents = test_tuning_error_cents(&signal, freq_hz, 44100);
+                assert!(
+                    error_cents.abs() < 5.0,
+                    "{name} at {freq_hz}Hz is {error_cents} cents out of tune"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::cell::RefCell;
+
+// Every source of randomness in the library gets its own generator when its signal is created.
+// When a seed is set, each new generator is seeded from a sequence derived from that seed. Since
+// a patch creates its signals in the same order each time it's built, building it with the same
+// seed produces identical output.
+
+thread_local! {
+    static SEED_SOURCE: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Seed all random number generators created on this thread from now on. Generators which
+/// already exist are unaffected, so set the seed before building the signals of a patch.
+pub fn set_random_seed(seed: u64) {
+    SEED_SOURCE.with(|source| *source.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+}
+
+/// Go back to seeding new random number generators from entropy
+pub fn clear_random_seed() {
+    SEED_SOURCE.with(|source| *source.borrow_mut() = None);
+}
+
+/// Call `f` with the random seed set to `seed`, restoring the previous seed afterwards. Useful
+/// for building a reproducible part of a patch without affecting the rest.
+pub fn with_random_seed<T, F: FnOnce() -> T>(seed: u64, f: F) -> T {
+    let previous =
+        SEED_SOURCE.with(|source| source.borrow_mut().replace(StdRng::seed_from_u64(seed)));
+    let output = f();
+    SEED_SOURCE.with(|source| *source.borrow_mut() = previous);
+    output
+}
+
+/// Create a random number generator, seeded according to the current random seed if one is
+/// set and from entropy otherwise
+pub fn new_rng() -> StdRng {
+    SEED_SOURCE.with(|source| match source.borrow_mut().as_mut() {
+        Some(source) => StdRng::seed_from_u64(source.gen()),
+        None => StdRng::from_entropy(),
+    })
+}
+
+#[test]
+fn test() {
+    use crate::signal::{noise, SignalCtx};
+    // Test that building the same signal with the same seed produces the same output
+    let render = || {
+        let signal = with_random_seed(42, noise);
+        (0..16)
+            .map(|sample_index| {
+                signal.sample(&SignalCtx {
+                    sample_index,
+                    sample_rate_hz: 44100.0,
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(render(), render());
+}
@@ -1,53 +1,328 @@
-use crate::signal::{Sf64, Signal, Trigger};
-use std::cell::Cell;
+use crate::signal::{const_, Gate, Sf64, Signal, SignalCtx, Trigger};
+use std::{cell::RefCell, f64::consts::PI, rc::Rc};
 
+/// Audio data to be played back by a sampler. Cloning a `Sample` shares its data.
 #[derive(Clone)]
 pub struct Sample {
-    samples: Vec<f64>,
+    samples: Rc<[f64]>,
+    sample_rate_hz: Option<f64>,
 }
 
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    #[default]
+    Linear,
+    /// 4-point Catmull-Rom spline
+    Cubic,
+    /// Windowed sinc, which is slower but keeps high frequencies intact and avoids aliasing
+    /// when pitching samples up
+    Sinc,
+}
+
+impl From<Interpolation> for Signal<Interpolation> {
+    fn from(value: Interpolation) -> Self {
+        const_(value)
+    }
+}
+
+/// Number of zero crossings of the sinc function on either side of the centre of the kernel
+const SINC_ZERO_CROSSINGS: f64 = 8.0;
+/// Limits the width of the sinc kernel when it's widened to filter out frequencies which would
+/// alias when pitching up by a large amount
+const SINC_MAX_HALF_WIDTH: f64 = 64.0;
+
 impl Sample {
     pub fn new(samples: Vec<f64>) -> Self {
-        Self { samples }
+        Self {
+            samples: samples.into(),
+            sample_rate_hz: None,
+        }
+    }
+
+    /// Set the sample rate at which the sample was recorded so that it plays at its original
+    /// speed regardless of the output sample rate
+    pub fn with_sample_rate_hz(mut self, sample_rate_hz: f64) -> Self {
+        self.sample_rate_hz = Some(sample_rate_hz);
+        self
+    }
+
+    /// The sample rate at which the sample was recorded, if known. Samples with an unknown
+    /// sample rate are played at one sample per output sample.
+    pub fn sample_rate_hz(&self) -> Option<f64> {
+        self.sample_rate_hz
+    }
+
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
     }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    fn get(&self, index: f64) -> f64 {
+        if index >= 0.0 && (index as usize) < self.samples.len() {
+            self.samples[index as usize]
+        } else {
+            0.0
+        }
+    }
+
+    /// Read the sample at a fractional index, interpolating linearly between neighbouring
+    /// samples. Indices outside the sample read as silence.
+    pub fn read_linear(&self, index: f64) -> f64 {
+        let floor = index.floor();
+        let frac = index - floor;
+        self.get(floor) + ((self.get(floor + 1.0) - self.get(floor)) * frac)
+    }
+
+    /// Read the sample at a fractional index. `speed` is the number of samples advanced per
+    /// output sample, which sinc interpolation uses to filter out frequencies which would
+    /// alias. Indices outside the sample read as silence.
+    pub fn read(&self, index: f64, interpolation: Interpolation, speed: f64) -> f64 {
+        let floor = index.floor();
+        let frac = index - floor;
+        match interpolation {
+            Interpolation::Nearest => self.get(index.round()),
+            Interpolation::Linear => self.read_linear(index),
+            Interpolation::Cubic => {
+                let y0 = self.get(floor - 1.0);
+                let y1 = self.get(floor);
+                let y2 = self.get(floor + 1.0);
+                let y3 = self.get(floor + 2.0);
+                let a = (-0.5 * y0) + (1.5 * y1) - (1.5 * y2) + (0.5 * y3);
+                let b = y0 - (2.5 * y1) + (2.0 * y2) - (0.5 * y3);
+                let c = (-0.5 * y0) + (0.5 * y2);
+                ((((a * frac) + b) * frac) + c) * frac + y1
+            }
+            Interpolation::Sinc => {
+                // Lower the cutoff below the nyquist frequency when pitching up
+                let cutoff = (1.0 / speed.abs().max(f64::EPSILON)).min(1.0);
+                let half_width = (SINC_ZERO_CROSSINGS / cutoff).min(SINC_MAX_HALF_WIDTH);
+                let first = (index - half_width).ceil() as i64;
+                let last = (index + half_width).floor() as i64;
+                let mut output = 0.0;
+                for i in first..=last {
+                    let x = i as f64 - index;
+                    let sinc = if x == 0.0 {
+                        1.0
+                    } else {
+                        (PI * x * cutoff).sin() / (PI * x * cutoff)
+                    };
+                    // Blackman window
+                    let w = 0.5 + (0.5 * x / half_width);
+                    let window =
+                        0.42 - (0.5 * (2.0 * PI * w).cos()) + (0.08 * (4.0 * PI * w).cos());
+                    output += self.get(i as f64) * sinc * window * cutoff;
+                }
+                output
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    #[default]
+    NoLoop,
+    /// Loop for as long as the sample plays
+    Continuous,
+    /// Loop while the gate is held, then play on to the end of the sample
+    Sustain,
+}
+
+/// Region of a sample to repeat while it plays
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SampleLoop {
+    pub mode: LoopMode,
+    /// Index of the first sample in the loop
+    pub start: usize,
+    /// Index of the sample after the last sample in the loop
+    pub end: usize,
+    /// Number of samples over which the end of the loop is faded into the material leading up
+    /// to the start of the loop, hiding the join
+    pub crossfade: usize,
 }
 
 #[derive(Clone)]
 pub struct Sampler {
-    sample: Sample,
-    index: usize,
-    play: Trigger,
+    pub sample: Sample,
+    /// Starts playing the sample from the start
+    pub play: Trigger,
+    /// Sustain loops repeat while this is held
+    pub gate: Gate,
+    /// Playback speed relative to the original speed of the sample
+    pub rate: Sf64,
+    pub interpolation: Signal<Interpolation>,
+    /// Index where playback starts
+    pub start: usize,
+    /// Index where playback stops, or the end of the sample if `None`
+    pub end: Option<usize>,
+    pub sample_loop: SampleLoop,
+    /// Play the sample backwards from `end` to `start`. Loops also run backwards.
+    pub reverse: bool,
 }
 
 impl Sampler {
     pub fn new(sample: &Sample, play: Trigger) -> Self {
-        let index = sample.samples.len(); // this prevents the sample from immediately playing
         Self {
             sample: sample.clone(),
-            index,
             play,
+            gate: Gate::never(),
+            rate: const_(1.0),
+            interpolation: const_(Interpolation::Linear),
+            start: 0,
+            end: None,
+            sample_loop: SampleLoop::default(),
+            reverse: false,
         }
     }
 
     pub fn signal(self) -> Sf64 {
-        let Self {
-            sample,
-            index,
-            play,
-        } = self;
-        let index = Cell::new(index);
+        let playhead = RefCell::new(Playhead::new(
+            &self.sample,
+            self.start,
+            self.end,
+            self.sample_loop,
+            self.reverse,
+        ));
         Signal::from_fn(move |ctx| {
-            if play.sample(ctx) {
-                index.set(0);
+            let mut playhead = playhead.borrow_mut();
+            if self.play.sample(ctx) {
+                playhead.restart();
             }
-            let index_value = index.get();
-            if index_value < sample.samples.len() {
-                let output = sample.samples[index_value];
-                index.set(index_value + 1);
-                output
-            } else {
-                0.0
+            if !playhead.is_playing() {
+                return 0.0;
             }
+            let gate = self.sample_loop.mode == LoopMode::Sustain && self.gate.sample(ctx);
+            playhead.next(
+                self.rate.sample(ctx),
+                gate,
+                self.interpolation.sample(ctx),
+                ctx,
+            )
         })
     }
 }
+
+/// The position of playback within a sample, shared by the samplers which play samples in
+/// different ways
+pub(crate) struct Playhead {
+    sample: Sample,
+    start: usize,
+    end: usize,
+    loop_mode: LoopMode,
+    loop_end: f64,
+    loop_len: f64,
+    crossfade: f64,
+    reverse: bool,
+    /// `None` when not playing
+    position: Option<f64>,
+}
+
+impl Playhead {
+    pub(crate) fn new(
+        sample: &Sample,
+        start: usize,
+        end: Option<usize>,
+        sample_loop: SampleLoop,
+        reverse: bool,
+    ) -> Self {
+        let end = end.unwrap_or(sample.len()).min(sample.len());
+        let start = start.min(end);
+        // Positions are tracked moving forwards from `start` to `end` and then mapped onto the
+        // sample, so that reverse playback is handled by reflecting the positions. A loop
+        // running backwards starts at the reflection of its end.
+        let reflect = |index: usize| (start + end).saturating_sub(index);
+        let (loop_start, loop_end) = if reverse {
+            (
+                reflect(sample_loop.end.min(end)),
+                reflect(sample_loop.start.max(start)),
+            )
+        } else {
+            (sample_loop.start.max(start), sample_loop.end.min(end))
+        };
+        let loop_len = loop_end.saturating_sub(loop_start);
+        // The crossfade reads from before the start of the loop so it can't be longer than
+        // the material available there
+        let crossfade = sample_loop
+            .crossfade
+            .min(loop_start.saturating_sub(start))
+            .min(loop_len);
+        Self {
+            sample: sample.clone(),
+            start,
+            end,
+            loop_mode: sample_loop.mode,
+            loop_end: loop_end as f64,
+            loop_len: loop_len as f64,
+            crossfade: crossfade as f64,
+            reverse,
+            position: None,
+        }
+    }
+
+    pub(crate) fn restart(&mut self) {
+        self.position = Some(self.start as f64);
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Read the sample at the current position and advance by `rate` times the original speed
+    /// of the sample. `gate` keeps sustain loops looping.
+    pub(crate) fn next(
+        &mut self,
+        rate: f64,
+        gate: bool,
+        interpolation: Interpolation,
+        ctx: &SignalCtx,
+    ) -> f64 {
+        let Some(pos) = self.position else {
+            return 0.0;
+        };
+        let looping = self.loop_len > 0.0
+            && match self.loop_mode {
+                LoopMode::NoLoop => false,
+                LoopMode::Continuous => true,
+                LoopMode::Sustain => gate,
+            };
+        let speed = rate.max(0.0)
+            * self
+                .sample
+                .sample_rate_hz
+                .map_or(1.0, |sample_rate_hz| sample_rate_hz / ctx.sample_rate_hz);
+        let read = |pos: f64| {
+            let index = if self.reverse {
+                (self.start + self.end) as f64 - 1.0 - pos
+            } else {
+                pos
+            };
+            self.sample.read(index, interpolation, speed)
+        };
+        let output = if looping && self.crossfade > 0.0 && pos >= self.loop_end - self.crossfade {
+            let t = (pos - (self.loop_end - self.crossfade)) / self.crossfade;
+            (read(pos) * (1.0 - t)) + (read(pos - self.loop_len) * t)
+        } else {
+            read(pos)
+        };
+        let mut next = pos + speed;
+        if looping && pos < self.loop_end {
+            while next >= self.loop_end {
+                next -= self.loop_len;
+            }
+        }
+        self.position = if next < self.end as f64 {
+            Some(next)
+        } else {
+            None
+        };
+        output
+    }
+}
@@ -1,4 +1,4 @@
-use crate::signal::{Sf64, Trigger, Triggerable};
+use crate::signal::{Sf64, Signal, Trigger, Triggerable};
 use std::{cell::RefCell, rc::Rc};
 
 pub struct SequencedTriggers {
@@ -6,18 +6,10 @@ pub struct SequencedTriggers {
     pub complete: Trigger,
 }
 
-pub fn bitwise_pattern_triggers_8(trigger: Trigger, pattern: Vec<u8>) -> SequencedTriggers {
-    let i = Rc::new(RefCell::new(0));
-    let pattern_signal = trigger.on({
-        let i = Rc::clone(&i);
-        move || {
-            let mut i = i.borrow_mut();
-            let out = pattern[*i];
-            *i = (*i + 1) % pattern.len();
-            out
-        }
-    });
-    let triggers = (0..8)
+/// A trigger for each bit of the pattern entries produced by `pattern_signal`. Trigger `i` fires
+/// on samples where the entry has bit `i` set.
+fn bitwise_entry_triggers_8(pattern_signal: &Signal<Option<u8>>) -> Vec<Trigger> {
+    (0..8)
         .map(|i| {
             pattern_signal
                 .map(move |pattern_entry| {
@@ -29,7 +21,21 @@ pub fn bitwise_pattern_triggers_8(trigger: Trigger, pattern: Vec<u8>) -> Sequenc
                 })
                 .to_trigger_raw()
         })
-        .collect();
+        .collect()
+}
+
+pub fn bitwise_pattern_triggers_8(trigger: Trigger, pattern: Vec<u8>) -> SequencedTriggers {
+    let i = Rc::new(RefCell::new(0));
+    let pattern_signal = trigger.on({
+        let i = Rc::clone(&i);
+        move || {
+            let mut i = i.borrow_mut();
+            let out = pattern[*i];
+            *i = (*i + 1) % pattern.len();
+            out
+        }
+    });
+    let triggers = bitwise_entry_triggers_8(&pattern_signal);
     let complete = pattern_signal
         .map({
             let i = Rc::clone(&i);
@@ -47,3 +53,190 @@ pub fn drum_loop_8(trigger: Trigger, pattern: Vec<u8>, drums: Vec<Triggerable<f6
         .map(|(trigger, drum)| drum.signal(trigger))
         .sum()
 }
+
+/// A named pattern of bitwise step entries in the format used by `bitwise_pattern_triggers_8`.
+/// Patterns are referred to by name from song entries.
+#[derive(Debug, Clone)]
+pub struct ArrangementPattern {
+    pub name: String,
+    pub steps: Vec<u8>,
+}
+
+/// Play the named pattern this many times before moving on to the next entry in the song.
+#[derive(Debug, Clone)]
+pub struct SongEntry {
+    pub pattern: String,
+    pub repeats: usize,
+}
+
+/// When `trigger` fires, playback will jump to the entry in the song at `song_index` at the next
+/// bar boundary.
+#[derive(Clone)]
+pub struct Scene {
+    pub trigger: Trigger,
+    pub song_index: usize,
+}
+
+/// Chains named patterns into a song. Each tick of `step` advances playback by one step of the
+/// current pattern. The song loops back to the start after its final entry, at which point the
+/// `complete` trigger fires.
+pub struct Arrangement {
+    pub step: Trigger,
+    pub steps_per_bar: usize,
+    pub patterns: Vec<ArrangementPattern>,
+    pub song: Vec<SongEntry>,
+    pub scenes: Vec<Scene>,
+}
+
+impl Arrangement {
+    pub fn sequenced_triggers(self) -> SequencedTriggers {
+        let Self {
+            step,
+            steps_per_bar,
+            patterns,
+            song,
+            scenes,
+        } = self;
+        assert!(!song.is_empty(), "song may not be empty");
+        assert!(steps_per_bar > 0, "steps_per_bar must be positive");
+        // Resolve pattern names to indices up front so that missing patterns are reported when
+        // the arrangement is built rather than during playback.
+        let song = song
+            .into_iter()
+            .map(|entry| {
+                let pattern_index = patterns
+                    .iter()
+                    .position(|pattern| pattern.name == entry.pattern)
+                    .unwrap_or_else(|| panic!("no pattern named {:?}", entry.pattern));
+                assert!(
+                    !patterns[pattern_index].steps.is_empty(),
+                    "pattern {:?} may not be empty",
+                    entry.pattern
+                );
+                (pattern_index, entry.repeats.max(1))
+            })
+            .collect::<Vec<_>>();
+        for scene in &scenes {
+            assert!(
+                scene.song_index < song.len(),
+                "scene song index {} is out of range (there are {} song entries)",
+                scene.song_index,
+                song.len()
+            );
+        }
+        struct State {
+            song_index: usize,
+            repeat: usize,
+            step_index: usize,
+            queued_song_index: Option<usize>,
+            complete: bool,
+        }
+        let state = Rc::new(RefCell::new(State {
+            song_index: 0,
+            repeat: 0,
+            step_index: 0,
+            queued_song_index: None,
+            complete: false,
+        }));
+        let pattern_signal = Signal::from_fn({
+            let state = Rc::clone(&state);
+            move |ctx| {
+                let mut state = state.borrow_mut();
+                for scene in &scenes {
+                    if scene.trigger.sample(ctx) {
+                        state.queued_song_index = Some(scene.song_index);
+                    }
+                }
+                state.complete = false;
+                if !step.sample(ctx) {
+                    return None;
+                }
+                let step_in_bar = state.step_index % steps_per_bar;
+                if step_in_bar == 0 {
+                    if let Some(song_index) = state.queued_song_index.take() {
+                        state.song_index = song_index;
+                        state.repeat = 0;
+                        state.step_index = 0;
+                    }
+                }
+                let (pattern_index, repeats) = song[state.song_index];
+                let steps = &patterns[pattern_index].steps;
+                let out = steps[state.step_index];
+                state.step_index += 1;
+                if state.step_index >= steps.len() {
+                    state.step_index = 0;
+                    state.repeat += 1;
+                    if state.repeat >= repeats {
+                        state.repeat = 0;
+                        state.song_index = (state.song_index + 1) % song.len();
+                        state.complete = state.song_index == 0;
+                    }
+                }
+                Some(out)
+            }
+        });
+        let triggers = bitwise_entry_triggers_8(&pattern_signal);
+        let complete = pattern_signal
+            .map({
+                let state = Rc::clone(&state);
+                move |_| state.borrow().complete
+            })
+            .to_trigger_raw();
+        SequencedTriggers { triggers, complete }
+    }
+}
+
+#[test]
+fn test_arrangement() {
+    use crate::signal::{const_, SignalCtx};
+    let scene_trigger = Signal::from_fn(|ctx| ctx.sample_index == 9).to_trigger_raw();
+    let SequencedTriggers { triggers, complete } = Arrangement {
+        step: const_(true).to_trigger_raw(),
+        steps_per_bar: 2,
+        patterns: vec![
+            ArrangementPattern {
+                name: "a".to_string(),
+                steps: vec![1, 1, 1, 1],
+            },
+            ArrangementPattern {
+                name: "b".to_string(),
+                steps: vec![2, 2],
+            },
+        ],
+        song: vec![
+            SongEntry {
+                pattern: "a".to_string(),
+                repeats: 1,
+            },
+            SongEntry {
+                pattern: "b".to_string(),
+                repeats: 2,
+            },
+        ],
+        scenes: vec![Scene {
+            trigger: scene_trigger,
+            song_index: 1,
+        }],
+    }
+    .sequenced_triggers();
+    let mut played = String::new();
+    let mut complete_sample_indices = Vec::new();
+    for sample_index in 0..16 {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 44100.0,
+        };
+        match (triggers[0].sample(&ctx), triggers[1].sample(&ctx)) {
+            (true, false) => played.push('a'),
+            (false, true) => played.push('b'),
+            _ => played.push('-'),
+        }
+        if complete.sample(&ctx) {
+            complete_sample_indices.push(sample_index);
+        }
+    }
+    // Pattern "b" repeats twice, then the song wraps around to "a". The scene is queued partway
+    // through a bar and takes effect at the start of the next bar.
+    assert_eq!(played, "aaaabbbbaabbbbaa");
+    assert_eq!(complete_sample_indices, vec![7, 13]);
+}
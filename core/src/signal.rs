@@ -1,4 +1,5 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::random;
+use rand::Rng;
 use std::{
     cell::{Cell, RefCell},
     rc::Rc,
@@ -54,12 +55,12 @@ pub fn sfreq_to_s(sfreq: impl Into<Sfreq>) -> Sf64 {
 }
 
 pub fn noise() -> Sf64 {
-    let rng = RefCell::new(StdRng::from_entropy());
+    let rng = RefCell::new(random::new_rng());
     Signal::from_fn(move |_ctx| (rng.borrow_mut().gen::<f64>() * 2.0) - 1.0)
 }
 
 pub fn noise_01() -> Sf64 {
-    let rng = RefCell::new(StdRng::from_entropy());
+    let rng = RefCell::new(random::new_rng());
     Signal::from_fn(move |_ctx| (rng.borrow_mut().gen::<f64>()))
 }
 
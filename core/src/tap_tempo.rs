@@ -0,0 +1,204 @@
+use crate::signal::{Sf64, Signal, Trigger};
+use std::{cell::RefCell, collections::VecDeque};
+
+pub struct TapTempo {
+    pub tap: Trigger,
+    /// Tempo to use until enough taps have been received to estimate one
+    pub initial_bpm: f64,
+    /// If no tap is received for this long, the next tap starts a new measurement
+    pub timeout_s: Sf64,
+    /// Intervals differing from the current estimate by more than this fraction of the estimate
+    /// are treated as mistakes and ignored
+    pub outlier_tolerance_01: Sf64,
+    /// Number of recent intervals to average when estimating the tempo
+    pub history_length: usize,
+}
+
+/// The result of tapping a tempo
+#[derive(Clone)]
+pub struct TapTempoOutput {
+    pub bpm: Sf64,
+    /// Pulses once per beat. Each accepted tap realigns the phase of the clock with the tap.
+    pub clock: Trigger,
+}
+
+#[derive(Default, Clone, Copy)]
+struct TapTempoFrame {
+    bpm: f64,
+    clock: bool,
+}
+
+struct TapTempoState {
+    samples_since_last_tap: Option<u64>,
+    intervals_samples: VecDeque<u64>,
+    consecutive_outliers: usize,
+    period_samples: Option<f64>,
+    phase_samples: f64,
+}
+
+impl TapTempoState {
+    /// Once this many consecutive taps are rejected as outliers assume the performer has
+    /// intentionally changed tempo and start measuring again.
+    const MAX_CONSECUTIVE_OUTLIERS: usize = 2;
+
+    fn estimate_period_samples(&self) -> Option<f64> {
+        if self.intervals_samples.is_empty() {
+            None
+        } else {
+            let total: u64 = self.intervals_samples.iter().sum();
+            Some(total as f64 / self.intervals_samples.len() as f64)
+        }
+    }
+
+    /// Returns true if the tap was accepted, in which case the clock should be realigned
+    fn handle_tap(
+        &mut self,
+        timeout_samples: f64,
+        outlier_tolerance_01: f64,
+        history_length: usize,
+    ) -> bool {
+        let samples_since_last_tap = self.samples_since_last_tap.replace(0);
+        let Some(interval_samples) = samples_since_last_tap else {
+            return false;
+        };
+        if interval_samples as f64 > timeout_samples || interval_samples == 0 {
+            self.intervals_samples.clear();
+            self.consecutive_outliers = 0;
+            return false;
+        }
+        if let Some(estimate) = self.estimate_period_samples() {
+            let delta = (interval_samples as f64 - estimate).abs();
+            if delta > estimate * outlier_tolerance_01 {
+                self.consecutive_outliers += 1;
+                if self.consecutive_outliers < Self::MAX_CONSECUTIVE_OUTLIERS {
+                    return false;
+                }
+                self.intervals_samples.clear();
+            }
+        }
+        self.consecutive_outliers = 0;
+        self.intervals_samples.push_back(interval_samples);
+        while self.intervals_samples.len() > history_length.max(1) {
+            self.intervals_samples.pop_front();
+        }
+        self.period_samples = self.estimate_period_samples();
+        true
+    }
+}
+
+impl TapTempo {
+    pub fn output(self) -> TapTempoOutput {
+        let Self {
+            tap,
+            initial_bpm,
+            timeout_s,
+            outlier_tolerance_01,
+            history_length,
+        } = self;
+        let state = RefCell::new(TapTempoState {
+            samples_since_last_tap: None,
+            intervals_samples: VecDeque::new(),
+            consecutive_outliers: 0,
+            period_samples: None,
+            phase_samples: 0.0,
+        });
+        let frame = Signal::from_fn(move |ctx| {
+            let mut state = state.borrow_mut();
+            if let Some(samples) = state.samples_since_last_tap.as_mut() {
+                *samples += 1;
+            }
+            let period_samples = state
+                .period_samples
+                .unwrap_or_else(|| (60.0 * ctx.sample_rate_hz) / initial_bpm);
+            let mut clock = false;
+            state.phase_samples += 1.0;
+            if state.phase_samples >= period_samples {
+                state.phase_samples -= period_samples;
+                clock = true;
+            }
+            if tap.sample(ctx) {
+                let accepted = state.handle_tap(
+                    timeout_s.sample(ctx) * ctx.sample_rate_hz,
+                    outlier_tolerance_01.sample(ctx),
+                    history_length,
+                );
+                if accepted {
+                    // If the clock pulsed recently then the tap was slightly late so just
+                    // realign the phase rather than pulsing a second time.
+                    if state.phase_samples >= period_samples / 2.0 {
+                        clock = true;
+                    }
+                    state.phase_samples = 0.0;
+                }
+            }
+            let period_samples = state
+                .period_samples
+                .unwrap_or_else(|| (60.0 * ctx.sample_rate_hz) / initial_bpm);
+            TapTempoFrame {
+                bpm: (60.0 * ctx.sample_rate_hz) / period_samples,
+                clock,
+            }
+        });
+        TapTempoOutput {
+            bpm: frame.map(|frame| frame.bpm),
+            clock: frame.map(|frame| frame.clock).to_trigger_raw(),
+        }
+    }
+}
+
+#[test]
+fn test_tap_tempo_outlier_rejection() {
+    use crate::signal::SignalCtx;
+    let taps = [0, 500, 1000, 1500, 2200, 2700, 3000, 3300];
+    let output = TapTempo {
+        tap: Signal::from_fn(move |ctx| taps.contains(&ctx.sample_index)).to_trigger_raw(),
+        initial_bpm: 60.0,
+        timeout_s: Signal::from(2.0),
+        outlier_tolerance_01: Signal::from(0.3),
+        history_length: 4,
+    }
+    .output();
+    let mut bpms = Vec::new();
+    for sample_index in 0..3500 {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        bpms.push(output.bpm.sample(&ctx));
+    }
+    assert_eq!(bpms[499], 60.0);
+    assert_eq!(bpms[1500], 120.0);
+    // A single tap that's far off the tempo is ignored
+    assert_eq!(bpms[2200], 120.0);
+    assert_eq!(bpms[2700], 120.0);
+    assert_eq!(bpms[3000], 120.0);
+    // Consecutive taps at a new tempo replace the old tempo
+    assert_eq!(bpms[3300], 200.0);
+}
+
+#[test]
+fn test_tap_tempo_phase_alignment() {
+    use crate::signal::SignalCtx;
+    let taps = [0, 500, 1000, 1600];
+    let output = TapTempo {
+        tap: Signal::from_fn(move |ctx| taps.contains(&ctx.sample_index)).to_trigger_raw(),
+        initial_bpm: 60.0,
+        timeout_s: Signal::from(2.0),
+        outlier_tolerance_01: Signal::from(0.3),
+        history_length: 1,
+    }
+    .output();
+    let mut clock_pulses = Vec::new();
+    for sample_index in 0..2500 {
+        let ctx = SignalCtx {
+            sample_index,
+            sample_rate_hz: 1000.0,
+        };
+        if output.clock.sample(&ctx) {
+            clock_pulses.push(sample_index);
+        }
+    }
+    // The clock pulses on each accepted tap. A tap shortly after a pulse realigns the clock
+    // without pulsing twice.
+    assert_eq!(clock_pulses, vec![500, 1000, 1500, 2200]);
+}
@@ -1,5 +1,8 @@
-use crate::signal::{Sf64, Signal, Trigger};
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::{
+    random,
+    signal::{Sf64, Signal, Trigger},
+};
+use rand::Rng;
 use std::{
     cell::{Cell, RefCell},
     rc::Rc,
@@ -17,7 +20,7 @@ pub fn bitwise_trigger_router_64(input: Trigger, selection: Signal<u64>) -> Vec<
 }
 
 pub fn weighted_random_choice<T: Copy + Default + 'static>(choices: Vec<(T, Sf64)>) -> Signal<T> {
-    let rng = RefCell::new(StdRng::from_entropy());
+    let rng = RefCell::new(random::new_rng());
     Signal::from_fn(move |ctx| {
         let mut total = 0.0;
         for (_, weight) in &choices {
@@ -1,13 +1,22 @@
 pub use currawong_core::{clock, envelope, filters, music, oscillator, signal};
+pub mod looper;
 #[cfg(feature = "midi")]
 pub mod midi;
 pub mod sample;
 pub mod sample_player;
+pub mod sf2;
+pub mod sfz;
 pub mod signal_player;
 pub mod prelude {
+    pub use crate::looper::{
+        load_midi_note_sequence, load_trigger_sequence, save_midi_note_sequence,
+        save_trigger_sequence,
+    };
     #[cfg(feature = "midi")]
     pub use crate::midi::{MidiFile, MidiLive, MidiLiveSerial};
     pub use crate::sample::read_wav;
+    pub use crate::sf2::{read_sf2, SoundFont, SoundFontPreset};
+    pub use crate::sfz::read_sfz;
     pub use crate::signal_player::SignalPlayer;
     pub use currawong_core::prelude::*;
 }
@@ -0,0 +1,332 @@
+use currawong_core::loopers::{LooperSequence, MidiNoteLooperEntry};
+use std::{fs, path::Path};
+
+// Sequences are saved as plain text with one step per line so that they can be inspected and
+// edited by hand.
+
+fn parse_flag(s: &str) -> anyhow::Result<bool> {
+    match s {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        other => anyhow::bail!("Expected 0 or 1 but got {:?}", other),
+    }
+}
+
+fn flag(b: bool) -> &'static str {
+    if b {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+fn non_empty_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+/// Save the sequence of a `ClockedTriggerLooper` to a file with one line per step, containing
+/// "1" if the step is set and "0" otherwise.
+pub fn save_trigger_sequence(
+    path: impl AsRef<Path>,
+    sequence: &LooperSequence<bool>,
+) -> anyhow::Result<()> {
+    let contents = sequence
+        .entries()
+        .into_iter()
+        .map(|entry| format!("{}\n", flag(entry)))
+        .collect::<String>();
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn load_trigger_sequence(path: impl AsRef<Path>) -> anyhow::Result<LooperSequence<bool>> {
+    let contents = fs::read_to_string(path)?;
+    let entries = non_empty_lines(&contents)
+        .map(|(line_number, line)| {
+            parse_flag(line).map_err(|e| anyhow::anyhow!("Line {}: {}", line_number, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if entries.is_empty() {
+        anyhow::bail!("Sequence is empty");
+    }
+    Ok(LooperSequence::new(entries))
+}
+
+/// Save the sequence of a `ClockedMidiNoteMonophonicLooper` to a file with one line per step.
+/// Each line contains the midi index, followed by the key down and key up flags (each "0" or
+/// "1"), separated by spaces.
+pub fn save_midi_note_sequence(
+    path: impl AsRef<Path>,
+    sequence: &LooperSequence<MidiNoteLooperEntry>,
+) -> anyhow::Result<()> {
+    let contents = sequence
+        .entries()
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "{} {} {}\n",
+                entry.midi_index,
+                flag(entry.key_down),
+                flag(entry.key_up)
+            )
+        })
+        .collect::<String>();
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn load_midi_note_sequence(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<LooperSequence<MidiNoteLooperEntry>> {
+    let contents = fs::read_to_string(path)?;
+    let parse_line = |line: &str| -> anyhow::Result<MidiNoteLooperEntry> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let [midi_index, key_down, key_up] = fields.as_slice() else {
+            anyhow::bail!("Expected 3 fields but got {}", fields.len());
+        };
+        let midi_index = midi_index.parse::<u8>()?;
+        if midi_index > 127 {
+            anyhow::bail!("Midi index {} is out of range", midi_index);
+        }
+        Ok(MidiNoteLooperEntry {
+            midi_index,
+            key_down: parse_flag(key_down)?,
+            key_up: parse_flag(key_up)?,
+        })
+    };
+    let entries = non_empty_lines(&contents)
+        .map(|(line_number, line)| {
+            parse_line(line).map_err(|e| anyhow::anyhow!("Line {}: {}", line_number, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if entries.is_empty() {
+        anyhow::bail!("Sequence is empty");
+    }
+    Ok(LooperSequence::new(entries))
+}
+
+#[cfg(feature = "midi")]
+pub mod smf {
+    use super::*;
+    use midly::{
+        num::{u15, u28, u4, u7},
+        Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+    };
+
+    /// Collects note events at absolute tick times and converts them into a track
+    struct TrackBuilder {
+        channel: u4,
+        /// (tick, midi index, pressed)
+        events: Vec<(u32, u8, bool)>,
+    }
+
+    impl TrackBuilder {
+        fn new(channel: u8) -> anyhow::Result<Self> {
+            let Some(channel) = u4::try_from(channel) else {
+                anyhow::bail!("Midi channel {} is out of range", channel);
+            };
+            Ok(Self {
+                channel,
+                events: Vec::new(),
+            })
+        }
+
+        fn note(&mut self, tick: u32, midi_index: u8, pressed: bool) {
+            self.events.push((tick, midi_index, pressed));
+        }
+
+        fn save(mut self, path: impl AsRef<Path>, ticks_per_beat: u16) -> anyhow::Result<()> {
+            // Stable sort so that events at the same tick keep the order they were added in
+            self.events.sort_by_key(|&(tick, _, _)| tick);
+            let mut track = Vec::new();
+            let mut prev_tick = 0;
+            for (tick, midi_index, pressed) in self.events {
+                let Some(key) = u7::try_from(midi_index) else {
+                    anyhow::bail!("Midi index {} is out of range", midi_index);
+                };
+                let message = if pressed {
+                    MidiMessage::NoteOn {
+                        key,
+                        vel: u7::new(127),
+                    }
+                } else {
+                    MidiMessage::NoteOff {
+                        key,
+                        vel: u7::new(0),
+                    }
+                };
+                track.push(TrackEvent {
+                    delta: u28::try_from(tick - prev_tick)
+                        .ok_or_else(|| anyhow::anyhow!("Tick {} is out of range", tick))?,
+                    kind: TrackEventKind::Midi {
+                        channel: self.channel,
+                        message,
+                    },
+                });
+                prev_tick = tick;
+            }
+            track.push(TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            });
+            let Some(ticks_per_beat) = u15::try_from(ticks_per_beat) else {
+                anyhow::bail!("{} ticks per beat is out of range", ticks_per_beat);
+            };
+            let mut smf = Smf::new(Header::new(
+                Format::SingleTrack,
+                Timing::Metrical(ticks_per_beat),
+            ));
+            smf.tracks.push(track);
+            smf.save(path)?;
+            Ok(())
+        }
+    }
+
+    fn ticks_per_beat(ticks_per_step: u16, steps_per_beat: u16) -> anyhow::Result<u16> {
+        ticks_per_step.checked_mul(steps_per_beat).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} ticks per step with {} steps per beat is too many ticks per beat",
+                ticks_per_step,
+                steps_per_beat
+            )
+        })
+    }
+
+    /// Export the sequence of a `ClockedTriggerLooper` as a Standard MIDI File. Each set step
+    /// becomes a note with the given midi index lasting half a step.
+    pub fn export_trigger_sequence(
+        path: impl AsRef<Path>,
+        sequence: &LooperSequence<bool>,
+        midi_index: u8,
+        channel: u8,
+        ticks_per_step: u16,
+        steps_per_beat: u16,
+    ) -> anyhow::Result<()> {
+        let ticks_per_beat = ticks_per_beat(ticks_per_step, steps_per_beat)?;
+        let ticks_per_step = ticks_per_step as u32;
+        let mut builder = TrackBuilder::new(channel)?;
+        for (i, entry) in sequence.entries().into_iter().enumerate() {
+            if entry {
+                let tick = i as u32 * ticks_per_step;
+                builder.note(tick, midi_index, true);
+                builder.note(tick + (ticks_per_step / 2).max(1), midi_index, false);
+            }
+        }
+        builder.save(path, ticks_per_beat)
+    }
+
+    /// Export the sequence of a `ClockedMidiNoteMonophonicLooper` as a Standard MIDI File,
+    /// reproducing the way the looper plays back held and tapped notes.
+    pub fn export_midi_note_sequence(
+        path: impl AsRef<Path>,
+        sequence: &LooperSequence<MidiNoteLooperEntry>,
+        channel: u8,
+        ticks_per_step: u16,
+        steps_per_beat: u16,
+    ) -> anyhow::Result<()> {
+        let ticks_per_beat = ticks_per_beat(ticks_per_step, steps_per_beat)?;
+        let ticks_per_step = ticks_per_step as u32;
+        let mut builder = TrackBuilder::new(channel)?;
+        let mut current_note = None;
+        let entries = sequence.entries();
+        for (i, entry) in entries.iter().enumerate() {
+            let tick = i as u32 * ticks_per_step;
+            if entry.key_down {
+                if current_note != Some(entry.midi_index) {
+                    if let Some(midi_index) = current_note {
+                        builder.note(tick, midi_index, false);
+                    }
+                    builder.note(tick, entry.midi_index, true);
+                }
+                current_note = Some(entry.midi_index);
+                if entry.key_up {
+                    builder.note(tick + (ticks_per_step / 2).max(1), entry.midi_index, false);
+                    current_note = None;
+                }
+            } else if let Some(midi_index) = current_note.take() {
+                builder.note(tick, midi_index, false);
+            }
+        }
+        if let Some(midi_index) = current_note {
+            builder.note(entries.len() as u32 * ticks_per_step, midi_index, false);
+        }
+        builder.save(path, ticks_per_beat)
+    }
+
+    #[test]
+    fn test_export_midi_note_sequence() {
+        let held = MidiNoteLooperEntry {
+            midi_index: 60,
+            key_down: true,
+            key_up: false,
+        };
+        let tapped = MidiNoteLooperEntry {
+            midi_index: 62,
+            key_down: true,
+            key_up: true,
+        };
+        let sequence =
+            LooperSequence::new(vec![held, held, tapped, MidiNoteLooperEntry::default()]);
+        let path = std::env::temp_dir().join("currawong_test_export_midi_note_sequence.mid");
+        export_midi_note_sequence(&path, &sequence, 0, 4, 4).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.header.timing, Timing::Metrical(u15::new(16)));
+        let notes = smf.tracks[0]
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { key, .. },
+                    ..
+                } => Some((event.delta.as_int(), key.as_int(), true)),
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOff { key, .. },
+                    ..
+                } => Some((event.delta.as_int(), key.as_int(), false)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // The held note lasts two steps and the tapped note half a step
+        assert_eq!(
+            notes,
+            vec![(0, 60, true), (8, 60, false), (0, 62, true), (2, 62, false)]
+        );
+    }
+}
+
+#[test]
+fn test_trigger_sequence_round_trip() {
+    let sequence = LooperSequence::new(vec![true, false, false, true, true]);
+    let path = std::env::temp_dir().join("currawong_test_trigger_sequence_round_trip.txt");
+    save_trigger_sequence(&path, &sequence).unwrap();
+    let loaded = load_trigger_sequence(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap().entries(), sequence.entries());
+}
+
+#[test]
+fn test_midi_note_sequence_round_trip() {
+    let sequence = LooperSequence::new(vec![
+        MidiNoteLooperEntry {
+            midi_index: 60,
+            key_down: true,
+            key_up: false,
+        },
+        MidiNoteLooperEntry {
+            midi_index: 127,
+            key_down: true,
+            key_up: true,
+        },
+        MidiNoteLooperEntry::default(),
+    ]);
+    let path = std::env::temp_dir().join("currawong_test_midi_note_sequence_round_trip.txt");
+    save_midi_note_sequence(&path, &sequence).unwrap();
+    let loaded = load_midi_note_sequence(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap().entries(), sequence.entries());
+}
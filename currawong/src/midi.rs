@@ -197,30 +197,42 @@ impl MidiLiveSerial {
     }
 }
 
+/// Messages sent from the live midi thread
+enum LiveMessage {
+    Event(MidiEvent),
+    Realtime(MidiRealtimeMessage),
+}
+
 struct MidirMidiInputEventSource {
     #[allow(unused)]
     midi_input_connection: MidiInputConnection<()>,
-    midi_event_receiver: mpsc::Receiver<MidiEvent>,
+    live_message_receiver: mpsc::Receiver<LiveMessage>,
+    /// Realtime messages received while draining the channel for midi events. These are passed
+    /// on by `for_each_new_realtime_message`.
+    pending_realtime_messages: Vec<MidiRealtimeMessage>,
 }
 
 impl MidirMidiInputEventSource {
     fn new(midi_input: MidiInput, port: &MidiInputPort) -> anyhow::Result<Self> {
         let port_name = format!("currawong {}", midi_input.port_name(port)?);
-        let (midi_event_sender, midi_event_receiver) = mpsc::channel::<MidiEvent>();
+        let (live_message_sender, live_message_receiver) = mpsc::channel::<LiveMessage>();
         let midi_input_connection = midi_input
             .connect(
                 port,
                 port_name.as_str(),
                 move |_timestamp_us, message, &mut ()| {
                     if let Ok(event) = LiveEvent::parse(message) {
-                        match event {
+                        let live_message = match event {
                             LiveEvent::Midi { channel, message } => {
-                                let midi_event = MidiEvent { channel, message };
-                                if let Err(_) = midi_event_sender.send(midi_event) {
-                                    log::error!("failed to send message from live midi thread");
-                                }
+                                LiveMessage::Event(MidiEvent { channel, message })
                             }
-                            _ => (),
+                            other => match MidiRealtimeMessage::from_live_event(&other) {
+                                Some(realtime_message) => LiveMessage::Realtime(realtime_message),
+                                None => return,
+                            },
+                        };
+                        if let Err(_) = live_message_sender.send(live_message) {
+                            log::error!("failed to send message from live midi thread");
                         }
                     }
                 },
@@ -229,17 +241,32 @@ impl MidirMidiInputEventSource {
             .map_err(|_| anyhow::anyhow!("Failed to connect to midi port"))?;
         Ok(Self {
             midi_input_connection,
-            midi_event_receiver,
+            live_message_receiver,
+            pending_realtime_messages: Vec::new(),
         })
     }
 }
 
 impl MidiEventSource for MidirMidiInputEventSource {
-    fn for_each_new_event<F>(&mut self, _ctx: &SignalCtx, f: F)
+    fn for_each_new_event<F>(&mut self, _ctx: &SignalCtx, mut f: F)
     where
         F: FnMut(MidiEvent),
     {
-        self.midi_event_receiver.try_iter().for_each(f)
+        for live_message in self.live_message_receiver.try_iter() {
+            match live_message {
+                LiveMessage::Event(midi_event) => f(midi_event),
+                LiveMessage::Realtime(realtime_message) => {
+                    self.pending_realtime_messages.push(realtime_message)
+                }
+            }
+        }
+    }
+
+    fn for_each_new_realtime_message<F>(&mut self, _ctx: &SignalCtx, f: F)
+    where
+        F: FnMut(MidiRealtimeMessage),
+    {
+        self.pending_realtime_messages.drain(..).for_each(f)
     }
 }
 
@@ -247,6 +274,7 @@ struct MidiLiveSerialEventSource {
     midi_live_serial: MidiLiveSerial,
     buf: Vec<u8>,
     message_buf: Vec<u8>,
+    pending_realtime_messages: Vec<MidiRealtimeMessage>,
 }
 
 impl MidiLiveSerialEventSource {
@@ -255,12 +283,60 @@ impl MidiLiveSerialEventSource {
             midi_live_serial,
             buf: Vec::new(),
             message_buf: Vec::new(),
+            pending_realtime_messages: Vec::new(),
+        }
+    }
+}
+
+/// Parse a stream of bytes read from a serial midi device, calling `f` on each midi event.
+/// A message ends at the next status byte or at the end of the stream.
+fn parse_serial_midi_bytes<F>(
+    bytes: impl IntoIterator<Item = u8>,
+    message_buf: &mut Vec<u8>,
+    realtime_messages: &mut Vec<MidiRealtimeMessage>,
+    mut f: F,
+) where
+    F: FnMut(MidiEvent),
+{
+    let mut flush = |message_buf: &mut Vec<u8>,
+                     realtime_messages: &mut Vec<MidiRealtimeMessage>| {
+        if message_buf.is_empty() {
+            return;
+        }
+        if let Ok(event) = LiveEvent::parse(message_buf) {
+            match event {
+                LiveEvent::Midi { channel, message } => f(MidiEvent { channel, message }),
+                other => {
+                    if let Some(realtime_message) = MidiRealtimeMessage::from_live_event(&other) {
+                        realtime_messages.push(realtime_message);
+                    }
+                }
+            }
+        }
+        message_buf.clear();
+    };
+    message_buf.clear();
+    for byte in bytes {
+        if byte >= 0xF8 {
+            // System realtime messages are a single byte and may be interleaved with the bytes
+            // of other messages, so handle them without disturbing the message buffer.
+            if let Ok(event) = LiveEvent::parse(&[byte]) {
+                if let Some(realtime_message) = MidiRealtimeMessage::from_live_event(&event) {
+                    realtime_messages.push(realtime_message);
+                }
+            }
+            continue;
+        }
+        if byte > 127 {
+            flush(message_buf, realtime_messages);
         }
+        message_buf.push(byte);
     }
+    flush(message_buf, realtime_messages);
 }
 
 impl MidiEventSource for MidiLiveSerialEventSource {
-    fn for_each_new_event<F>(&mut self, _ctx: &SignalCtx, mut f: F)
+    fn for_each_new_event<F>(&mut self, _ctx: &SignalCtx, f: F)
     where
         F: FnMut(MidiEvent),
     {
@@ -278,22 +354,40 @@ impl MidiEventSource for MidiLiveSerialEventSource {
                 }
                 drain.next();
             }
-            self.message_buf.clear();
-            while let Some(byte) = drain.next() {
-                self.message_buf.push(byte);
-                if let Some(&next_byte) = drain.peek() {
-                    if next_byte <= 127 {
-                        continue;
-                    }
-                }
-                if let Ok(event) = LiveEvent::parse(&self.message_buf) {
-                    match event {
-                        LiveEvent::Midi { channel, message } => f(MidiEvent { channel, message }),
-                        _ => (),
-                    }
-                }
-                self.message_buf.clear();
-            }
+            parse_serial_midi_bytes(
+                drain,
+                &mut self.message_buf,
+                &mut self.pending_realtime_messages,
+                f,
+            );
         }
     }
+
+    fn for_each_new_realtime_message<F>(&mut self, _ctx: &SignalCtx, f: F)
+    where
+        F: FnMut(MidiRealtimeMessage),
+    {
+        self.pending_realtime_messages.drain(..).for_each(f)
+    }
+}
+
+#[test]
+fn test_parse_serial_midi_bytes_with_interleaved_clock() {
+    let mut events = Vec::new();
+    let mut realtime_messages = Vec::new();
+    parse_serial_midi_bytes(
+        [0x90, 0x3C, 0xF8, 0x64],
+        &mut Vec::new(),
+        &mut realtime_messages,
+        |event| events.push(event),
+    );
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        events[0].message,
+        MidiMessage::NoteOn { key, vel } if key.as_int() == 0x3C && vel.as_int() == 0x64
+    ));
+    assert!(matches!(
+        realtime_messages.as_slice(),
+        [MidiRealtimeMessage::TimingClock]
+    ));
 }
@@ -0,0 +1,621 @@
+use currawong_core::{
+    multisample::{MultiSampleInstrument, SampleRegion},
+    sampler::{LoopMode, Sample, SampleLoop},
+};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Generator operators from the SoundFont 2 specification which affect playback
+mod generator {
+    pub const START_ADDRS_OFFSET: usize = 0;
+    pub const END_ADDRS_OFFSET: usize = 1;
+    pub const STARTLOOP_ADDRS_OFFSET: usize = 2;
+    pub const ENDLOOP_ADDRS_OFFSET: usize = 3;
+    pub const START_ADDRS_COARSE_OFFSET: usize = 4;
+    pub const END_ADDRS_COARSE_OFFSET: usize = 12;
+    pub const ATTACK_VOL_ENV: usize = 34;
+    pub const HOLD_VOL_ENV: usize = 35;
+    pub const DECAY_VOL_ENV: usize = 36;
+    pub const SUSTAIN_VOL_ENV: usize = 37;
+    pub const RELEASE_VOL_ENV: usize = 38;
+    pub const INSTRUMENT: usize = 41;
+    pub const KEY_RANGE: usize = 43;
+    pub const VEL_RANGE: usize = 44;
+    pub const STARTLOOP_ADDRS_COARSE_OFFSET: usize = 45;
+    pub const INITIAL_ATTENUATION: usize = 48;
+    pub const ENDLOOP_ADDRS_COARSE_OFFSET: usize = 50;
+    pub const COARSE_TUNE: usize = 51;
+    pub const FINE_TUNE: usize = 52;
+    pub const SAMPLE_ID: usize = 53;
+    pub const SAMPLE_MODES: usize = 54;
+    pub const SCALE_TUNING: usize = 56;
+    pub const OVERRIDING_ROOT_KEY: usize = 58;
+    pub const COUNT: usize = 61;
+}
+
+/// The default value of volume envelope times, in timecents (about 1ms)
+const DEFAULT_VOL_ENV_TIMECENTS: i32 = -12000;
+
+/// Generators which presets may add to the values of the instruments they contain, with the
+/// default values the offsets are added to when instruments don't set them
+const PRESET_ADDITIVE_GENERATORS: &[(usize, i32)] = &[
+    (generator::ATTACK_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS),
+    (generator::HOLD_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS),
+    (generator::DECAY_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS),
+    (generator::SUSTAIN_VOL_ENV, 0),
+    (generator::RELEASE_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS),
+    (generator::INITIAL_ATTENUATION, 0),
+    (generator::COARSE_TUNE, 0),
+    (generator::FINE_TUNE, 0),
+    (generator::SCALE_TUNING, 100),
+];
+
+/// The generator values of a zone. Values are stored as raw 16-bit amounts, which are
+/// interpreted as signed numbers or as ranges depending on the generator.
+#[derive(Clone, Copy)]
+struct Generators([Option<u16>; generator::COUNT]);
+
+impl Generators {
+    fn new() -> Self {
+        Self([None; generator::COUNT])
+    }
+
+    /// Generators set in `self` take precedence over those in `defaults`
+    fn or(self, defaults: Self) -> Self {
+        let mut output = self;
+        for (value, default) in output.0.iter_mut().zip(defaults.0) {
+            *value = value.or(default);
+        }
+        output
+    }
+
+    fn get(&self, generator: usize) -> Option<u16> {
+        self.0[generator]
+    }
+
+    fn i32(&self, generator: usize, default: i32) -> i32 {
+        self.get(generator)
+            .map_or(default, |value| value as i16 as i32)
+    }
+
+    fn range(&self, generator: usize) -> (u8, u8) {
+        self.get(generator)
+            .map_or((0, 127), |value| ((value & 0xFF) as u8, (value >> 8) as u8))
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if n > self.data.len() {
+            anyhow::bail!("Unexpected end of data");
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn name(&mut self) -> anyhow::Result<String> {
+        let raw = self.take(20)?;
+        let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+        Ok(String::from_utf8_lossy(&raw[..len]).trim().to_string())
+    }
+
+    /// Read a RIFF chunk, returning its id and contents
+    fn chunk(&mut self) -> anyhow::Result<(&'a [u8], &'a [u8])> {
+        let id = self.take(4)?;
+        let size = self.u32()? as usize;
+        let data = self.take(size.min(self.data.len()))?;
+        // Chunks are padded to an even number of bytes
+        if size % 2 == 1 && !self.data.is_empty() {
+            self.take(1)?;
+        }
+        Ok((id, data))
+    }
+
+    /// Read the records of a fixed-size array chunk
+    fn records<T>(
+        data: &'a [u8],
+        record_size: usize,
+        mut f: impl FnMut(&mut Reader<'a>) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Vec<T>> {
+        data.chunks_exact(record_size)
+            .map(|record| f(&mut Reader { data: record }))
+            .collect()
+    }
+}
+
+struct PresetHeader {
+    name: String,
+    preset: u16,
+    bank: u16,
+    bag_index: usize,
+}
+
+struct InstrumentHeader {
+    bag_index: usize,
+}
+
+struct SampleHeader {
+    start: u32,
+    end: u32,
+    start_loop: u32,
+    end_loop: u32,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+    sample_type: u16,
+}
+
+/// Sample types other than mono are one channel of a stereo sample
+const SAMPLE_TYPE_MONO: u16 = 1;
+/// ROM samples aren't stored in the file
+const SAMPLE_TYPE_ROM: u16 = 0x8000;
+
+/// The generators of each zone of a preset or instrument. The first zone is a global zone
+/// providing defaults for the others if it lacks the generator which terminates other zones.
+fn zones(
+    bag_range: std::ops::Range<usize>,
+    bag_generator_indices: &[usize],
+    generators: &[(u16, u16)],
+    terminal_generator: usize,
+) -> anyhow::Result<(Generators, Vec<Generators>)> {
+    let mut global = Generators::new();
+    let mut zones = Vec::new();
+    for bag in bag_range {
+        let (Some(&first), Some(&last)) = (
+            bag_generator_indices.get(bag),
+            bag_generator_indices.get(bag + 1),
+        ) else {
+            anyhow::bail!("Zone index {} is out of range", bag);
+        };
+        let mut zone = Generators::new();
+        for &(operator, amount) in generators.get(first..last).unwrap_or(&[]) {
+            if let Some(value) = zone.0.get_mut(operator as usize) {
+                *value = Some(amount);
+            }
+        }
+        if zone.get(terminal_generator).is_some() {
+            zones.push(zone);
+        } else if zones.is_empty() {
+            global = zone;
+        }
+    }
+    Ok((global, zones))
+}
+
+fn intersect_ranges((a_lo, a_hi): (u8, u8), (b_lo, b_hi): (u8, u8)) -> Option<(u8, u8)> {
+    let lo = a_lo.max(b_lo);
+    let hi = a_hi.min(b_hi);
+    if lo <= hi {
+        Some((lo, hi))
+    } else {
+        None
+    }
+}
+
+fn timecents_to_s(timecents: i32) -> f64 {
+    (timecents as f64 / 1200.0).exp2()
+}
+
+/// A preset from a SoundFont, made of samples split across keys and velocities
+pub struct SoundFontPreset {
+    pub name: String,
+    pub bank: u16,
+    pub preset: u16,
+    pub regions: Vec<SampleRegion>,
+}
+
+impl SoundFontPreset {
+    pub fn instrument(&self) -> MultiSampleInstrument {
+        MultiSampleInstrument::new(self.regions.clone())
+    }
+}
+
+/// The presets of a SoundFont 2 bank
+pub struct SoundFont {
+    pub presets: Vec<SoundFontPreset>,
+}
+
+impl SoundFont {
+    /// Find a preset by its bank and preset (program) number
+    pub fn preset(&self, bank: u16, preset: u16) -> Option<&SoundFontPreset> {
+        self.presets
+            .iter()
+            .find(|p| p.bank == bank && p.preset == preset)
+    }
+
+    pub fn preset_by_name(&self, name: &str) -> Option<&SoundFontPreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    /// An instrument playing a preset, or `None` if the SoundFont has no such preset
+    pub fn instrument(&self, bank: u16, preset: u16) -> Option<MultiSampleInstrument> {
+        self.preset(bank, preset).map(SoundFontPreset::instrument)
+    }
+}
+
+/// Read the samples from the `sdta` chunk as numbers between -1 and 1
+fn parse_sample_data(sdta: &[u8]) -> anyhow::Result<Vec<f64>> {
+    let mut reader = Reader { data: sdta };
+    let mut smpl = None;
+    let mut sm24 = None;
+    while !reader.data.is_empty() {
+        match reader.chunk()? {
+            (b"smpl", data) => smpl = Some(data),
+            (b"sm24", data) => sm24 = Some(data),
+            _ => (),
+        }
+    }
+    let Some(smpl) = smpl else {
+        anyhow::bail!("No sample data");
+    };
+    let samples = smpl
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]));
+    Ok(match sm24 {
+        // 24-bit samples store their least significant bytes in a separate chunk
+        Some(sm24) if sm24.len() * 2 >= smpl.len() => samples
+            .zip(sm24.iter())
+            .map(|(high, &low)| (((high as i32) << 8) | low as i32) as f64 / (1 << 23) as f64)
+            .collect(),
+        _ => samples
+            .map(|sample| sample as f64 / (1 << 15) as f64)
+            .collect(),
+    })
+}
+
+/// Parse a SoundFont 2 file
+pub fn parse_sf2(data: &[u8]) -> anyhow::Result<SoundFont> {
+    let mut reader = Reader { data };
+    let (riff, riff_data) = reader.chunk()?;
+    let mut reader = Reader { data: riff_data };
+    if riff != b"RIFF" || reader.take(4)? != b"sfbk" {
+        anyhow::bail!("Not a SoundFont 2 file");
+    }
+    let mut sample_data = None;
+    let mut pdta = HashMap::new();
+    while !reader.data.is_empty() {
+        let (id, data) = reader.chunk()?;
+        if id != b"LIST" {
+            continue;
+        }
+        let mut list = Reader { data };
+        match list.take(4)? {
+            b"sdta" => sample_data = Some(parse_sample_data(list.data)?),
+            b"pdta" => {
+                while !list.data.is_empty() {
+                    let (id, data) = list.chunk()?;
+                    pdta.insert(id, data);
+                }
+            }
+            _ => (),
+        }
+    }
+    let Some(sample_data) = sample_data else {
+        anyhow::bail!("Missing sample data");
+    };
+    let chunk = |id: &[u8]| {
+        pdta.get(id)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Missing {} chunk", String::from_utf8_lossy(id)))
+    };
+    let preset_headers = Reader::records(chunk(b"phdr")?, 38, |r| {
+        let name = r.name()?;
+        let preset = r.u16()?;
+        let bank = r.u16()?;
+        let bag_index = r.u16()? as usize;
+        Ok(PresetHeader {
+            name,
+            preset,
+            bank,
+            bag_index,
+        })
+    })?;
+    let bag = |r: &mut Reader| Ok(r.u16()? as usize);
+    let generator = |r: &mut Reader| Ok((r.u16()?, r.u16()?));
+    let preset_bags = Reader::records(chunk(b"pbag")?, 4, bag)?;
+    let preset_generators = Reader::records(chunk(b"pgen")?, 4, generator)?;
+    let instrument_headers = Reader::records(chunk(b"inst")?, 22, |r| {
+        r.name()?;
+        Ok(InstrumentHeader {
+            bag_index: r.u16()? as usize,
+        })
+    })?;
+    let instrument_bags = Reader::records(chunk(b"ibag")?, 4, bag)?;
+    let instrument_generators = Reader::records(chunk(b"igen")?, 4, generator)?;
+    let sample_headers = Reader::records(chunk(b"shdr")?, 46, |r| {
+        r.name()?;
+        let start = r.u32()?;
+        let end = r.u32()?;
+        let start_loop = r.u32()?;
+        let end_loop = r.u32()?;
+        let sample_rate = r.u32()?;
+        let original_pitch = r.u8()?;
+        let pitch_correction = r.u8()? as i8;
+        let _sample_link = r.u16()?;
+        let sample_type = r.u16()?;
+        Ok(SampleHeader {
+            start,
+            end,
+            start_loop,
+            end_loop,
+            sample_rate,
+            original_pitch,
+            pitch_correction,
+            sample_type,
+        })
+    })?;
+    // Samples are read directly from the sample data of the whole file, which is shared
+    // between all samples with the same sample rate
+    let mut samples_by_rate = HashMap::<u32, Sample>::new();
+    let mut presets = Vec::new();
+    // The last header of each list marks the end of the previous one
+    for (header, next_header) in preset_headers.iter().zip(preset_headers.iter().skip(1)) {
+        let (preset_global, preset_zones) = zones(
+            header.bag_index..next_header.bag_index,
+            &preset_bags,
+            &preset_generators,
+            generator::INSTRUMENT,
+        )?;
+        let mut regions = Vec::new();
+        for preset_zone in preset_zones {
+            let preset_zone = preset_zone.or(preset_global);
+            let instrument_index = preset_zone.get(generator::INSTRUMENT).unwrap_or(0) as usize;
+            let (Some(instrument), Some(next_instrument)) = (
+                instrument_headers.get(instrument_index),
+                instrument_headers.get(instrument_index + 1),
+            ) else {
+                anyhow::bail!("Instrument index {} is out of range", instrument_index);
+            };
+            let (instrument_global, instrument_zones) = zones(
+                instrument.bag_index..next_instrument.bag_index,
+                &instrument_bags,
+                &instrument_generators,
+                generator::SAMPLE_ID,
+            )?;
+            for instrument_zone in instrument_zones {
+                let mut zone = instrument_zone.or(instrument_global);
+                let (Some(key_range), Some(velocity_range)) = (
+                    intersect_ranges(
+                        zone.range(generator::KEY_RANGE),
+                        preset_zone.range(generator::KEY_RANGE),
+                    ),
+                    intersect_ranges(
+                        zone.range(generator::VEL_RANGE),
+                        preset_zone.range(generator::VEL_RANGE),
+                    ),
+                ) else {
+                    continue;
+                };
+                for &(generator, default) in PRESET_ADDITIVE_GENERATORS {
+                    if let Some(offset) = preset_zone.get(generator) {
+                        let value = zone.i32(generator, default) + (offset as i16 as i32);
+                        zone.0[generator] =
+                            Some(value.clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16);
+                    }
+                }
+                let sample_index = zone.get(generator::SAMPLE_ID).unwrap_or(0) as usize;
+                let Some(sample_header) = sample_headers.get(sample_index) else {
+                    anyhow::bail!("Sample index {} is out of range", sample_index);
+                };
+                if sample_header.sample_type & SAMPLE_TYPE_ROM != 0 {
+                    continue;
+                }
+                let sample = samples_by_rate
+                    .entry(sample_header.sample_rate)
+                    .or_insert_with(|| {
+                        Sample::new(sample_data.clone())
+                            .with_sample_rate_hz(sample_header.sample_rate as f64)
+                    });
+                let address = |base: u32, fine: usize, coarse: usize| {
+                    let offset = zone.i32(fine, 0) + (zone.i32(coarse, 0) * 32768);
+                    (base as i64 + offset as i64).clamp(0, sample_data.len() as i64) as usize
+                };
+                let start = address(
+                    sample_header.start,
+                    generator::START_ADDRS_OFFSET,
+                    generator::START_ADDRS_COARSE_OFFSET,
+                );
+                let end = address(
+                    sample_header.end,
+                    generator::END_ADDRS_OFFSET,
+                    generator::END_ADDRS_COARSE_OFFSET,
+                );
+                let mode = match zone.i32(generator::SAMPLE_MODES, 0) {
+                    1 => LoopMode::Continuous,
+                    3 => LoopMode::Sustain,
+                    _ => LoopMode::NoLoop,
+                };
+                let mut region = SampleRegion::new(sample);
+                region.lo_key = key_range.0;
+                region.hi_key = key_range.1;
+                region.lo_velocity = velocity_range.0;
+                region.hi_velocity = velocity_range.1;
+                let root_key = zone.i32(generator::OVERRIDING_ROOT_KEY, -1);
+                region.root_key = if (0..=127).contains(&root_key) {
+                    root_key as u8
+                } else if sample_header.original_pitch <= 127 {
+                    sample_header.original_pitch
+                } else {
+                    60
+                };
+                region.tune_cents = (zone.i32(generator::COARSE_TUNE, 0) * 100) as f64
+                    + zone.i32(generator::FINE_TUNE, 0) as f64
+                    + sample_header.pitch_correction as f64;
+                region.key_tracking_cents = zone.i32(generator::SCALE_TUNING, 100) as f64;
+                region.volume_db =
+                    -(zone.i32(generator::INITIAL_ATTENUATION, 0).max(0) as f64 / 10.0);
+                if sample_header.sample_type & !SAMPLE_TYPE_ROM != SAMPLE_TYPE_MONO {
+                    // Each channel of a stereo sample has its own zone. Both are played and
+                    // mixed down to mono, so halve their volume.
+                    region.volume_db -= 6.0;
+                }
+                region.start = start;
+                region.end = Some(end);
+                region.sample_loop = SampleLoop {
+                    mode,
+                    start: address(
+                        sample_header.start_loop,
+                        generator::STARTLOOP_ADDRS_OFFSET,
+                        generator::STARTLOOP_ADDRS_COARSE_OFFSET,
+                    ),
+                    end: address(
+                        sample_header.end_loop,
+                        generator::ENDLOOP_ADDRS_OFFSET,
+                        generator::ENDLOOP_ADDRS_COARSE_OFFSET,
+                    ),
+                    crossfade: 0,
+                };
+                region.attack_s =
+                    timecents_to_s(zone.i32(generator::ATTACK_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS));
+                region.hold_s =
+                    timecents_to_s(zone.i32(generator::HOLD_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS));
+                region.decay_s =
+                    timecents_to_s(zone.i32(generator::DECAY_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS));
+                // Sustain is an attenuation in centibels
+                region.sustain_01 = 10f64
+                    .powf(-(zone.i32(generator::SUSTAIN_VOL_ENV, 0).clamp(0, 1440) as f64) / 200.0);
+                region.release_s =
+                    timecents_to_s(zone.i32(generator::RELEASE_VOL_ENV, DEFAULT_VOL_ENV_TIMECENTS));
+                regions.push(region);
+            }
+        }
+        presets.push(SoundFontPreset {
+            name: header.name.clone(),
+            bank: header.bank,
+            preset: header.preset,
+            regions,
+        });
+    }
+    Ok(SoundFont { presets })
+}
+
+/// Load a SoundFont 2 bank
+pub fn read_sf2(path: impl AsRef<Path>) -> anyhow::Result<SoundFont> {
+    parse_sf2(&fs::read(path)?)
+}
+
+#[test]
+fn test_parse_sf2() {
+    fn chunk(id: &[u8], data: Vec<u8>) -> Vec<u8> {
+        let mut output = id.to_vec();
+        output.extend((data.len() as u32).to_le_bytes());
+        output.extend(&data);
+        output
+    }
+    fn list(kind: &[u8], chunks: Vec<Vec<u8>>) -> Vec<u8> {
+        chunk(
+            b"LIST",
+            [kind.to_vec()]
+                .into_iter()
+                .chain(chunks)
+                .flatten()
+                .collect(),
+        )
+    }
+    fn name(name: &str) -> Vec<u8> {
+        let mut output = name.as_bytes().to_vec();
+        output.resize(20, 0);
+        output
+    }
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+    // Two samples of 100 frames each followed by 46 frames of silence
+    let smpl = [
+        vec![8192i16; 100],
+        vec![0; 46],
+        vec![-8192; 100],
+        vec![0; 46],
+    ]
+    .concat();
+    let sdta = list(
+        b"sdta",
+        vec![chunk(
+            b"smpl",
+            smpl.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        )],
+    );
+    let mut phdr = Vec::new();
+    for (preset_name, preset, bag_index) in [("Test", 5, 0), ("EOP", 0, 2)] {
+        phdr.extend(name(preset_name));
+        phdr.extend(u16s(&[preset, 0, bag_index]));
+        phdr.extend([0; 12]);
+    }
+    // A global zone with 6dB of attenuation and a release 1200 timecents longer than the
+    // default, then a zone playing instrument 0
+    let pbag = u16s(&[0, 0, 2, 0, 4, 0]);
+    let pgen = u16s(&[48, 60, 38, 1200, 43, 0x7F00, 41, 0]);
+    let mut inst = Vec::new();
+    inst.extend(name("Inst"));
+    inst.extend(u16s(&[0]));
+    inst.extend(name("EOI"));
+    inst.extend(u16s(&[2]));
+    // Sample 0 below middle C, and sample 1 looping from middle C up with a root key of 72
+    let ibag = u16s(&[0, 0, 2, 0, 6, 0]);
+    let igen = u16s(&[43, 0x3B00, 53, 0, 43, 0x7F3C, 54, 1, 58, 72, 53, 1]);
+    let mut shdr = Vec::new();
+    for (sample_name, start, end, start_loop, end_loop) in [
+        ("S0", 0u32, 100u32, 0u32, 0u32),
+        ("S1", 146, 246, 156, 196),
+        ("EOS", 0, 0, 0, 0),
+    ] {
+        shdr.extend(name(sample_name));
+        for x in [start, end, start_loop, end_loop, 22050] {
+            shdr.extend(x.to_le_bytes());
+        }
+        shdr.extend([60, 0]);
+        shdr.extend(u16s(&[0, SAMPLE_TYPE_MONO]));
+    }
+    let pdta = list(
+        b"pdta",
+        vec![
+            chunk(b"phdr", phdr),
+            chunk(b"pbag", pbag),
+            chunk(b"pmod", vec![0; 10]),
+            chunk(b"pgen", pgen),
+            chunk(b"inst", inst),
+            chunk(b"ibag", ibag),
+            chunk(b"imod", vec![0; 10]),
+            chunk(b"igen", igen),
+            chunk(b"shdr", shdr),
+        ],
+    );
+    let riff = chunk(b"RIFF", [b"sfbk".to_vec(), sdta, pdta].concat());
+    let sound_font = parse_sf2(&riff).unwrap();
+    assert_eq!(sound_font.presets.len(), 1);
+    let preset = sound_font.preset(0, 5).unwrap();
+    assert_eq!(preset.name, "Test");
+    let [low, high] = preset.regions.as_slice() else {
+        panic!("Expected 2 regions");
+    };
+    assert_eq!((low.lo_key, low.hi_key, low.root_key), (0, 59, 60));
+    assert_eq!((high.lo_key, high.hi_key, high.root_key), (60, 127, 72));
+    assert_eq!((low.start, low.end), (0, Some(100)));
+    assert_eq!(low.sample_loop.mode, LoopMode::NoLoop);
+    assert_eq!(high.sample_loop.mode, LoopMode::Continuous);
+    assert_eq!((high.sample_loop.start, high.sample_loop.end), (156, 196));
+    assert_eq!(low.sample.sample_rate_hz(), Some(22050.0));
+    for region in [low, high] {
+        assert_eq!(region.volume_db, -6.0);
+        assert_eq!(region.key_tracking_cents, 100.0);
+        // The preset's release is added to the default of -12000 timecents
+        assert!((region.release_s - (-10800.0f64 / 1200.0).exp2()).abs() < 1e-9);
+        assert!(region.attack_s < 0.002);
+    }
+}
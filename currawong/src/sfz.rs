@@ -0,0 +1,404 @@
+use crate::sample::read_wav;
+use currawong_core::{
+    multisample::{MultiSampleInstrument, SampleRegion},
+    sampler::{LoopMode, Sample, SampleLoop},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The opcodes in effect for a region, after inheriting from the headers enclosing it
+type Opcodes = HashMap<String, String>;
+
+/// Opcodes set by each level of header in the order they're inherited
+#[derive(Default)]
+struct Scopes {
+    control: Opcodes,
+    global: Opcodes,
+    master: Opcodes,
+    group: Opcodes,
+}
+
+impl Scopes {
+    fn region_opcodes(&self, region: Opcodes) -> Opcodes {
+        let mut opcodes = self.control.clone();
+        for scope in [&self.global, &self.master, &self.group] {
+            opcodes.extend(scope.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        opcodes.extend(region);
+        opcodes
+    }
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    loop {
+        match (rest.find("//"), rest.find("/*")) {
+            (Some(line), block) if block.is_none_or(|block| line < block) => {
+                output.push_str(&rest[..line]);
+                rest = rest[line..]
+                    .find('\n')
+                    .map_or("", |end| &rest[line + end..]);
+            }
+            (_, Some(block)) => {
+                output.push_str(&rest[..block]);
+                output.push(' ');
+                rest = rest[block..]
+                    .find("*/")
+                    .map_or("", |end| &rest[block + end + 2..]);
+            }
+            _ => {
+                output.push_str(rest);
+                return output;
+            }
+        }
+    }
+}
+
+/// Replace `#define $NAME value` variables and remove the definitions
+fn expand_defines(text: &str) -> String {
+    let mut defines = Vec::<(String, String)>::new();
+    let mut output = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(definition) = trimmed.strip_prefix("#define") {
+            // The value is the rest of the line, so it may contain spaces
+            if let Some((name, value)) = definition.trim().split_once(char::is_whitespace) {
+                defines.push((name.to_string(), value.trim().to_string()));
+                // Longer names first so that `$A` doesn't replace the start of `$AB`
+                defines.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+            }
+            continue;
+        }
+        let mut line = line.to_string();
+        for (name, value) in defines.iter() {
+            line = line.replace(name.as_str(), value);
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Header(String),
+    Opcode(String, String),
+}
+
+/// Split the text into headers and opcodes. Opcode values end at the next opcode or header,
+/// so they may contain spaces, as is common in sample paths.
+fn tokenize(text: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(header) = rest.strip_prefix('<') {
+            let Some(end) = header.find('>') else {
+                anyhow::bail!("Unterminated header: {:?}", header);
+            };
+            tokens.push(Token::Header(header[..end].trim().to_string()));
+            rest = &header[end + 1..];
+            continue;
+        }
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let Some(eq) = rest[..word_end].find('=') else {
+            anyhow::bail!("Expected an opcode but got {:?}", &rest[..word_end]);
+        };
+        let name = rest[..eq].to_string();
+        let value_start = &rest[eq + 1..];
+        // The value runs up to the start of the next word containing '=' or the next header
+        let mut value_end = value_start.len();
+        let mut offset = 0;
+        for word in value_start.split_inclusive(char::is_whitespace) {
+            if offset > 0 && (word.contains('=') || word.starts_with('<')) {
+                value_end = offset;
+                break;
+            }
+            if let Some(header) = word.find('<') {
+                value_end = offset + header;
+                break;
+            }
+            offset += word.len();
+        }
+        tokens.push(Token::Opcode(
+            name,
+            value_start[..value_end].trim().to_string(),
+        ));
+        rest = &value_start[value_end..];
+    }
+    Ok(tokens)
+}
+
+/// Parse a midi note number or a note name such as "c4", "f#3" or "eb-1", where c4 is 60
+fn parse_note(value: &str) -> anyhow::Result<i32> {
+    if let Ok(index) = value.parse::<i32>() {
+        return Ok(index);
+    }
+    let lower = value.to_lowercase();
+    let mut chars = lower.chars();
+    let semitone = match chars.next() {
+        Some('c') => 0,
+        Some('d') => 2,
+        Some('e') => 4,
+        Some('f') => 5,
+        Some('g') => 7,
+        Some('a') => 9,
+        Some('b') => 11,
+        _ => anyhow::bail!("Invalid note: {:?}", value),
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = if let Some(octave) = rest.strip_prefix('#') {
+        (1, octave)
+    } else if let Some(octave) = rest.strip_prefix('b') {
+        (-1, octave)
+    } else {
+        (0, rest)
+    };
+    let octave = octave
+        .parse::<i32>()
+        .map_err(|_| anyhow::anyhow!("Invalid note: {:?}", value))?;
+    Ok(((octave + 1) * 12) + semitone + accidental)
+}
+
+struct RegionParser<'a> {
+    opcodes: &'a Opcodes,
+}
+
+impl<'a> RegionParser<'a> {
+    fn get(&self, names: &[&str]) -> Option<&'a str> {
+        names
+            .iter()
+            .find_map(|name| self.opcodes.get(*name))
+            .map(|value| value.as_str())
+    }
+
+    fn f64(&self, names: &[&str]) -> anyhow::Result<Option<f64>> {
+        self.get(names)
+            .map(|value| {
+                value.parse::<f64>().map_err(|_| {
+                    anyhow::anyhow!("Expected a number for {} but got {:?}", names[0], value)
+                })
+            })
+            .transpose()
+    }
+
+    fn usize(&self, names: &[&str]) -> anyhow::Result<Option<usize>> {
+        Ok(self.f64(names)?.map(|value| value.max(0.0) as usize))
+    }
+
+    /// Parses a note and applies the note and octave offsets from the control header
+    fn key(&self, names: &[&str]) -> anyhow::Result<Option<u8>> {
+        let Some(value) = self.get(names) else {
+            return Ok(None);
+        };
+        let offset = self.f64(&["note_offset"])?.unwrap_or(0.0) as i32
+            + (self.f64(&["octave_offset"])?.unwrap_or(0.0) as i32 * 12);
+        Ok(Some((parse_note(value)? + offset).clamp(0, 127) as u8))
+    }
+
+    fn velocity(&self, names: &[&str]) -> anyhow::Result<Option<u8>> {
+        Ok(self.f64(names)?.map(|value| value.clamp(0.0, 127.0) as u8))
+    }
+}
+
+fn load_sample(path: &Path, sample_cache: &mut HashMap<PathBuf, Sample>) -> anyhow::Result<Sample> {
+    if let Some(sample) = sample_cache.get(path) {
+        return Ok(sample.clone());
+    }
+    let sample = read_wav(path)
+        .map_err(|e| anyhow::anyhow!("Failed to load sample {}: {}", path.display(), e))?;
+    sample_cache.insert(path.to_path_buf(), sample.clone());
+    Ok(sample)
+}
+
+fn region_from_opcodes(
+    opcodes: &Opcodes,
+    base_dir: &Path,
+    sample_cache: &mut HashMap<PathBuf, Sample>,
+) -> anyhow::Result<Option<SampleRegion>> {
+    let parser = RegionParser { opcodes };
+    if let Some(trigger) = parser.get(&["trigger"]) {
+        // Release triggers aren't supported. Other triggers play like regular notes.
+        if trigger.starts_with("release") {
+            return Ok(None);
+        }
+    }
+    let Some(sample_name) = parser.get(&["sample"]) else {
+        return Ok(None);
+    };
+    if sample_name.starts_with('*') {
+        anyhow::bail!(
+            "Generated samples such as {:?} aren't supported",
+            sample_name
+        );
+    }
+    let default_path = parser.get(&["default_path"]).unwrap_or("");
+    let relative_path = format!("{}{}", default_path, sample_name).replace('\\', "/");
+    let sample = load_sample(&base_dir.join(relative_path), sample_cache)?;
+    let mut region = SampleRegion::new(&sample);
+    if let Some(key) = parser.key(&["key"])? {
+        region.lo_key = key;
+        region.hi_key = key;
+        region.root_key = key;
+    }
+    if let Some(lo_key) = parser.key(&["lokey"])? {
+        region.lo_key = lo_key;
+    }
+    if let Some(hi_key) = parser.key(&["hikey"])? {
+        region.hi_key = hi_key;
+    }
+    if let Some(root_key) = parser.key(&["pitch_keycenter"])? {
+        region.root_key = root_key;
+    }
+    if let Some(lo_velocity) = parser.velocity(&["lovel"])? {
+        region.lo_velocity = lo_velocity;
+    }
+    if let Some(hi_velocity) = parser.velocity(&["hivel"])? {
+        region.hi_velocity = hi_velocity;
+    }
+    region.tune_cents = parser.f64(&["tune"])?.unwrap_or(0.0)
+        + (parser.f64(&["transpose"])?.unwrap_or(0.0) * 100.0);
+    if let Some(key_tracking_cents) = parser.f64(&["pitch_keytrack"])? {
+        region.key_tracking_cents = key_tracking_cents;
+    }
+    region.volume_db = parser.f64(&["volume"])?.unwrap_or(0.0);
+    if let Some(velocity_tracking_percent) = parser.f64(&["amp_veltrack"])? {
+        region.velocity_tracking_01 = velocity_tracking_percent / 100.0;
+    }
+    region.start = parser.usize(&["offset"])?.unwrap_or(0);
+    // Ends of samples and loops are inclusive in SFZ files
+    region.end = parser.usize(&["end"])?.map(|end| end + 1);
+    let loop_start = parser.usize(&["loop_start", "loopstart"])?;
+    let loop_end = parser.usize(&["loop_end", "loopend"])?;
+    let mode = match parser.get(&["loop_mode", "loopmode"]) {
+        Some("no_loop") => LoopMode::NoLoop,
+        Some("one_shot") => {
+            region.one_shot = true;
+            LoopMode::NoLoop
+        }
+        Some("loop_continuous") => LoopMode::Continuous,
+        Some("loop_sustain") => LoopMode::Sustain,
+        Some(other) => anyhow::bail!("Unknown loop mode: {:?}", other),
+        // Samples with loop points loop by default
+        None if loop_end.is_some() => LoopMode::Continuous,
+        None => LoopMode::NoLoop,
+    };
+    let sample_rate_hz = sample.sample_rate_hz().unwrap_or(44100.0);
+    region.sample_loop = SampleLoop {
+        mode,
+        start: loop_start.unwrap_or(0),
+        end: loop_end.map_or(sample.len(), |end| end + 1),
+        crossfade: (parser.f64(&["loop_crossfade"])?.unwrap_or(0.0) * sample_rate_hz) as usize,
+    };
+    region.attack_s = parser.f64(&["ampeg_attack"])?.unwrap_or(0.0);
+    region.hold_s = parser.f64(&["ampeg_hold"])?.unwrap_or(0.0);
+    region.decay_s = parser.f64(&["ampeg_decay"])?.unwrap_or(0.0);
+    region.sustain_01 = parser.f64(&["ampeg_sustain"])?.unwrap_or(100.0) / 100.0;
+    region.release_s = parser.f64(&["ampeg_release"])?.unwrap_or(0.001);
+    region.round_robin_length = parser.usize(&["seq_length"])?.unwrap_or(1);
+    region.round_robin_position = parser.usize(&["seq_position"])?.unwrap_or(1);
+    Ok(Some(region))
+}
+
+/// Parse the text of an SFZ file, loading samples relative to `base_dir`
+pub fn parse_sfz(text: &str, base_dir: impl AsRef<Path>) -> anyhow::Result<MultiSampleInstrument> {
+    let base_dir = base_dir.as_ref();
+    let text = expand_defines(&strip_comments(text));
+    let mut scopes = Scopes::default();
+    let mut sample_cache = HashMap::new();
+    let mut regions = Vec::new();
+    // The opcodes of the header currently being parsed, and the header's name
+    let mut current: Option<(String, Opcodes)> = None;
+    let mut finish_header = |current: Option<(String, Opcodes)>,
+                             scopes: &mut Scopes|
+     -> anyhow::Result<()> {
+        let Some((header, opcodes)) = current else {
+            return Ok(());
+        };
+        match header.as_str() {
+            "control" => scopes.control.extend(opcodes),
+            "global" => scopes.global = opcodes,
+            "master" => scopes.master = opcodes,
+            "group" => scopes.group = opcodes,
+            "region" => {
+                let opcodes = scopes.region_opcodes(opcodes);
+                if let Some(region) = region_from_opcodes(&opcodes, base_dir, &mut sample_cache)? {
+                    regions.push(region);
+                }
+            }
+            // Effects, curves and other headers aren't supported
+            _ => (),
+        }
+        Ok(())
+    };
+    for token in tokenize(&text)? {
+        match token {
+            Token::Header(header) => {
+                finish_header(current.take(), &mut scopes)?;
+                // Starting a header clears the headers it contains
+                match header.as_str() {
+                    "global" => {
+                        scopes.master.clear();
+                        scopes.group.clear();
+                    }
+                    "master" => scopes.group.clear(),
+                    _ => (),
+                }
+                current = Some((header, Opcodes::new()));
+            }
+            Token::Opcode(name, value) => match current.as_mut() {
+                Some((_, opcodes)) => {
+                    opcodes.insert(name, value);
+                }
+                None => anyhow::bail!("Opcode {:?} appears before any header", name),
+            },
+        }
+    }
+    finish_header(current.take(), &mut scopes)?;
+    if regions.is_empty() {
+        anyhow::bail!("No playable regions");
+    }
+    Ok(MultiSampleInstrument::new(regions))
+}
+
+/// Load an SFZ instrument. Samples must be WAV files.
+pub fn read_sfz(path: impl AsRef<Path>) -> anyhow::Result<MultiSampleInstrument> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    parse_sfz(&text, base_dir)
+}
+
+#[test]
+fn test_parse_sfz_text() {
+    let text = expand_defines(&strip_comments(
+        "#define $DIR My Samples // comment\n\
+         <region> sample=$DIR/piano C4 soft.wav lokey=eb-1 hikey=f#3<group>\n\
+         key=60 /* block\ncomment */ tune=-5",
+    ));
+    let opcode = |name: &str, value: &str| Token::Opcode(name.to_string(), value.to_string());
+    assert_eq!(
+        tokenize(&text).unwrap(),
+        vec![
+            Token::Header("region".to_string()),
+            opcode("sample", "My Samples/piano C4 soft.wav"),
+            opcode("lokey", "eb-1"),
+            opcode("hikey", "f#3"),
+            Token::Header("group".to_string()),
+            opcode("key", "60"),
+            opcode("tune", "-5"),
+        ]
+    );
+    assert_eq!(parse_note("eb-1").unwrap(), 3);
+    assert_eq!(parse_note("f#3").unwrap(), 54);
+    assert_eq!(parse_note("C4").unwrap(), 60);
+    assert_eq!(parse_note("60").unwrap(), 60);
+    assert!(parse_note("h2").is_err());
+}
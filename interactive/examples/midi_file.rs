@@ -7,6 +7,7 @@ fn voice(
         key_down,
         key_press,
         velocity_01,
+        ..
     }: VoiceDesc,
 ) -> Sf64 {
     let note_freq_hz = note.freq_hz();